@@ -2,6 +2,7 @@ use fbas_reward_distributor::*;
 
 use env_logger::Env;
 use fbas_analyzer::Fbas;
+use indicatif::ProgressIterator;
 use lazy_static::lazy_static;
 use log::{info, trace};
 use par_map::ParMap;
@@ -41,7 +42,13 @@ struct Cli {
     #[structopt(subcommand)]
     fbas_type: FbasType,
 
+    /// Format of the output, csv or json. Updating existing output files requires csv.
+    #[structopt(long = "format", default_value = "csv")]
+    format: OutputFormat,
+
     /// Update output file with missing results (doesn't repeat analyses for existing lines).
+    /// New results are appended to the file as soon as they are available so that an interrupted
+    /// run can be resumed by running the same command again.
     #[structopt(short = "u", long = "update")]
     update: bool,
 
@@ -58,6 +65,11 @@ struct Cli {
     #[structopt(long = "no-quorum-intersection")]
     dont_check_for_qi: bool,
 
+    /// Decimal places the mean absolute percentage errors are truncated to. Raise it to tell
+    /// apart the tiny errors of large sample budgets.
+    #[structopt(long = "pctg-precision", default_value = "3")]
+    pctg_precision: u32,
+
     #[structopt(long = "log", short = "l", default_value = "info")]
     log_level: String,
 }
@@ -72,6 +84,12 @@ fn main() -> Result<(), Box<dyn Error>> {
     let fbas_type = args.fbas_type;
     let inputs: Vec<InputDataPoint> =
         generate_inputs(args.max_top_tier_size, args.runs, fbas_type.clone());
+    if args.update && args.format != OutputFormat::Csv {
+        return Err(Box::new(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Updating existing results is only supported for CSV output.",
+        )));
+    }
     let existing_outputs = if args.update {
         load_existing_outputs(&args.output_path)?
     } else {
@@ -80,14 +98,20 @@ fn main() -> Result<(), Box<dyn Error>> {
     let tasks = make_sorted_tasklist(inputs, existing_outputs);
 
     let qi_check = !args.dont_check_for_qi;
-    let output_iterator = bulk_do(tasks, args.jobs, fbas_type.clone(), qi_check);
+    let output_iterator = bulk_do(
+        tasks,
+        args.jobs,
+        fbas_type.clone(),
+        qi_check,
+        args.pctg_precision,
+    );
     info!(
         "Starting measurements for {:?} like FBAS with upto {} nodes.\n
              Performing {} iterations per FBAS.",
         fbas_type, args.max_top_tier_size, args.runs
     );
 
-    write_csv(output_iterator, &args.output_path, args.update)?;
+    write_output(output_iterator, &args.output_path, args.update, args.format)?;
     Ok(())
 }
 
@@ -109,7 +133,11 @@ fn generate_inputs(
 fn load_existing_outputs(
     path: &Option<PathBuf>,
 ) -> Result<BTreeMap<InputDataPoint, ErrorDataPoint>, Box<dyn Error>> {
-    if let Some(path) = path {
+    if let Some(path) = path.as_ref().filter(|p| p.exists()) {
+        // new results are appended, so the file needs the current columns
+        if upgrade_error_data_csv(path)? {
+            info!("Added the columns missing in {} to it.", path.display());
+        }
         let data_points = read_error_data_csv_from_file(path)?;
         let data_points_map = data_points
             .into_iter()
@@ -121,6 +149,7 @@ fn load_existing_outputs(
     }
 }
 
+/// Existing outputs are not reused but kept in the output file, see write_output
 fn make_sorted_tasklist(
     inputs: Vec<InputDataPoint>,
     existing_outputs: BTreeMap<InputDataPoint, ErrorDataPoint>,
@@ -134,7 +163,6 @@ fn make_sorted_tasklist(
                 None
             }
         })
-        .chain(existing_outputs.values().cloned().map(Task::ReuseErrorData))
         .collect();
     tasks.sort_by_cached_key(|t| t.label());
     tasks
@@ -145,14 +173,25 @@ fn bulk_do(
     jobs: usize,
     fbas_type: FbasType,
     qi_check: bool,
+    pctg_precision: u32,
 ) -> impl Iterator<Item = ErrorDataPoint> {
-    tasks
+    let progress = task_progress_bar(tasks.len());
+    let inputs: Vec<InputDataPoint> = tasks.iter().map(Task::input).collect();
+    let data_points = tasks
         .into_iter()
         .with_nb_threads(jobs)
-        .par_map(move |task| analyze_or_reuse(task, fbas_type.clone(), qi_check))
+        .par_map(move |task| analyze_or_reuse(task, fbas_type.clone(), qi_check, pctg_precision))
+        .progress_with(progress);
+    // the tasks finish in any order, reorder them to get the same output for the same inputs
+    in_input_order(data_points, inputs, InputDataPoint::from_error_data_point)
 }
 
-fn analyze_or_reuse(task: Task, fbas_type: FbasType, qi_check: bool) -> ErrorDataPoint {
+fn analyze_or_reuse(
+    task: Task,
+    fbas_type: FbasType,
+    qi_check: bool,
+    pctg_precision: u32,
+) -> ErrorDataPoint {
     match task {
         Task::ReuseErrorData(output) => {
             trace!(
@@ -160,9 +199,9 @@ fn analyze_or_reuse(task: Task, fbas_type: FbasType, qi_check: bool) -> ErrorDat
                 output.top_tier_size,
                 output.run
             );
-            output
+            *output
         }
-        Task::Analyze(input) => rank(input, fbas_type, qi_check),
+        Task::Analyze(input) => rank(input, fbas_type, qi_check, pctg_precision),
         _ => panic!("Unexpected data point"),
     }
 }
@@ -183,7 +222,12 @@ fn get_or_compute_truth_value(fbas_size: usize, fbas: &Fbas, qi_check: bool) ->
     exact_scores
 }
 
-fn rank(input: InputDataPoint, fbas_type: FbasType, qi_check: bool) -> ErrorDataPoint {
+fn rank(
+    input: InputDataPoint,
+    fbas_type: FbasType,
+    qi_check: bool,
+    pctg_precision: u32,
+) -> ErrorDataPoint {
     let fbas = fbas_type.make_one(input.top_tier_size);
     assert!(fbas.number_of_nodes() == input.top_tier_size);
     let size = fbas.number_of_nodes();
@@ -195,11 +239,15 @@ fn rank(input: InputDataPoint, fbas_type: FbasType, qi_check: bool) -> ErrorData
     );
     let approx_power_indices_10_pow_1 = rank_nodes(
         &fbas,
-        RankingAlg::PowerIndexApprox(10usize.pow(1)),
+        RankingAlg::PowerIndexApprox(10usize.pow(1), None),
         qi_check,
     );
     let (mean_abs_error_10_pow_1, median_abs_error_10_pow_1, mean_abs_percentage_error_10_pow_1) =
-        mean_med_pctg_errors(&approx_power_indices_10_pow_1, &exact_power_index);
+        mean_med_pctg_errors_with_precision(
+            &approx_power_indices_10_pow_1,
+            &exact_power_index,
+            pctg_precision,
+        );
     info!("Completed 10^1 approximation for FBAS of size {}.", size);
     info!(
         "Starting 10^2 approximation run {} for FBAS of size {}.",
@@ -207,11 +255,15 @@ fn rank(input: InputDataPoint, fbas_type: FbasType, qi_check: bool) -> ErrorData
     );
     let approx_power_indices_10_pow_2 = rank_nodes(
         &fbas,
-        RankingAlg::PowerIndexApprox(10usize.pow(2)),
+        RankingAlg::PowerIndexApprox(10usize.pow(2), None),
         qi_check,
     );
     let (mean_abs_error_10_pow_2, median_abs_error_10_pow_2, mean_abs_percentage_error_10_pow_2) =
-        mean_med_pctg_errors(&approx_power_indices_10_pow_2, &exact_power_index);
+        mean_med_pctg_errors_with_precision(
+            &approx_power_indices_10_pow_2,
+            &exact_power_index,
+            pctg_precision,
+        );
     info!("Completed 10^2 approximation for FBAS of size {}.", size);
     info!(
         "Starting 10^3 approximation run {} for FBAS of size {}.",
@@ -219,11 +271,15 @@ fn rank(input: InputDataPoint, fbas_type: FbasType, qi_check: bool) -> ErrorData
     );
     let approx_power_indices_10_pow_3 = rank_nodes(
         &fbas,
-        RankingAlg::PowerIndexApprox(10usize.pow(3)),
+        RankingAlg::PowerIndexApprox(10usize.pow(3), None),
         qi_check,
     );
     let (mean_abs_error_10_pow_3, median_abs_error_10_pow_3, mean_abs_percentage_error_10_pow_3) =
-        mean_med_pctg_errors(&approx_power_indices_10_pow_3, &exact_power_index);
+        mean_med_pctg_errors_with_precision(
+            &approx_power_indices_10_pow_3,
+            &exact_power_index,
+            pctg_precision,
+        );
     info!("Completed 10^3 approximation for FBAS of size {}.", size);
     info!(
         "Starting 10^4 approximation run {} for FBAS of size {}.",
@@ -231,11 +287,15 @@ fn rank(input: InputDataPoint, fbas_type: FbasType, qi_check: bool) -> ErrorData
     );
     let approx_power_indices_10_pow_4 = rank_nodes(
         &fbas,
-        RankingAlg::PowerIndexApprox(10usize.pow(4)),
+        RankingAlg::PowerIndexApprox(10usize.pow(4), None),
         qi_check,
     );
     let (mean_abs_error_10_pow_4, median_abs_error_10_pow_4, mean_abs_percentage_error_10_pow_4) =
-        mean_med_pctg_errors(&approx_power_indices_10_pow_4, &exact_power_index);
+        mean_med_pctg_errors_with_precision(
+            &approx_power_indices_10_pow_4,
+            &exact_power_index,
+            pctg_precision,
+        );
     info!("Completed 10^4 approximation for FBAS of size {}.", size);
     info!(
         "Starting 10^5 approximation run {} for FBAS of size {}.",
@@ -243,11 +303,15 @@ fn rank(input: InputDataPoint, fbas_type: FbasType, qi_check: bool) -> ErrorData
     );
     let approx_power_indices_10_pow_5 = rank_nodes(
         &fbas,
-        RankingAlg::PowerIndexApprox(10usize.pow(5)),
+        RankingAlg::PowerIndexApprox(10usize.pow(5), None),
         qi_check,
     );
     let (mean_abs_error_10_pow_5, median_abs_error_10_pow_5, mean_abs_percentage_error_10_pow_5) =
-        mean_med_pctg_errors(&approx_power_indices_10_pow_5, &exact_power_index);
+        mean_med_pctg_errors_with_precision(
+            &approx_power_indices_10_pow_5,
+            &exact_power_index,
+            pctg_precision,
+        );
     info!("Completed 10^5 approximation for FBAS of size {}.", size);
     info!(
         "Starting 10^6 approximation run {} for FBAS of size {}.",
@@ -255,11 +319,15 @@ fn rank(input: InputDataPoint, fbas_type: FbasType, qi_check: bool) -> ErrorData
     );
     let approx_power_indices_10_pow_6 = rank_nodes(
         &fbas,
-        RankingAlg::PowerIndexApprox(10usize.pow(6)),
+        RankingAlg::PowerIndexApprox(10usize.pow(6), None),
         qi_check,
     );
     let (mean_abs_error_10_pow_6, median_abs_error_10_pow_6, mean_abs_percentage_error_10_pow_6) =
-        mean_med_pctg_errors(&approx_power_indices_10_pow_6, &exact_power_index);
+        mean_med_pctg_errors_with_precision(
+            &approx_power_indices_10_pow_6,
+            &exact_power_index,
+            pctg_precision,
+        );
     info!("Completed 10^6 approximation for FBAS of size {}.", size);
     info!(
         "Starting 10^7 approximation run {} for FBAS of size {}.",
@@ -267,11 +335,15 @@ fn rank(input: InputDataPoint, fbas_type: FbasType, qi_check: bool) -> ErrorData
     );
     let approx_power_indices_10_pow_7 = rank_nodes(
         &fbas,
-        RankingAlg::PowerIndexApprox(10usize.pow(7)),
+        RankingAlg::PowerIndexApprox(10usize.pow(7), None),
         qi_check,
     );
     let (mean_abs_error_10_pow_7, median_abs_error_10_pow_7, mean_abs_percentage_error_10_pow_7) =
-        mean_med_pctg_errors(&approx_power_indices_10_pow_7, &exact_power_index);
+        mean_med_pctg_errors_with_precision(
+            &approx_power_indices_10_pow_7,
+            &exact_power_index,
+            pctg_precision,
+        );
     info!("Completed 10^7 approximation for FBAS of size {}.", size);
     info!(
         "Starting 10^8 approximation run {} for FBAS of size {}.",
@@ -279,11 +351,15 @@ fn rank(input: InputDataPoint, fbas_type: FbasType, qi_check: bool) -> ErrorData
     );
     let approx_power_indices_10_pow_8 = rank_nodes(
         &fbas,
-        RankingAlg::PowerIndexApprox(10usize.pow(8)),
+        RankingAlg::PowerIndexApprox(10usize.pow(8), None),
         qi_check,
     );
     let (mean_abs_error_10_pow_8, median_abs_error_10_pow_8, mean_abs_percentage_error_10_pow_8) =
-        mean_med_pctg_errors(&approx_power_indices_10_pow_8, &exact_power_index);
+        mean_med_pctg_errors_with_precision(
+            &approx_power_indices_10_pow_8,
+            &exact_power_index,
+            pctg_precision,
+        );
     info!(
         "Completed 10^8 Approximation run {} for FBAS of size {}.",
         input.run, size
@@ -316,25 +392,75 @@ fn rank(input: InputDataPoint, fbas_type: FbasType, qi_check: bool) -> ErrorData
         mean_abs_error_10_pow_8,
         median_abs_error_10_pow_8,
         mean_abs_percentage_error_10_pow_8,
+        score_sum_deviation_10_pow_1: score_sum_deviation(&approx_power_indices_10_pow_1),
+        score_sum_deviation_10_pow_2: score_sum_deviation(&approx_power_indices_10_pow_2),
+        score_sum_deviation_10_pow_3: score_sum_deviation(&approx_power_indices_10_pow_3),
+        score_sum_deviation_10_pow_4: score_sum_deviation(&approx_power_indices_10_pow_4),
+        score_sum_deviation_10_pow_5: score_sum_deviation(&approx_power_indices_10_pow_5),
+        score_sum_deviation_10_pow_6: score_sum_deviation(&approx_power_indices_10_pow_6),
+        score_sum_deviation_10_pow_7: score_sum_deviation(&approx_power_indices_10_pow_7),
+        score_sum_deviation_10_pow_8: score_sum_deviation(&approx_power_indices_10_pow_8),
+        ordering_recovered_10_pow_1: preserves_order(
+            &exact_power_index,
+            &approx_power_indices_10_pow_1,
+        ),
+        ordering_recovered_10_pow_2: preserves_order(
+            &exact_power_index,
+            &approx_power_indices_10_pow_2,
+        ),
+        ordering_recovered_10_pow_3: preserves_order(
+            &exact_power_index,
+            &approx_power_indices_10_pow_3,
+        ),
+        ordering_recovered_10_pow_4: preserves_order(
+            &exact_power_index,
+            &approx_power_indices_10_pow_4,
+        ),
+        ordering_recovered_10_pow_5: preserves_order(
+            &exact_power_index,
+            &approx_power_indices_10_pow_5,
+        ),
+        ordering_recovered_10_pow_6: preserves_order(
+            &exact_power_index,
+            &approx_power_indices_10_pow_6,
+        ),
+        ordering_recovered_10_pow_7: preserves_order(
+            &exact_power_index,
+            &approx_power_indices_10_pow_7,
+        ),
+        ordering_recovered_10_pow_8: preserves_order(
+            &exact_power_index,
+            &approx_power_indices_10_pow_8,
+        ),
     }
 }
 
-fn write_csv(
+/// Writes the data points as they arrive. In update mode, they are appended to the existing file.
+fn write_output(
     data_points: impl IntoIterator<Item = impl serde::Serialize>,
     output_path: &Option<PathBuf>,
-    overwrite_allowed: bool,
+    update: bool,
+    format: OutputFormat,
 ) -> Result<(), Box<dyn Error>> {
     if let Some(path) = output_path {
-        if !overwrite_allowed && path.exists() {
+        if update {
+            append_csv_to_file(data_points, path)
+        } else if path.exists() {
             Err(Box::new(io::Error::new(
                 io::ErrorKind::AlreadyExists,
                 "Output file exists, refusing to overwrite.",
             )))
         } else {
-            write_csv_to_file(data_points, path)
+            match format {
+                OutputFormat::Csv => write_csv_to_file(data_points, path),
+                OutputFormat::Json => write_json_to_file(data_points, path),
+            }
         }
     } else {
-        write_csv_to_stdout(data_points)
+        match format {
+            OutputFormat::Csv => write_csv_to_stdout(data_points),
+            OutputFormat::Json => write_json_to_stdout(data_points),
+        }
     }
 }
 
@@ -2,9 +2,20 @@ use fbas_analyzer::*;
 use fbas_reward_distributor::*;
 
 use env_logger::Env;
-use log::{debug, info, trace};
+use indicatif::ProgressIterator;
+use log::{debug, info, trace, warn};
 use par_map::ParMap;
-use std::{collections::BTreeMap, error::Error, io, path::PathBuf};
+use std::{
+    collections::BTreeMap,
+    env,
+    error::Error,
+    io::{self, BufRead, BufReader},
+    path::PathBuf,
+    process::{Command, Stdio},
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
 use structopt::StructOpt;
 
 /// Run performance measurements on different sized FBASs based on the input parameters.
@@ -28,6 +39,10 @@ struct Cli {
     #[structopt(flatten)]
     run_config: RunConfig,
 
+    /// Format of the output, csv or json. Updating existing output files requires csv.
+    #[structopt(long = "format", default_value = "csv")]
+    format: OutputFormat,
+
     /// Update output file with missing results (doesn't repeat analyses for existing lines).
     #[structopt(short = "u", long = "update")]
     update: bool,
@@ -40,6 +55,17 @@ struct Cli {
     #[structopt(short = "j", long = "jobs", default_value = "1")]
     jobs: usize,
 
+    /// Give up on a ranking after this many seconds and record its duration as NaN. Each ranking
+    /// then runs in its own process, which is killed on timeout. Generating the FBAS is not timed.
+    /// Runs with a NaN duration are repeated in update mode.
+    #[structopt(long = "timeout-secs")]
+    timeout_secs: Option<u64>,
+
+    /// Internal: only rank the FBAS of this "top_tier_size,run" and print the duration, see
+    /// batch_rank_with_timeout.
+    #[structopt(long = "worker-task", hidden = true, parse(try_from_str = parse_worker_task))]
+    worker_task: Option<InputDataPoint>,
+
     /// Do not assert that the FBAS has quorum intersection before proceeding with further computations.
     /// Default behaviour is to always check for QI.
     #[structopt(long = "no-quorum-intersection")]
@@ -80,10 +106,20 @@ fn main() -> Result<(), Box<dyn Error>> {
     let ranking_alg = match args.run_config.ranking_alg {
         RankingAlgConfig::NodeRank => RankingAlg::NodeRank,
         RankingAlgConfig::PowerIndexEnum => RankingAlg::PowerIndexEnum(None),
-        RankingAlgConfig::PowerIndexApprox { s } => RankingAlg::PowerIndexApprox(s),
+        RankingAlgConfig::PowerIndexApprox { s } => RankingAlg::PowerIndexApprox(s, None),
     };
+    if let Some(input) = args.worker_task {
+        run_worker_task(input, fbas_type, !args.dont_check_for_qi, ranking_alg);
+        return Ok(());
+    }
     let inputs: Vec<InputDataPoint> =
         generate_inputs(args.max_top_tier_size, args.runs, fbas_type.clone());
+    if args.update && args.format != OutputFormat::Csv {
+        return Err(Box::new(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Updating existing results is only supported for CSV output.",
+        )));
+    }
     let existing_outputs = if args.update {
         load_existing_outputs(&args.output_path)?
     } else {
@@ -92,10 +128,18 @@ fn main() -> Result<(), Box<dyn Error>> {
     let tasks = make_sorted_tasklist(inputs, existing_outputs);
 
     let qi_check = !args.dont_check_for_qi;
-    let output_iterator = bulk_do(tasks, args.jobs, fbas_type.clone(), qi_check, ranking_alg);
+    let timeout = args.timeout_secs.map(Duration::from_secs);
+    let output_iterator = bulk_do(
+        tasks,
+        args.jobs,
+        fbas_type.clone(),
+        qi_check,
+        ranking_alg,
+        timeout,
+    );
     info!("Starting performance measurements for {:?} like FBAS with upto {} nodes.\n Performing {} iterations per FBAS.",fbas_type, args.max_top_tier_size, args.runs);
 
-    write_csv(output_iterator, &args.output_path, args.update)?;
+    write_output(output_iterator, &args.output_path, args.update, args.format)?;
     Ok(())
 }
 
@@ -133,6 +177,10 @@ fn make_sorted_tasklist(
     inputs: Vec<InputDataPoint>,
     existing_outputs: BTreeMap<InputDataPoint, PerfDataPoint>,
 ) -> Vec<Task> {
+    // timed out runs are missing a duration, try them again
+    let (retried_outputs, existing_outputs): (BTreeMap<_, _>, BTreeMap<_, _>) = existing_outputs
+        .into_iter()
+        .partition(|(input, output)| output.duration.is_nan() && inputs.contains(input));
     let mut tasks: Vec<Task> = inputs
         .into_iter()
         .filter_map(|input| {
@@ -144,6 +192,9 @@ fn make_sorted_tasklist(
         })
         .chain(existing_outputs.values().cloned().map(Task::ReusePerfData))
         .collect();
+    if !retried_outputs.is_empty() {
+        info!("Retrying {} timed out runs.", retried_outputs.len());
+    }
     tasks.sort_by_cached_key(|t| t.label());
     tasks
 }
@@ -154,11 +205,19 @@ fn bulk_do(
     fbas_type: FbasType,
     qi_check: bool,
     alg: RankingAlg,
+    timeout: Option<Duration>,
 ) -> impl Iterator<Item = PerfDataPoint> {
-    tasks
+    let progress = task_progress_bar(tasks.len());
+    let inputs: Vec<InputDataPoint> = tasks.iter().map(Task::input).collect();
+    let data_points = tasks
         .into_iter()
         .with_nb_threads(jobs)
-        .par_map(move |task| analyze_or_reuse(task, fbas_type.clone(), qi_check, alg.clone()))
+        .par_map(move |task| {
+            analyze_or_reuse(task, fbas_type.clone(), qi_check, alg.clone(), timeout)
+        })
+        .progress_with(progress);
+    // the tasks finish in any order, reorder them to get the same output for the same inputs
+    in_input_order(data_points, inputs, InputDataPoint::from_perf_data_point)
 }
 
 fn analyze_or_reuse(
@@ -166,6 +225,7 @@ fn analyze_or_reuse(
     fbas_type: FbasType,
     qi_check: bool,
     alg: RankingAlg,
+    timeout: Option<Duration>,
 ) -> PerfDataPoint {
     match task {
         Task::ReusePerfData(output) => {
@@ -176,7 +236,13 @@ fn analyze_or_reuse(
             );
             output
         }
-        Task::Analyze(input) => batch_rank(input, fbas_type, qi_check, alg),
+        Task::Analyze(input) => {
+            if let Some(timeout) = timeout {
+                batch_rank_with_timeout(input, timeout)
+            } else {
+                batch_rank(input, fbas_type, qi_check, alg)
+            }
+        }
         _ => panic!("Unexpected data point"),
     }
 }
@@ -187,7 +253,12 @@ fn rank_fbas(input: InputDataPoint, fbas: &Fbas, alg: RankingAlg, qi_check: bool
         "Starting {:?} run {} for FBAS of size {}.",
         alg, input.run, size
     );
-    let (_, duration) = timed_secs!(rank_nodes(fbas, alg.clone(), qi_check));
+    // the top tier is all nodes by construction, checking it would distort the timings
+    let config = RankConfig {
+        strict: false,
+        ..Default::default()
+    };
+    let (_, duration) = timed_secs!(rank_nodes_with_config(fbas, alg.clone(), qi_check, config));
     debug!(
         "Completed {:?} run {} for FBAS of size {}.",
         alg, input.run, size
@@ -201,19 +272,109 @@ fn batch_rank(
     qi_check: bool,
     alg: RankingAlg,
 ) -> PerfDataPoint {
+    let fbas = make_fbas(&input, fbas_type);
+    let duration = rank_generated_fbas(&input, &fbas, alg, qi_check);
+
+    PerfDataPoint {
+        top_tier_size: input.top_tier_size,
+        run: input.run,
+        duration,
+    }
+}
+
+fn make_fbas(input: &InputDataPoint, fbas_type: FbasType) -> Fbas {
     let fbas = fbas_type.make_one(input.top_tier_size);
     assert!(fbas.number_of_nodes() == input.top_tier_size);
-    let size = fbas.number_of_nodes();
-    info!("Starting run {} for FBAS with {} nodes", input.run, size);
+    info!(
+        "Starting run {} for FBAS with {} nodes",
+        input.run,
+        fbas.number_of_nodes()
+    );
+    fbas
+}
 
-    let duration = if alg == RankingAlg::PowerIndexEnum(None) {
-        let top_tier_nodes: Vec<NodeId> = fbas.all_nodes().iter().collect();
-        let alg_with_tt = RankingAlg::PowerIndexEnum(Some(top_tier_nodes));
-        rank_fbas(input.clone(), &fbas, alg_with_tt, qi_check)
-    } else {
-        rank_fbas(input.clone(), &fbas, alg, qi_check)
+fn rank_generated_fbas(
+    input: &InputDataPoint,
+    fbas: &Fbas,
+    alg: RankingAlg,
+    qi_check: bool,
+) -> f64 {
+    // the generated FBAS only consist of top-tier nodes, i.e. we can skip the computation
+    let top_tier_nodes: Vec<NodeId> = fbas.all_nodes().iter().collect();
+    let alg_with_tt = match alg {
+        RankingAlg::PowerIndexEnum(None) => RankingAlg::PowerIndexEnum(Some(top_tier_nodes)),
+        RankingAlg::PowerIndexApprox(samples, None) => {
+            RankingAlg::PowerIndexApprox(samples, Some(top_tier_nodes))
+        }
+        alg => alg,
     };
+    rank_fbas(input.clone(), fbas, alg_with_tt, qi_check)
+}
+
+/// Printed by a worker process once it has generated the FBAS and starts ranking it
+const WORKER_READY: &str = "ready";
+
+fn parse_worker_task(task: &str) -> Result<InputDataPoint, String> {
+    let (top_tier_size, run) = task
+        .split_once(',')
+        .ok_or_else(|| format!("Expected top_tier_size,run, got {}", task))?;
+    Ok(InputDataPoint {
+        top_tier_size: top_tier_size.parse().map_err(|e| format!("{}", e))?,
+        run: run.parse().map_err(|e| format!("{}", e))?,
+    })
+}
 
+/// Entry point of the worker processes started by batch_rank_with_timeout
+fn run_worker_task(input: InputDataPoint, fbas_type: FbasType, qi_check: bool, alg: RankingAlg) {
+    let fbas = make_fbas(&input, fbas_type);
+    println!("{}", WORKER_READY);
+    let duration = rank_generated_fbas(&input, &fbas, alg, qi_check);
+    println!("{}", duration);
+}
+
+/// Runs the task in a worker process, i.e. this program started with the same arguments plus
+/// --worker-task, and kills it if the ranking does not finish in time. The clock only starts once
+/// the worker has generated the FBAS.
+fn batch_rank_with_timeout(input: InputDataPoint, timeout: Duration) -> PerfDataPoint {
+    let mut worker = Command::new(env::current_exe().expect("Failed to locate the executable"))
+        .arg("--worker-task")
+        .arg(format!("{},{}", input.top_tier_size, input.run))
+        .args(env::args_os().skip(1))
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to start worker process");
+    let stdout = worker.stdout.take().expect("Worker has no stdout");
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    let worker_failed = || -> ! {
+        panic!(
+            "Worker for run {} for FBAS with {} nodes failed",
+            input.run, input.top_tier_size
+        )
+    };
+    match receiver.recv() {
+        Ok(line) if line == WORKER_READY => {}
+        _ => worker_failed(),
+    }
+    let duration = match receiver.recv_timeout(timeout) {
+        Ok(line) => line.parse().unwrap_or_else(|_| worker_failed()),
+        Err(RecvTimeoutError::Timeout) => {
+            warn!(
+                "Run {} for FBAS with {} nodes timed out after {:?}.",
+                input.run, input.top_tier_size, timeout
+            );
+            worker.kill().expect("Failed to kill worker process");
+            f64::NAN
+        }
+        Err(RecvTimeoutError::Disconnected) => worker_failed(),
+    };
+    worker.wait().expect("Failed to wait for worker process");
     PerfDataPoint {
         top_tier_size: input.top_tier_size,
         run: input.run,
@@ -221,10 +382,11 @@ fn batch_rank(
     }
 }
 
-fn write_csv(
+fn write_output(
     data_points: impl IntoIterator<Item = impl serde::Serialize>,
     output_path: &Option<PathBuf>,
     overwrite_allowed: bool,
+    format: OutputFormat,
 ) -> Result<(), Box<dyn Error>> {
     if let Some(path) = output_path {
         if !overwrite_allowed && path.exists() {
@@ -233,9 +395,15 @@ fn write_csv(
                 "Output file exists, refusing to overwrite.",
             )))
         } else {
-            write_csv_to_file(data_points, path)
+            match format {
+                OutputFormat::Csv => write_csv_to_file(data_points, path),
+                OutputFormat::Json => write_json_to_file(data_points, path),
+            }
         }
     } else {
-        write_csv_to_stdout(data_points)
+        match format {
+            OutputFormat::Csv => write_csv_to_stdout(data_points),
+            OutputFormat::Json => write_json_to_stdout(data_points),
+        }
     }
 }
@@ -1,11 +1,20 @@
-use fbas_analyzer::{Fbas, NodeId};
+use fbas_analyzer::{timed, timed_secs, timing, Fbas, NodeId, NodeIdSet};
 use fbas_reward_distributor::*;
 
 use structopt::StructOpt;
 
+use base64::{engine::general_purpose::STANDARD, Engine};
 use env_logger::Env;
-use log::info;
-use std::path::PathBuf;
+use log::{info, warn};
+use std::{
+    cell::OnceCell,
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
 
 /// Rank nodes of an FBAS and allocate rewards to them accordingly
 #[derive(Debug, StructOpt)]
@@ -23,6 +32,163 @@ struct Cli {
 enum SubCommand {
     Rank(RankCmds),
     Distribute(DistCmds),
+    Validate(ValidateCmds),
+    ExportGame(ExportGameCmds),
+    Apportion(ApportionCmds),
+    ExportDot(ExportDotCmds),
+    All(AllCmds),
+}
+
+/// Rank the nodes using NodeRank, the exact and the approximated Shapley-Shubik index and compare
+/// the rankings
+#[derive(Debug, StructOpt)]
+#[structopt(author = "Charmaine Ndolo")]
+struct AllCmds {
+    /// Path to JSON file describing the FBAS in stellarbeat.org "nodes" format.
+    /// Will use STDIN if omitted.
+    nodes_path: Option<PathBuf>,
+
+    /// Prior to any analysis, filter out all nodes marked as `"active" == false` in the input
+    /// nodes JSON (the one at `nodes_path`).
+    #[structopt(short = "i", long = "ignore-inactive-nodes")]
+    ignore_inactive_nodes: bool,
+
+    /// Number of samples of the approximation.
+    #[structopt(short = "s", long = "samples", default_value = "1000")]
+    samples: usize,
+
+    /// Seed of the sampled permutations. If omitted, a random seed is drawn.
+    #[structopt(long = "seed")]
+    seed: Option<u64>,
+
+    /// Skip the exact enumeration if the top tier has more nodes than this.
+    #[structopt(long = "max-nodes", default_value = "25")]
+    max_nodes: usize,
+
+    /// Identify nodes by their public key.
+    /// Default is to use node IDs corresponding to indices in the input file.
+    #[structopt(short = "p", long = "pretty")]
+    pks: bool,
+
+    /// Do not assert that the FBAS has quorum intersection before proceeding with further computations.
+    /// Default behaviour is to always check for QI.
+    #[structopt(long = "no-quorum-intersection")]
+    dont_check_for_qi: bool,
+
+    #[structopt(long = "log", short = "l", default_value = "info")]
+    log_level: String,
+}
+
+/// Write the trust graph as a GraphViz DOT file whose nodes are labelled and sized by their scores
+#[derive(Debug, StructOpt)]
+#[structopt(author = "Charmaine Ndolo")]
+struct ExportDotCmds {
+    /// Ranking algorithm to use.
+    #[structopt(subcommand)]
+    alg: RankingAlgConfig,
+
+    /// Path to JSON file describing the FBAS in stellarbeat.org "nodes" format.
+    /// Will use STDIN if omitted.
+    nodes_path: Option<PathBuf>,
+
+    /// Output DOT file (will output to STDOUT if omitted).
+    #[structopt(short = "o", long = "out")]
+    output_path: Option<PathBuf>,
+
+    /// Prior to any analysis, filter out all nodes marked as `"active" == false` in the input
+    /// nodes JSON (the one at `nodes_path`).
+    #[structopt(short = "i", long = "ignore-inactive-nodes")]
+    ignore_inactive_nodes: bool,
+
+    /// Do not assert that the FBAS has quorum intersection before proceeding with further computations.
+    /// Default behaviour is to always check for QI.
+    #[structopt(long = "no-quorum-intersection")]
+    dont_check_for_qi: bool,
+
+    #[structopt(long = "log", short = "l", default_value = "info")]
+    log_level: String,
+}
+
+/// Distribute a reward according to scores computed elsewhere, no FBAS or ranking needed
+#[derive(Debug, StructOpt)]
+#[structopt(author = "Charmaine Ndolo")]
+struct ApportionCmds {
+    /// Path to a CSV file with a `node_id,score` header and one line per node. The node IDs must
+    /// be 0 to n-1. Will use STDIN if omitted.
+    scores_path: Option<PathBuf>,
+
+    /// Path to the JSON file of the FBAS the scores belong to, in stellarbeat.org "nodes" format.
+    /// If passed, the nodes are identified by their public keys.
+    #[structopt(long = "nodes")]
+    nodes_path: Option<PathBuf>,
+
+    /// Amount to be shared among the nodes.
+    #[structopt(short = "r", long = "reward", default_value = "1")]
+    total_reward: f64,
+
+    /// Distribute the reward as whole units using the passed apportionment method
+    /// (hamilton or webster) so that the rewards sum up exactly to the reward value.
+    #[structopt(long = "apportionment")]
+    apportionment: Option<Apportionment>,
+
+    /// List the distribution sorted by ascending instead of descending score.
+    #[structopt(long = "ascending")]
+    ascending: bool,
+
+    /// Print the distribution as csv, json or markdown instead of text, see the distribute
+    /// subcommand.
+    #[structopt(long = "format")]
+    format: Option<ReportFormat>,
+
+    /// Decimal places of the scores and rewards in the markdown output.
+    #[structopt(long = "precision", default_value = "3")]
+    precision: usize,
+
+    #[structopt(long = "log", short = "l", default_value = "info")]
+    log_level: String,
+}
+
+/// Write the winning coalitions of the top tier as JSON, e.g., for external Shapley solvers
+#[derive(Debug, StructOpt)]
+#[structopt(author = "Charmaine Ndolo")]
+struct ExportGameCmds {
+    /// Path to JSON file describing the FBAS in stellarbeat.org "nodes" format.
+    /// Will use STDIN if omitted.
+    nodes_path: Option<PathBuf>,
+
+    /// Output JSON file (will output to STDOUT if omitted).
+    #[structopt(short = "o", long = "out")]
+    output_path: Option<PathBuf>,
+
+    /// Prior to any analysis, filter out all nodes marked as `"active" == false` in the input
+    /// nodes JSON (the one at `nodes_path`).
+    #[structopt(short = "i", long = "ignore-inactive-nodes")]
+    ignore_inactive_nodes: bool,
+
+    /// Do not assert that the FBAS has quorum intersection before proceeding with further computations.
+    /// Default behaviour is to always check for QI.
+    #[structopt(short = "nq", long = "no-quorum-intersection")]
+    dont_check_for_qi: bool,
+
+    #[structopt(long = "log", short = "l", default_value = "info")]
+    log_level: String,
+}
+
+/// Check the assumptions the rankings rely on without ranking
+#[derive(Debug, StructOpt)]
+#[structopt(author = "Charmaine Ndolo")]
+struct ValidateCmds {
+    /// Path to JSON file describing the FBAS in stellarbeat.org "nodes" format.
+    /// Will use STDIN if omitted.
+    nodes_path: Option<PathBuf>,
+
+    /// Prior to any analysis, filter out all nodes marked as `"active" == false` in the input
+    /// nodes JSON (the one at `nodes_path`).
+    #[structopt(short = "i", long = "ignore-inactive-nodes")]
+    ignore_inactive_nodes: bool,
+
+    #[structopt(long = "log", short = "l", default_value = "info")]
+    log_level: String,
 }
 
 /// Rank only, do not compute a distribution
@@ -37,11 +203,39 @@ struct RankCmds {
     /// Will use STDIN if omitted.
     nodes_path: Option<PathBuf>,
 
+    /// Read the nodes JSON base64-encoded from STDIN instead of a file.
+    #[structopt(long = "base64", conflicts_with = "nodes-path")]
+    base64: bool,
+
+    /// Base64-encoded nodes JSON to use instead of a file or STDIN.
+    #[structopt(long = "nodes-b64", conflicts_with = "nodes-path")]
+    nodes_b64: Option<String>,
+
+    /// Rank every JSON file in the passed directory instead and print each node's scores over
+    /// time. The files are ordered by name, and the name without extension is used as the date.
+    #[structopt(
+        long = "series",
+        conflicts_with_all = &["nodes-path", "base64", "nodes-b64"]
+    )]
+    series: Option<PathBuf>,
+
+    /// Rank the union of the FBAS and the one in the passed nodes JSON file, e.g. to see the
+    /// effect of merging two networks. Nodes are matched by public key; if both FBASs contain a
+    /// node, its quorum set from the first FBAS is used.
+    #[structopt(long = "merge-with", conflicts_with = "series")]
+    merge_with: Option<PathBuf>,
+
     /// Prior to any analysis, filter out all nodes marked as `"active" == false` in the input
     /// nodes JSON (the one at `nodes_path`).
     #[structopt(short = "i", long = "ignore-inactive-nodes")]
     ignore_inactive_nodes: bool,
 
+    /// Prior to any analysis, filter out all nodes marked as `"active" == false` in the passed
+    /// JSON file, a list of `{ "publicKey": ..., "active": ... }` objects. Independent of the
+    /// `active` fields in the nodes JSON.
+    #[structopt(long = "status")]
+    status_path: Option<PathBuf>,
+
     /// Identify nodes by their public key.
     /// Default is to use node IDs corresponding to indices in the input file.
     #[structopt(short = "p", long = "pretty")]
@@ -52,6 +246,55 @@ struct RankCmds {
     #[structopt(short = "nq", long = "no-quorum-intersection")]
     dont_check_for_qi: bool,
 
+    /// Do not report whether the FBAS enjoys quorum intersection, which requires computing all
+    /// minimal quorums.
+    #[structopt(long = "skip-qi-report")]
+    skip_qi_report: bool,
+
+    /// Print the groups of nodes that have the same quorum set.
+    #[structopt(long = "groups")]
+    groups: bool,
+
+    /// Print the minimal quorums as lists of their members' public keys.
+    #[structopt(long = "minimal-quorums")]
+    minimal_quorums: bool,
+
+    /// Print the indices of the minimal quorums each node belongs to.
+    #[structopt(long = "quorum-membership")]
+    quorum_membership: bool,
+
+    /// Print the smallest set of the most powerful nodes whose combined normalised score exceeds
+    /// the passed fraction, e.g. 0.5.
+    #[structopt(long = "controlling")]
+    controlling: Option<f64>,
+
+    /// Print the rankings as csv, json or markdown instead of text. Nothing else is printed in
+    /// this case.
+    #[structopt(long = "format")]
+    format: Option<ReportFormat>,
+
+    /// Decimal places of the scores in the markdown output.
+    #[structopt(long = "precision", default_value = "3")]
+    precision: usize,
+
+    /// Add each node's quorum set hash to the csv, json or markdown output.
+    #[structopt(long = "qset-hash")]
+    qset_hash: bool,
+
+    /// Scale the scores before reporting them: sum scales them to sum up to 1, max divides them
+    /// by the highest score, e.g. for bar charts. Scores are reported as computed if omitted. Not
+    /// applied to confidence intervals or PageRank scores.
+    #[structopt(long = "scale")]
+    scale: Option<ScoreScale>,
+
+    /// Explain the score of the node with the passed ID, e.g. why it is 0.
+    #[structopt(long = "explain", conflicts_with = "format")]
+    explain: Option<NodeId>,
+
+    /// Print how long the major phases of the run took to STDERR.
+    #[structopt(long = "profile")]
+    profile: bool,
+
     #[structopt(long = "log", short = "l", default_value = "info")]
     log_level: String,
 }
@@ -68,25 +311,93 @@ struct DistCmds {
     /// Will use STDIN if omitted.
     nodes_path: Option<PathBuf>,
 
+    /// Read the nodes JSON base64-encoded from STDIN instead of a file.
+    #[structopt(long = "base64", conflicts_with = "nodes-path")]
+    base64: bool,
+
+    /// Base64-encoded nodes JSON to use instead of a file or STDIN.
+    #[structopt(long = "nodes-b64", conflicts_with = "nodes-path")]
+    nodes_b64: Option<String>,
+
     /// Prior to any analysis, filter out all nodes marked as `"active" == false` in the input
     /// nodes JSON (the one at `nodes_path`).
     #[structopt(short = "i", long = "ignore-inactive-nodes")]
     ignore_inactive_nodes: bool,
 
+    /// Prior to any analysis, filter out all nodes marked as `"active" == false` in the passed
+    /// JSON file, a list of `{ "publicKey": ..., "active": ... }` objects. Independent of the
+    /// `active` fields in the nodes JSON.
+    #[structopt(long = "status")]
+    status_path: Option<PathBuf>,
+
     /// Amount to be shared among the nodes.
     #[structopt(short = "r", long = "reward", default_value = "1")]
     total_reward: f64,
 
+    /// Distribute the reward as whole units using the passed apportionment method
+    /// (hamilton or webster) so that the rewards sum up exactly to the reward value.
+    #[structopt(long = "apportionment")]
+    apportionment: Option<Apportionment>,
+
     /// Identify nodes by their public key.
     /// Default is to use node IDs corresponding to indices in the input file.
     #[structopt(short = "p", long = "pretty")]
     pks: bool,
 
+    /// List the distribution sorted by ascending instead of descending score.
+    #[structopt(long = "ascending")]
+    ascending: bool,
+
     /// Do not assert that the FBAS has quorum intersection before proceeding with further computations.
     /// Default behaviour is to always check for QI.
     #[structopt(long = "no-quorum-intersection")]
     dont_check_for_qi: bool,
 
+    /// Do not report whether the FBAS enjoys quorum intersection, which requires computing all
+    /// minimal quorums.
+    #[structopt(long = "skip-qi-report")]
+    skip_qi_report: bool,
+
+    /// Print the groups of nodes that have the same quorum set.
+    #[structopt(long = "groups")]
+    groups: bool,
+
+    /// Print the minimal quorums as lists of their members' public keys.
+    #[structopt(long = "minimal-quorums")]
+    minimal_quorums: bool,
+
+    /// Print the indices of the minimal quorums each node belongs to.
+    #[structopt(long = "quorum-membership")]
+    quorum_membership: bool,
+
+    /// Print the distribution as csv, json or markdown instead of text. Nothing else is printed
+    /// in this case. basis-points prints csv with the rewards replaced by the nodes' shares in
+    /// basis points, which sum up to exactly 10000.
+    #[structopt(long = "format")]
+    format: Option<ReportFormat>,
+
+    /// Decimal places of the scores and rewards in the markdown output.
+    #[structopt(long = "precision", default_value = "3")]
+    precision: usize,
+
+    /// Add each node's quorum set hash to the csv, json or markdown output.
+    #[structopt(long = "qset-hash")]
+    qset_hash: bool,
+
+    /// Print how long the major phases of the run took to STDERR.
+    #[structopt(long = "profile")]
+    profile: bool,
+
+    /// Also print every node's reward next to its share under an equal split of the reward and
+    /// the difference between the two.
+    #[structopt(long = "compare-equal", conflicts_with = "format")]
+    compare_equal: bool,
+
+    /// Distribute the reward proportionally to the square roots of the scores, as in quadratic
+    /// funding, which flattens the distribution towards the nodes with lower scores.
+    #[structopt(long = "sqrt", conflicts_with = "apportionment")]
+    sqrt: bool,
+
     #[structopt(long = "log", short = "l", default_value = "info")]
     log_level: String,
 }
@@ -94,38 +405,224 @@ struct DistCmds {
 #[derive(Debug, StructOpt)]
 enum RankingAlgConfig {
     /// Use NodeRank, an extension of PageRank, to measure nodes' weight in the FBAS
-    NodeRank,
+    NodeRank {
+        /// Also print the PageRank scores the NodeRank scores are based on.
+        /// Only used by the rank subcommand.
+        #[structopt(long = "show-pagerank")]
+        show_pagerank: bool,
+        /// Also print the PageRank scores and every node's amplification, i.e., NodeRank /
+        /// PageRank, to see how much NodeRank boosts or dampens a node. Only used by the rank
+        /// subcommand.
+        #[structopt(long = "verbose")]
+        verbose: bool,
+        /// Only rank the largest strongly connected component of the trust graph, in which every
+        /// node (transitively) trusts every other node via its quorum set; all other nodes get a
+        /// score of 0. PageRank ignores links leaving the component and always uses the internal
+        /// backend. Only used by the rank subcommand.
+        #[structopt(long = "lscc-only", conflicts_with_all = &["show-pagerank", "verbose"])]
+        lscc_only: bool,
+        /// PageRank implementation to base the NodeRank scores on: internal (fbas_analyzer's
+        /// Fbas::rank_nodes) or analyzer (fbas_analyzer's graph-based rank). Useful to cross-check
+        /// the scores. Only used by the rank subcommand.
+        #[structopt(long = "pagerank-backend", default_value = "internal")]
+        pagerank_backend: PageRankBackend,
+    },
     /// Use Shapley-Shubik power indices to calculate nodes' importance in the FBAS. Not
     /// recommended for FBAS with many players because of time complexity
-    PowerIndexEnum,
+    PowerIndexEnum {
+        /// Only print the number of coalitions the enumeration would check and a rough
+        /// time and memory estimate instead of computing the power indices.
+        #[structopt(long = "estimate")]
+        estimate: bool,
+        /// Give up on the enumeration after the passed number of seconds and approximate the
+        /// power indices instead. The output is labelled as approximate in this case.
+        #[structopt(long = "exact-timeout-secs")]
+        exact_timeout_secs: Option<u64>,
+        /// Abort instead of enumerating if the top tier has more than the passed number of
+        /// nodes. 0 disables the check.
+        #[structopt(long = "max-nodes", default_value = "25")]
+        max_nodes: usize,
+    },
     /// Approximate Shapley values as a measure of nodes' importance in the FBAS. The number of
     /// samples to use must be passed if selected.
-    PowerIndexApprox { s: usize },
+    PowerIndexApprox {
+        s: usize,
+        /// Also print bootstrapped confidence intervals at the passed confidence level, e.g. 0.95.
+        /// Only used by the rank subcommand.
+        #[structopt(long = "bootstrap-ci")]
+        bootstrap_ci: Option<f64>,
+        /// Also print 95% confidence intervals derived from the variance of the nodes' marginal
+        /// contributions. Added as ci_low and ci_high to the csv, json or markdown output.
+        /// Only used by the rank subcommand.
+        #[structopt(long = "with-ci", conflicts_with = "bootstrap-ci")]
+        with_ci: bool,
+        /// Print the number of samples needed to stay within the passed maximum error for all
+        /// players at 95% confidence, following Castro et al.'s bound.
+        #[structopt(long = "suggest-samples")]
+        suggest_samples: Option<f64>,
+        /// Seed of the sampled permutations. If omitted, a random seed is drawn. The seed is
+        /// printed either way so that the run can be replayed.
+        #[structopt(long = "seed")]
+        seed: Option<u64>,
+        /// Derive the seed from a hash of the FBAS instead of drawing a random one, so that the
+        /// same FBAS always gets the same seed.
+        #[structopt(long = "seed-from-fbas", conflicts_with = "seed")]
+        seed_from_fbas: bool,
+    },
+    /// Use Banzhaf indices, i.e. the nodes' shares of all swings, to calculate nodes' importance
+    /// in the FBAS. Computed via enumeration, same as power-index-enum
+    BanzhafIndexEnum {
+        /// Print the absolute Banzhaf index (swings / 2^(n-1)) instead of the normalised one.
+        /// Only used by the rank subcommand.
+        #[structopt(long = "absolute")]
+        absolute: bool,
+    },
 }
 
+/// Confidence level used when suggesting a number of samples
+const SUGGESTION_CONFIDENCE: f64 = 0.95;
+
+/// Number of samples of the approximation used if the exact enumeration times out
+const FALLBACK_SAMPLES: usize = 1000;
+
+/// Confidence level of the intervals printed with --with-ci
+const CI_CONFIDENCE: f64 = 0.95;
+
+/// Number of bootstrap resamples drawn when computing confidence intervals
+const BOOTSTRAP_RESAMPLES: usize = 1000;
+
+/// Largest top tier the validate subcommand accepts for the exact algorithms, about a minute of
+/// enumeration at SECONDS_PER_COALITION
+const MAX_EXACT_TOP_TIER_SIZE: usize = 25;
+
+/// Largest top tier export-game writes without warning about the size of the file, about a million
+/// winning coalitions in the worst case
+const MAX_EXPORT_TOP_TIER_SIZE: usize = 20;
+
+/// Rough time it takes to check whether one coalition contains a quorum, measured on the
+/// nonsymmetric FBAS of the performance tests (release build)
+const SECONDS_PER_COALITION: f64 = 2e-6;
+
+/// Rough memory needed to store one winning coalition as a BitSet in a HashSet
+const BYTES_PER_COALITION: f64 = 64.0;
+
 fn get_ranking_alg_from_params(cfg: RankingAlgConfig) -> RankingAlg {
     match cfg {
-        RankingAlgConfig::NodeRank => RankingAlg::NodeRank,
-        RankingAlgConfig::PowerIndexEnum =>
+        RankingAlgConfig::NodeRank { .. } => RankingAlg::NodeRank,
+        RankingAlgConfig::PowerIndexEnum { .. } =>
         // top tier is computed in the next step
         {
             RankingAlg::PowerIndexEnum(None)
         }
-        RankingAlgConfig::PowerIndexApprox { s } => RankingAlg::PowerIndexApprox(s),
+        RankingAlgConfig::PowerIndexApprox { s, .. } => RankingAlg::PowerIndexApprox(s, None),
+        RankingAlgConfig::BanzhafIndexEnum { .. } => RankingAlg::BanzhafIndexEnum(None),
+    }
+}
+
+/// The minimal quorums of an FBAS, found on first use. Finding them is exponential in the worst
+/// case, so all steps of a run share them and they are not searched for if no step needs them.
+struct MinimalQuorums<'a> {
+    fbas: &'a Fbas,
+    min_qs: OnceCell<Vec<NodeIdSet>>,
+}
+
+impl<'a> MinimalQuorums<'a> {
+    fn new(fbas: &'a Fbas) -> Self {
+        Self {
+            fbas,
+            min_qs: OnceCell::new(),
+        }
+    }
+    fn get(&self) -> &[NodeIdSet] {
+        self.min_qs
+            .get_or_init(|| fbas_analyzer::find_minimal_quorums(self.fbas))
+    }
+}
+
+/// Reports the QI status regardless of whether QI is asserted
+fn print_qi_report(min_qs: &[NodeIdSet]) {
+    let has_qi = fbas_analyzer::all_intersect(min_qs);
+    println!("Quorum intersection: {}", if has_qi { "yes" } else { "no" });
+}
+
+/// Warns about the nodes that are not part of any minimal quorum, e.g. misconfigured validators
+fn warn_about_unreachable_nodes(fbas: &Fbas, min_qs: &[NodeIdSet], use_pks: bool) {
+    let unreachable = unreachable_nodes(fbas, min_qs);
+    if unreachable.is_empty() {
+        return;
+    }
+    if use_pks {
+        let pks = fbas_analyzer::to_public_keys(unreachable, fbas);
+        warn!("Nodes outside every minimal quorum, they have zero power: {pks:?}");
+    } else {
+        warn!("Nodes outside every minimal quorum, they have zero power: {unreachable:?}");
+    }
+}
+
+/// Prints the indices of the minimal quorums, in the order of --minimal-quorums, that each node
+/// belongs to
+fn print_quorum_membership(fbas: &Fbas) {
+    let membership: Vec<(PublicKey, Vec<usize>)> =
+        fbas_analyzer::to_public_keys(0..fbas.number_of_nodes(), fbas)
+            .into_iter()
+            .zip(minimal_quorum_membership(fbas))
+            .collect();
+    println!("Minimal quorums each node belongs to as (PK, Quorum indices):\n {membership:?}");
+}
+
+fn print_symmetric_groups(fbas: &Fbas, use_pks: bool) {
+    let groups = symmetric_node_groups(fbas);
+    let groups = if use_pks {
+        format!(
+            "{:?}",
+            groups
+                .into_iter()
+                .map(|group| fbas_analyzer::to_public_keys(group, fbas))
+                .collect::<Vec<Vec<PublicKey>>>()
+        )
+    } else {
+        format!("{groups:?}")
+    };
+    println!("Nodes with the same quorum set:\n {groups}");
+}
+
+/// Prints the cost of enumerating all coalitions of the top tier. The memory estimate assumes the
+/// worst case in which every coalition is winning.
+fn print_enumeration_estimate(top_tier_size: usize) {
+    let coalitions = 2f64.powi(top_tier_size as i32);
+    println!(
+        "Estimated cost of the exact enumeration: 2^{} = {} coalitions, ~{:.3} s, ~{:.3} MiB",
+        top_tier_size,
+        coalitions,
+        coalitions * SECONDS_PER_COALITION,
+        coalitions * BYTES_PER_COALITION / (1024.0 * 1024.0)
+    );
+}
+
+fn print_sample_suggestion(cfg: &RankingAlgConfig, fbas: &Fbas) {
+    if let RankingAlgConfig::PowerIndexApprox {
+        suggest_samples: Some(target_error),
+        ..
+    } = cfg
+    {
+        let bound =
+            castro_sample_bound(fbas.number_of_nodes(), *target_error, SUGGESTION_CONFIDENCE);
+        println!(
+            "Suggested number of samples for a maximum error of {target_error} at {SUGGESTION_CONFIDENCE} confidence: {bound}"
+        );
     }
 }
 
-fn get_top_tier_nodes(fbas: &Fbas, qi_check: bool) -> Vec<NodeId> {
-    let min_qs = fbas_analyzer::find_minimal_quorums(fbas);
+fn get_top_tier_nodes(min_qs: &[NodeIdSet], qi_check: bool) -> Vec<NodeId> {
     if qi_check {
         info!("Ensuring the FBAS has quorum intersection.");
         assert!(
-            fbas_analyzer::all_intersect(&min_qs),
+            fbas_analyzer::all_intersect(min_qs),
             "FBAS lacks quorum intersection!"
         );
         info!("FBAS enjoys quorum intersection!");
     }
-    let involved_nodes: Vec<NodeId> = fbas_analyzer::involved_nodes(&min_qs).into_iter().collect();
+    let involved_nodes: Vec<NodeId> = fbas_analyzer::involved_nodes(min_qs).into_iter().collect();
     info!("Computed top tier with {} nodes.", involved_nodes.len());
     involved_nodes
 }
@@ -133,57 +630,977 @@ fn get_top_tier_nodes(fbas: &Fbas, qi_check: bool) -> Vec<NodeId> {
 fn main() {
     let cli = Cli::from_args();
     match cli.subcommand {
-        SubCommand::Rank(cmd) => {
-            let ignore_inactive_nodes = cmd.ignore_inactive_nodes;
-            let alg_cfg = cmd.alg;
-            let use_pks = cmd.pks;
-            let fbas = load_fbas(cmd.nodes_path.as_ref(), ignore_inactive_nodes);
-            let node_ids: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
-            let qi_check = !cmd.dont_check_for_qi;
-            let log_level = cmd.log_level;
-            let env = Env::default()
-                .filter_or("MY_LOG_LEVEL", log_level)
-                .write_style_or("MY_LOG_STYLE", "always");
-            env_logger::init_from_env(env);
-            let mut alg = get_ranking_alg_from_params(alg_cfg);
-            alg = match alg {
-                RankingAlg::PowerIndexEnum(_) => {
-                    RankingAlg::PowerIndexEnum(Some(get_top_tier_nodes(&fbas, qi_check)))
-                }
-                _ => alg,
-            };
-            let rankings = compute_influence(&node_ids, &fbas, alg, use_pks, qi_check);
-            println!("List of Rankings as (NodeId, PK, Score):\n {rankings:?}");
-        }
-        SubCommand::Distribute(cmd) => {
-            let ignore_inactive_nodes = cmd.ignore_inactive_nodes;
-            let alg_cfg = cmd.alg;
-            let total_reward = cmd.total_reward;
-            let use_pks = cmd.pks;
-            let fbas = load_fbas(cmd.nodes_path.as_ref(), ignore_inactive_nodes);
-            let node_ids: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
-            let qi_check = !cmd.dont_check_for_qi;
-            let log_level = cmd.log_level;
-            let env = Env::default()
-                .filter_or("MY_LOG_LEVEL", log_level)
-                .write_style_or("MY_LOG_STYLE", "always");
-            env_logger::init_from_env(env);
-            let mut alg = get_ranking_alg_from_params(alg_cfg);
-            alg = match alg {
-                RankingAlg::PowerIndexEnum(_) => {
-                    RankingAlg::PowerIndexEnum(Some(get_top_tier_nodes(&fbas, qi_check)))
-                }
-                _ => alg,
-            };
-            let allocation =
-                distribute_rewards(alg, &node_ids, &fbas, total_reward, use_pks, qi_check);
-            println!("List of Distributions as (NodeId, PK, Score, Reward):\n {allocation:?}");
+        SubCommand::Rank(cmd) => rank(cmd),
+        SubCommand::Distribute(cmd) => distribute(cmd),
+        SubCommand::Validate(cmd) => validate(cmd),
+        SubCommand::ExportGame(cmd) => export_game(cmd),
+        SubCommand::Apportion(cmd) => apportion(cmd),
+        SubCommand::ExportDot(cmd) => export_dot_graph(cmd),
+        SubCommand::All(cmd) => compare_all(cmd),
+    };
+}
+
+fn rank(cmd: RankCmds) {
+    assert!(
+        cmd.format != Some(ReportFormat::BasisPoints),
+        "The basis-points format is only supported by the distribute subcommand"
+    );
+    let ignore_inactive_nodes = cmd.ignore_inactive_nodes;
+    let alg_cfg = cmd.alg;
+    let confidence = match alg_cfg {
+        RankingAlgConfig::PowerIndexApprox { bootstrap_ci, .. } => bootstrap_ci,
+        _ => None,
+    };
+    let with_ci = matches!(
+        alg_cfg,
+        RankingAlgConfig::PowerIndexApprox { with_ci: true, .. }
+    );
+    let (show_pagerank, verbose, lscc_only, pagerank_backend) = match alg_cfg {
+        RankingAlgConfig::NodeRank {
+            show_pagerank,
+            verbose,
+            lscc_only,
+            pagerank_backend,
+        } => (show_pagerank, verbose, lscc_only, pagerank_backend),
+        _ => (false, false, false, PageRankBackend::default()),
+    };
+    let mut options = AlgOptions {
+        absolute_banzhaf: matches!(
+            alg_cfg,
+            RankingAlgConfig::BanzhafIndexEnum { absolute: true }
+        ),
+        pagerank_backend,
+        lscc_only,
+        seed: None,
+        scale: cmd.scale,
+    };
+    let exact_timeout = exact_timeout(&alg_cfg);
+    let max_nodes = max_nodes(&alg_cfg);
+    let use_pks = cmd.pks;
+    if let Some(dir) = cmd.series {
+        let alg = get_ranking_alg_from_params(alg_cfg);
+        print_time_series(&dir, ignore_inactive_nodes, alg, !cmd.dont_check_for_qi);
+        return;
+    }
+    let mut profile = Profile::new(cmd.profile);
+    let (mut fbas, loading_secs) = timed_secs!(load_fbas(
+        cmd.nodes_path.as_ref(),
+        ignore_inactive_nodes,
+        cmd.base64,
+        cmd.nodes_b64.as_deref(),
+    ));
+    profile.record("loading", loading_secs);
+    if let Some(other_path) = cmd.merge_with.as_ref() {
+        fbas = merge_fbas(&fbas, &Fbas::from_json_file(other_path));
+        info!("Merged FBAS has {} nodes.", fbas.number_of_nodes());
+    }
+    if let Some(status_path) = cmd.status_path.as_ref() {
+        fbas = without_nodes_inactive_in_status(&fbas, status_path);
+    }
+    options.seed = approximation_seed(&alg_cfg, &fbas);
+    let node_ids: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+    let qi_check = !cmd.dont_check_for_qi;
+    let log_level = cmd.log_level;
+    let env = Env::default()
+        .filter_or("MY_LOG_LEVEL", log_level)
+        .write_style_or("MY_LOG_STYLE", "always");
+    env_logger::init_from_env(env);
+    let min_qs = MinimalQuorums::new(&fbas);
+    if qi_check {
+        exit_if_quorums_do_not_intersect(&fbas, min_qs.get());
+        if !cmd.skip_qi_report {
+            warn_about_unreachable_nodes(&fbas, min_qs.get(), use_pks);
+        }
+    }
+    if cmd.format.is_none() {
+        if !cmd.skip_qi_report {
+            print_qi_report(min_qs.get());
+        }
+        if cmd.groups {
+            print_symmetric_groups(&fbas, use_pks);
+        }
+        if cmd.minimal_quorums {
+            println!("Minimal quorums:\n {:?}", minimal_quorums_with_pks(&fbas));
+        }
+        if cmd.quorum_membership {
+            print_quorum_membership(&fbas);
+        }
+        print_sample_suggestion(&alg_cfg, &fbas);
+        if let Some(seed) = options.seed {
+            println!("Seed: {seed}");
+        }
+    }
+    let estimate_only = matches!(
+        alg_cfg,
+        RankingAlgConfig::PowerIndexEnum { estimate: true, .. }
+    );
+    let (alg, top_tier_secs) = timed_secs!(with_top_tier(
+        get_ranking_alg_from_params(alg_cfg),
+        &min_qs,
+        qi_check
+    ));
+    profile.record("top tier", top_tier_secs);
+    if let (true, RankingAlg::PowerIndexEnum(Some(top_tier))) = (estimate_only, &alg) {
+        print_enumeration_estimate(top_tier.len());
+        return;
+    }
+    abort_if_top_tier_too_large(&alg, max_nodes);
+    let is_approx = matches!(alg, RankingAlg::PowerIndexApprox(..));
+    let is_node_rank = alg == RankingAlg::NodeRank;
+    let qset_hashes = cmd.qset_hash.then(|| quorum_set_hashes(&fbas));
+    let influence = {
+        let (node_ids, fbas) = (node_ids.clone(), fbas.clone());
+        move |alg| compute_influence(&node_ids, &fbas, alg, options, use_pks, qi_check)
+    };
+    let normal_ci = match (&alg, with_ci) {
+        (RankingAlg::PowerIndexApprox(samples, _), true) => Some(scaled_with_ci(
+            approx_power_indices_with_normal_ci(
+                &fbas,
+                *samples,
+                CI_CONFIDENCE,
+                options.seed,
+                qi_check,
+            ),
+            options.scale,
+        )),
+        _ => None,
+    };
+    if let Some(format) = cmd.format {
+        let ((alg, rankings), ranking_secs) = timed_secs!(match &normal_ci {
+            Some(scores_with_ci) => {
+                let scores = scores_with_ci.iter().map(|s| s.0).collect();
+                (
+                    alg,
+                    create_node_ranking_report(&node_ids, scores, &fbas, use_pks),
+                )
+            }
+            None => with_exact_timeout(alg, exact_timeout, false, influence),
+        });
+        profile.record("ranking", ranking_secs);
+        let intervals: Option<Vec<(f64, f64)>> = normal_ci
+            .as_ref()
+            .map(|scores_with_ci| scores_with_ci.iter().map(|s| s.1).collect());
+        let algorithm = algorithm_name(&alg);
+        write_records(
+            ranking_records(
+                &rankings,
+                qset_hashes.as_deref(),
+                options.seed,
+                intervals.as_deref(),
+            ),
+            format,
+            algorithm,
+            cmd.precision,
+        );
+        profile.print();
+        return;
+    }
+    let (scores, ranking_secs): (Vec<(NodeId, Score)>, f64) = timed_secs!(if let (
+        RankingAlg::PowerIndexApprox(samples, _),
+        Some(confidence),
+    ) = (&alg, confidence)
+    {
+        let scores_with_ci = scaled_with_ci(
+            approx_power_indices_with_ci(
+                &fbas,
+                *samples,
+                confidence,
+                BOOTSTRAP_RESAMPLES,
+                options.seed,
+                qi_check,
+            ),
+            options.scale,
+        );
+        let scores: Vec<Score> = scores_with_ci.iter().map(|s| s.0).collect();
+        let rankings = create_node_ranking_report(&node_ids, scores, &fbas, use_pks);
+        println!("List of Rankings as (NodeId, PK, Score, Rank):\n {rankings:?}");
+        let intervals: Vec<(NodeId, f64, f64)> = rankings
+            .iter()
+            .map(|r| (r.0, scores_with_ci[r.0].1 .0, scores_with_ci[r.0].1 .1))
+            .collect();
+        println!(
+            "Bootstrap confidence intervals ({confidence}) as (NodeId, Low, High):\n {intervals:?}"
+        );
+        rankings.iter().map(|r| (r.0, r.2)).collect()
+    } else if let Some(scores_with_ci) = normal_ci {
+        let scores: Vec<Score> = scores_with_ci.iter().map(|s| s.0).collect();
+        let rankings: Vec<(NodeId, PublicKey, Score, Rank, f64, f64)> =
+            create_node_ranking_report(&node_ids, scores, &fbas, use_pks)
+                .into_iter()
+                .map(|(node, pk, score, rank)| {
+                    let (low, high) = scores_with_ci[node].1;
+                    (node, pk, score, rank, low, high)
+                })
+                .collect();
+        println!(
+            "List of Rankings as (NodeId, PK, Score, Rank, CI low, CI high) at {CI_CONFIDENCE} \
+             confidence:\n {rankings:?}"
+        );
+        rankings.iter().map(|r| (r.0, r.2)).collect()
+    } else if verbose {
+        let (scores, pr_scores) =
+            compute_node_rank_for_fbas_with_backend(&node_ids, &fbas, qi_check, pagerank_backend);
+        let amplification = amplification_factors(&scores, &pr_scores);
+        let scores = scaled(scores, options.scale);
+        let rankings: Vec<(NodeId, PublicKey, Score, Rank, Score, f64)> =
+            create_node_ranking_report(&node_ids, scores, &fbas, use_pks)
+                .into_iter()
+                .map(|(node, pk, score, rank)| {
+                    (node, pk, score, rank, pr_scores[node], amplification[node])
+                })
+                .collect();
+        println!(
+                "List of Rankings as (NodeId, PK, Score, Rank, PageRank, Amplification):\n {rankings:?}"
+            );
+        rankings.iter().map(|r| (r.0, r.2)).collect()
+    } else if show_pagerank {
+        let (scores, pr_scores) =
+            compute_node_rank_for_fbas_with_backend(&node_ids, &fbas, qi_check, pagerank_backend);
+        let scores = scaled(scores, options.scale);
+        let rankings: Vec<(NodeId, PublicKey, Score, Rank, Score)> =
+            create_node_ranking_report(&node_ids, scores, &fbas, use_pks)
+                .into_iter()
+                .map(|(node, pk, score, rank)| (node, pk, score, rank, pr_scores[node]))
+                .collect();
+        println!("List of Rankings as (NodeId, PK, Score, Rank, PageRank):\n {rankings:?}");
+        rankings.iter().map(|r| (r.0, r.2)).collect()
+    } else {
+        let (_, rankings) = with_exact_timeout(alg, exact_timeout, true, influence);
+        println!("List of Rankings as (NodeId, PK, Score, Rank):\n {rankings:?}");
+        rankings.iter().map(|r| (r.0, r.2)).collect()
+    });
+    profile.record("ranking", ranking_secs);
+    let mut scores_by_id = vec![Score::default(); fbas.number_of_nodes()];
+    for (node, score) in scores {
+        scores_by_id[node] = score;
+    }
+    println!(
+        "Decentralization index: {:.3}",
+        decentralization_index(&scores_by_id)
+    );
+    if is_approx {
+        println!(
+            "Score sum deviation: {:.3}",
+            score_sum_deviation(&scores_by_id)
+        );
+    }
+    if let Some(threshold) = cmd.controlling {
+        let controlling = controlling_set(&scores_by_id, &fbas, threshold);
+        if use_pks {
+            println!(
+                "Controlling set (> {threshold}):\n {:?}",
+                fbas_analyzer::to_public_keys(controlling, &fbas)
+            );
+        } else {
+            println!("Controlling set (> {threshold}):\n {controlling:?}");
+        }
+    }
+    if let Some(node) = cmd.explain {
+        explain_score(&fbas, node, &scores_by_id, is_node_rank, qi_check);
+    }
+    profile.print();
+}
+
+/// Prints a coalition the node is critical for or, if it is a dummy player, why its score is 0
+fn explain_score(fbas: &Fbas, node: NodeId, scores: &[Score], is_node_rank: bool, qi_check: bool) {
+    assert!(node < scores.len(), "Unknown node {node}");
+    if let Some(coalition) = smallest_critical_coalition(fbas, node, qi_check) {
+        println!(
+            "Node {node} is critical for the winning coalition {:?}",
+            coalition.iter().collect::<Vec<NodeId>>()
+        );
+        return;
+    }
+    println!("Node {node} is a dummy player: it is never critical in any winning coalition.");
+    if is_node_rank && scores[node] == 0.0 {
+        let trusting = trusting_nodes(fbas, node);
+        if trusting.is_empty() {
+            println!("It belongs to no quorum set, so it receives no NodeRank.");
+        } else {
+            let pagerank = rank_nodes_using_page_rank(fbas, false);
+            if trusting.iter().all(|&v| pagerank[v] == 0.0) {
+                println!(
+                    "It only belongs to quorum sets of nodes with zero PageRank, so it receives \
+                     no NodeRank."
+                );
+            }
+        }
+    }
+}
+
+fn distribute(cmd: DistCmds) {
+    let ignore_inactive_nodes = cmd.ignore_inactive_nodes;
+    let alg_cfg = cmd.alg;
+    let total_reward = cmd.total_reward;
+    let exact_timeout = exact_timeout(&alg_cfg);
+    let max_nodes = max_nodes(&alg_cfg);
+    let use_pks = cmd.pks;
+    let ascending = cmd.ascending;
+    let mut profile = Profile::new(cmd.profile);
+    let (mut fbas, loading_secs) = timed_secs!(load_fbas(
+        cmd.nodes_path.as_ref(),
+        ignore_inactive_nodes,
+        cmd.base64,
+        cmd.nodes_b64.as_deref(),
+    ));
+    profile.record("loading", loading_secs);
+    if let Some(status_path) = cmd.status_path.as_ref() {
+        fbas = without_nodes_inactive_in_status(&fbas, status_path);
+    }
+    let seed = approximation_seed(&alg_cfg, &fbas);
+    let qi_check = !cmd.dont_check_for_qi;
+    let log_level = cmd.log_level;
+    let env = Env::default()
+        .filter_or("MY_LOG_LEVEL", log_level)
+        .write_style_or("MY_LOG_STYLE", "always");
+    env_logger::init_from_env(env);
+    let min_qs = MinimalQuorums::new(&fbas);
+    if qi_check {
+        exit_if_quorums_do_not_intersect(&fbas, min_qs.get());
+        if !cmd.skip_qi_report {
+            warn_about_unreachable_nodes(&fbas, min_qs.get(), use_pks);
+        }
+    }
+    if cmd.format.is_none() {
+        if !cmd.skip_qi_report {
+            print_qi_report(min_qs.get());
+        }
+        if cmd.groups {
+            print_symmetric_groups(&fbas, use_pks);
+        }
+        if cmd.minimal_quorums {
+            println!("Minimal quorums:\n {:?}", minimal_quorums_with_pks(&fbas));
+        }
+        if cmd.quorum_membership {
+            print_quorum_membership(&fbas);
         }
+        print_sample_suggestion(&alg_cfg, &fbas);
+        if let Some(seed) = seed {
+            println!("Seed: {seed}");
+        }
+    }
+    let estimate_only = matches!(
+        alg_cfg,
+        RankingAlgConfig::PowerIndexEnum { estimate: true, .. }
+    );
+    let (alg, top_tier_secs) = timed_secs!(with_top_tier(
+        get_ranking_alg_from_params(alg_cfg),
+        &min_qs,
+        qi_check
+    ));
+    profile.record("top tier", top_tier_secs);
+    if let (true, RankingAlg::PowerIndexEnum(Some(top_tier))) = (estimate_only, &alg) {
+        print_enumeration_estimate(top_tier.len());
+        return;
+    }
+    abort_if_top_tier_too_large(&alg, max_nodes);
+    let apportionment = cmd.apportionment;
+    // the top tier has just been derived from the FBAS, so there is no need to validate it
+    let dist_config = DistConfig {
+        reward_exponent: if cmd.sqrt { 0.5 } else { 1.0 },
+        seed,
+        strict: false,
     };
+    let allocate = {
+        let fbas = fbas.clone();
+        move |alg| {
+            if let Some(method) = apportionment {
+                assert!(
+                    total_reward >= 0.0 && total_reward.fract() == 0.0,
+                    "Apportionment requires the reward to be a whole number of units"
+                );
+                let config = RankConfig {
+                    seed,
+                    strict: false,
+                    ..Default::default()
+                };
+                let scores = rank_nodes_with_config(&fbas, alg, qi_check, config);
+                let units = allocate_integer_rewards(&scores, total_reward as u64, method);
+                let id_score_reward = units
+                    .into_iter()
+                    .map(|(node, score, share)| (node, score, share as Reward))
+                    .collect();
+                create_reward_report(id_score_reward, &fbas, use_pks, ascending)
+            } else {
+                distribute_rewards(
+                    alg,
+                    &fbas,
+                    total_reward,
+                    dist_config,
+                    use_pks,
+                    ascending,
+                    qi_check,
+                )
+            }
+        }
+    };
+    // the rankings and the rewards are computed in one go, see distribute_rewards
+    let ((alg, allocation), allocation_secs) = timed_secs!(with_exact_timeout(
+        alg,
+        exact_timeout,
+        cmd.format.is_none(),
+        allocate
+    ));
+    profile.record("ranking and reward allocation", allocation_secs);
+    let algorithm = algorithm_name(&alg);
+    if let Some(format) = cmd.format {
+        let qset_hashes = cmd.qset_hash.then(|| quorum_set_hashes(&fbas));
+        let allocation = if format == ReportFormat::BasisPoints {
+            let mut scores = vec![Score::default(); fbas.number_of_nodes()];
+            for (node, _, score, _, _) in allocation.iter() {
+                scores[*node] = *score;
+            }
+            let weights = reward_weights(&scores, dist_config.reward_exponent);
+            let id_score_bps = allocate_basis_points(&weights)
+                .into_iter()
+                .map(|(node, _, bps)| (node, scores[node], bps as Reward))
+                .collect();
+            create_reward_report(id_score_bps, &fbas, use_pks, ascending)
+        } else {
+            allocation
+        };
+        write_records(
+            reward_records(&allocation, qset_hashes.as_deref(), seed),
+            format,
+            algorithm,
+            cmd.precision,
+        );
+        profile.print();
+        return;
+    }
+    println!("List of Distributions as (NodeId, PK, Score, Reward, Rank):\n {allocation:?}");
+    let mut rewards = vec![Reward::default(); fbas.number_of_nodes()];
+    for (node, _, _, reward, _) in allocation.iter() {
+        rewards[*node] = *reward;
+    }
+    let top_tier = get_top_tier_nodes(min_qs.get(), qi_check);
+    let summary = reward_summary(&rewards, &top_tier);
+    println!(
+        "Reward summary as (Total, Mean, Median, Top tier mean):\n ({}, {}, {}, {})",
+        summary.total, summary.mean, summary.median, summary.top_tier_mean
+    );
+    println!(
+        "Top tier reward fraction: {:.3}",
+        top_tier_reward_fraction(&rewards, &top_tier)
+    );
+    if cmd.compare_equal {
+        let all_nodes: Vec<NodeId> = (0..fbas.number_of_nodes()).collect();
+        let equal_shares = equal_split_rewards(&all_nodes, total_reward);
+        let comparison: Vec<(NodeId, PublicKey, Reward, Reward, Reward)> = allocation
+            .iter()
+            .map(|(node, pk, _, reward, _)| {
+                let equal_share = equal_shares[*node].1;
+                (
+                    *node,
+                    pk.clone(),
+                    *reward,
+                    equal_share,
+                    reward - equal_share,
+                )
+            })
+            .collect();
+        println!(
+            "Comparison with an equal split as (NodeId, PK, Reward, Equal share, Difference):\n {comparison:?}"
+        );
+    }
+    profile.print();
+}
+
+/// Durations of the phases of a run, printed to STDERR if profiling is enabled
+#[derive(Debug, Default)]
+struct Profile {
+    enabled: bool,
+    /// (phase, duration in seconds) in the order the phases ran
+    phases: Vec<(&'static str, f64)>,
 }
 
-fn load_fbas(o_nodes_path: Option<&PathBuf>, ignore_inactive_nodes: bool) -> Fbas {
-    let fbas = if let Some(nodes_path) = o_nodes_path {
+impl Profile {
+    fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            ..Default::default()
+        }
+    }
+    fn record(&mut self, phase: &'static str, secs: f64) {
+        self.phases.push((phase, secs));
+    }
+    fn print(&self) {
+        if !self.enabled {
+            return;
+        }
+        eprintln!("Profile as (Phase, Seconds):");
+        for (phase, secs) in self.phases.iter() {
+            eprintln!(" {phase}: {secs:.6}");
+        }
+    }
+}
+
+/// Adds the top tier to the algorithms that enumerate its coalitions, leaves the others as they are
+fn with_top_tier(alg: RankingAlg, min_qs: &MinimalQuorums, qi_check: bool) -> RankingAlg {
+    match alg {
+        RankingAlg::PowerIndexEnum(_) => {
+            RankingAlg::PowerIndexEnum(Some(get_top_tier_nodes(min_qs.get(), qi_check)))
+        }
+        RankingAlg::BanzhafIndexEnum(_) => {
+            RankingAlg::BanzhafIndexEnum(Some(get_top_tier_nodes(min_qs.get(), qi_check)))
+        }
+        _ => alg,
+    }
+}
+
+/// Settings of the rank subcommand that only apply to some of the algorithms
+#[derive(Debug, Clone, Copy, Default)]
+struct AlgOptions {
+    /// Absolute instead of normalised Banzhaf index
+    absolute_banzhaf: bool,
+    /// PageRank implementation NodeRank is based on
+    pagerank_backend: PageRankBackend,
+    /// Only rank the largest strongly connected component with NodeRank
+    lscc_only: bool,
+    /// Seed of the approximation
+    seed: Option<u64>,
+    /// Scale of the reported scores, if any
+    scale: Option<ScoreScale>,
+}
+
+/// Seed the approximation is run with: the passed one, one derived from the FBAS or a random one
+/// so that every run can be replayed. None for the other algorithms.
+fn approximation_seed(cfg: &RankingAlgConfig, fbas: &Fbas) -> Option<u64> {
+    match cfg {
+        RankingAlgConfig::PowerIndexApprox {
+            seed,
+            seed_from_fbas,
+            ..
+        } => Some(seed.unwrap_or_else(|| {
+            if *seed_from_fbas {
+                fbas_seed(fbas)
+            } else {
+                rand::random()
+            }
+        })),
+        _ => None,
+    }
+}
+
+/// Time limit of the exact enumeration, if any
+fn exact_timeout(cfg: &RankingAlgConfig) -> Option<Duration> {
+    match cfg {
+        RankingAlgConfig::PowerIndexEnum {
+            exact_timeout_secs, ..
+        } => exact_timeout_secs.map(Duration::from_secs),
+        _ => None,
+    }
+}
+
+/// Largest top tier the exact enumeration is attempted for, None if unlimited
+fn max_nodes(cfg: &RankingAlgConfig) -> Option<usize> {
+    match cfg {
+        RankingAlgConfig::PowerIndexEnum { max_nodes, .. } if *max_nodes > 0 => Some(*max_nodes),
+        _ => None,
+    }
+}
+
+/// Exits with an error listing two disjoint quorums if the FBAS lacks quorum intersection
+fn exit_if_quorums_do_not_intersect(fbas: &Fbas, min_qs: &[NodeIdSet]) {
+    if let Some((a, b)) = quorum_intersection_witness(min_qs) {
+        eprintln!(
+            "FBAS lacks quorum intersection! These quorums are disjoint:\n {:?}\n {:?}",
+            fbas_analyzer::to_public_keys(a.iter(), fbas),
+            fbas_analyzer::to_public_keys(b.iter(), fbas)
+        );
+        std::process::exit(1);
+    }
+}
+
+/// Exits with an error suggesting the approximation if the exact enumeration would have to go
+/// through the coalitions of a top tier of more than max_nodes nodes
+fn abort_if_top_tier_too_large(alg: &RankingAlg, max_nodes: Option<usize>) {
+    if let (RankingAlg::PowerIndexEnum(Some(top_tier)), Some(max_nodes)) = (alg, max_nodes) {
+        if top_tier.len() > max_nodes {
+            eprintln!(
+                "The top tier has {} nodes, more than the maximum of {} for the exact enumeration. \
+                 Use power-index-approx instead or raise the limit with --max-nodes (0 disables it).",
+                top_tier.len(),
+                max_nodes
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Runs compute with the passed algorithm. If the algorithm is the exact enumeration and a timeout
+/// is passed, the enumeration runs in a worker thread; if it does not finish in time, compute is
+/// run again with an approximation of FALLBACK_SAMPLES samples. The worker keeps running until
+/// the program exits. Returns the algorithm that was used along with the result. announce prints
+/// a note on the fallback.
+fn with_exact_timeout<T, F>(
+    alg: RankingAlg,
+    timeout: Option<Duration>,
+    announce: bool,
+    compute: F,
+) -> (RankingAlg, T)
+where
+    T: Send + 'static,
+    F: Fn(RankingAlg) -> T + Clone + Send + 'static,
+{
+    let timeout = match (&alg, timeout) {
+        (RankingAlg::PowerIndexEnum(_), Some(timeout)) => timeout,
+        _ => return (alg.clone(), compute(alg)),
+    };
+    let (sender, receiver) = mpsc::channel();
+    let worker_alg = alg.clone();
+    let worker_compute = compute.clone();
+    thread::spawn(move || {
+        // the receiver is gone if we timed out already
+        let _ = sender.send(worker_compute(worker_alg));
+    });
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => (alg, result),
+        Err(_) => {
+            if announce {
+                println!(
+                    "Exact enumeration did not finish within {timeout:?}, falling back to \
+                     power-index-approx with {FALLBACK_SAMPLES} samples. The scores are \
+                     approximate."
+                );
+            }
+            let fallback = RankingAlg::PowerIndexApprox(FALLBACK_SAMPLES, None);
+            (fallback.clone(), compute(fallback))
+        }
+    }
+}
+
+/// Name of the algorithm in the JSON output, same as the name of its subcommand
+fn algorithm_name(alg: &RankingAlg) -> &'static str {
+    match alg {
+        RankingAlg::NodeRank => "node-rank",
+        RankingAlg::PowerIndexEnum(_) => "power-index-enum",
+        RankingAlg::PowerIndexApprox(..) => "power-index-approx",
+        RankingAlg::BanzhafIndexEnum(_) => "banzhaf-index-enum",
+    }
+}
+
+/// Writes CSV lines, a JSON envelope or a Markdown table containing the records. precision is
+/// only used by the Markdown table.
+fn write_records<T: serde::Serialize + MarkdownRecord>(
+    records: Vec<T>,
+    format: ReportFormat,
+    algorithm: &str,
+    precision: usize,
+) {
+    match format {
+        ReportFormat::Csv | ReportFormat::BasisPoints => {
+            write_csv_to_stdout(records).expect("Error writing output")
+        }
+        ReportFormat::Json => {
+            serde_json::to_writer_pretty(io::stdout(), &JsonEnvelope::new(algorithm, records))
+                .expect("Error writing output");
+            println!();
+        }
+        ReportFormat::Markdown => print!("{}", markdown_table(&records, precision)),
+    }
+}
+
+/// Raise every score to the reward exponent so that the basis points follow the same weighting as
+/// the rewards
+fn reward_weights(scores: &[Score], reward_exponent: f64) -> Vec<Score> {
+    scores.iter().map(|s| s.powf(reward_exponent)).collect()
+}
+
+fn print_time_series(dir: &Path, ignore_inactive_nodes: bool, alg: RankingAlg, qi_check: bool) {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .expect("Failed to read the series directory")
+        .map(|entry| entry.expect("Failed to read the series directory").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+    let snapshots: Vec<(String, Fbas)> = paths
+        .iter()
+        .map(|path| {
+            let date = path.file_stem().unwrap().to_string_lossy().into_owned();
+            (
+                date,
+                load_fbas(Some(path), ignore_inactive_nodes, false, None),
+            )
+        })
+        .collect();
+    let series = rank_time_series(&snapshots, alg, qi_check);
+    println!("Scores over time as (PK, [(Date, Score)]):\n {series:?}");
+}
+
+/// Prints a pass/fail line per check and exits with an error if any check fails
+fn validate(cmd: ValidateCmds) {
+    let fbas = load_fbas(
+        cmd.nodes_path.as_ref(),
+        cmd.ignore_inactive_nodes,
+        false,
+        None,
+    );
+    let env = Env::default()
+        .filter_or("MY_LOG_LEVEL", cmd.log_level)
+        .write_style_or("MY_LOG_STYLE", "always");
+    env_logger::init_from_env(env);
+    let min_qs = fbas_analyzer::find_minimal_quorums(&fbas);
+    let top_tier_size = fbas_analyzer::involved_nodes(&min_qs).len();
+    let empty_qsets = nodes_with_empty_quorum_sets(&fbas);
+    let self_trusting = self_trusting_nodes(&fbas);
+    let checks = [
+        (
+            "Quorum intersection".to_string(),
+            fbas_analyzer::all_intersect(&min_qs),
+        ),
+        (
+            format!("No empty quorum sets {empty_qsets:?}"),
+            empty_qsets.is_empty(),
+        ),
+        (
+            format!("No nodes trusting only themselves {self_trusting:?}"),
+            self_trusting.is_empty(),
+        ),
+        (
+            format!(
+                "Top tier size {top_tier_size} within exact enumeration limit {MAX_EXACT_TOP_TIER_SIZE}"
+            ),
+            top_tier_size <= MAX_EXACT_TOP_TIER_SIZE,
+        ),
+    ];
+    for (check, passed) in checks.iter() {
+        println!("{check}: {}", if *passed { "pass" } else { "fail" });
+    }
+    if checks.iter().all(|(_, passed)| *passed) {
+        println!("All checks passed");
+    } else {
+        println!("Some checks failed");
+        std::process::exit(1);
+    }
+}
+
+/// Writes the top tier's winning coalitions as JSON
+fn export_game(cmd: ExportGameCmds) {
+    let fbas = load_fbas(
+        cmd.nodes_path.as_ref(),
+        cmd.ignore_inactive_nodes,
+        false,
+        None,
+    );
+    let env = Env::default()
+        .filter_or("MY_LOG_LEVEL", cmd.log_level)
+        .write_style_or("MY_LOG_STYLE", "always");
+    env_logger::init_from_env(env);
+    let min_qs = fbas_analyzer::find_minimal_quorums(&fbas);
+    let top_tier_size = get_top_tier_nodes(&min_qs, !cmd.dont_check_for_qi).len();
+    if top_tier_size > MAX_EXPORT_TOP_TIER_SIZE {
+        warn!(
+            "The top tier has {} nodes, the export can contain up to 2^{} winning coalitions and become huge",
+            top_tier_size, top_tier_size
+        );
+    }
+    // QI has been checked while computing the top tier
+    let game = serde_json::to_string(&game_record(&fbas, false)).expect("Error serialising JSON");
+    if let Some(path) = cmd.output_path {
+        fs::write(&path, game).expect("Error writing the game to the output file");
+    } else {
+        println!("{game}");
+    }
+}
+
+/// Ranks the nodes and writes the trust graph annotated with their scores as DOT
+fn export_dot_graph(cmd: ExportDotCmds) {
+    let fbas = load_fbas(
+        cmd.nodes_path.as_ref(),
+        cmd.ignore_inactive_nodes,
+        false,
+        None,
+    );
+    let env = Env::default()
+        .filter_or("MY_LOG_LEVEL", cmd.log_level)
+        .write_style_or("MY_LOG_STYLE", "always");
+    env_logger::init_from_env(env);
+    let qi_check = !cmd.dont_check_for_qi;
+    let config = RankConfig {
+        seed: approximation_seed(&cmd.alg, &fbas),
+        strict: false,
+        ..Default::default()
+    };
+    let min_qs = MinimalQuorums::new(&fbas);
+    let alg = with_top_tier(get_ranking_alg_from_params(cmd.alg), &min_qs, qi_check);
+    let scores = rank_nodes_with_config(&fbas, alg, qi_check, config);
+    if let Some(path) = cmd.output_path {
+        export_dot(&fbas, &scores, &path).expect("Error writing the DOT graph to the output file");
+    } else {
+        print!("{}", dot_graph(&fbas, &scores));
+    }
+}
+
+/// Ranks the nodes with every algorithm and prints the scores side by side along with the
+/// pairwise Spearman correlations of the rankings
+fn compare_all(cmd: AllCmds) {
+    let fbas = load_fbas(
+        cmd.nodes_path.as_ref(),
+        cmd.ignore_inactive_nodes,
+        false,
+        None,
+    );
+    let env = Env::default()
+        .filter_or("MY_LOG_LEVEL", cmd.log_level)
+        .write_style_or("MY_LOG_STYLE", "always");
+    env_logger::init_from_env(env);
+    let qi_check = !cmd.dont_check_for_qi;
+    let min_qs = fbas_analyzer::find_minimal_quorums(&fbas);
+    if qi_check {
+        exit_if_quorums_do_not_intersect(&fbas, &min_qs);
+    }
+    let seed = cmd.seed.unwrap_or_else(rand::random);
+    println!("Seed: {seed}");
+    let mut columns: Vec<(&str, Vec<Score>)> = vec![(
+        "NodeRank",
+        rank_nodes(&fbas, RankingAlg::NodeRank, qi_check),
+    )];
+    let top_tier = get_top_tier_nodes(&min_qs, qi_check);
+    if top_tier.len() > cmd.max_nodes {
+        println!(
+            "Skipping the exact Shapley-Shubik index: the top tier has {} nodes, more than the \
+             maximum of {}.",
+            top_tier.len(),
+            cmd.max_nodes
+        );
+    } else {
+        let config = RankConfig {
+            strict: false,
+            ..Default::default()
+        };
+        let exact = rank_nodes_with_config(
+            &fbas,
+            RankingAlg::PowerIndexEnum(Some(top_tier)),
+            qi_check,
+            config,
+        );
+        columns.push(("Exact Shapley-Shubik", exact));
+    }
+    let config = RankConfig {
+        seed: Some(seed),
+        ..Default::default()
+    };
+    let approx = rank_nodes_with_config(
+        &fbas,
+        RankingAlg::PowerIndexApprox(cmd.samples, None),
+        qi_check,
+        config,
+    );
+    columns.push(("Approximate Shapley-Shubik", approx));
+    let pks = if cmd.pks {
+        fbas_analyzer::to_public_keys(0..fbas.number_of_nodes(), &fbas)
+    } else {
+        vec![PublicKey::default(); fbas.number_of_nodes()]
+    };
+    let names: Vec<&str> = columns.iter().map(|c| c.0).collect();
+    let rows: Vec<(NodeId, PublicKey, Vec<Score>)> = (0..fbas.number_of_nodes())
+        .map(|node| {
+            let scores = columns.iter().map(|c| c.1[node]).collect();
+            (node, pks[node].clone(), scores)
+        })
+        .collect();
+    println!(
+        "List of Scores as (NodeId, PK, [{}]):\n {rows:?}",
+        names.join(", ")
+    );
+    for (i, (name_a, scores_a)) in columns.iter().enumerate() {
+        for (name_b, scores_b) in columns[i + 1..].iter() {
+            println!(
+                "Spearman correlation ({name_a}, {name_b}): {:.3}",
+                spearman_correlation(scores_a, scores_b)
+            );
+        }
+    }
+}
+
+/// Distributes the reward according to the scores read from CSV
+fn apportion(cmd: ApportionCmds) {
+    let env = Env::default()
+        .filter_or("MY_LOG_LEVEL", cmd.log_level)
+        .write_style_or("MY_LOG_STYLE", "always");
+    env_logger::init_from_env(env);
+    let scores = load_scores(cmd.scores_path.as_ref());
+    let total_reward = cmd.total_reward;
+    let id_score_reward = if cmd.format == Some(ReportFormat::BasisPoints) {
+        allocate_basis_points(&scores)
+            .into_iter()
+            .map(|(node, score, bps)| (node, score, bps as Reward))
+            .collect()
+    } else if let Some(method) = cmd.apportionment {
+        assert!(
+            total_reward >= 0.0 && total_reward.fract() == 0.0,
+            "Apportionment requires the reward to be a whole number of units"
+        );
+        allocate_integer_rewards(&scores, total_reward as u64, method)
+            .into_iter()
+            .map(|(node, score, share)| (node, score, share as Reward))
+            .collect()
+    } else {
+        apportion_rewards(&scores, total_reward)
+    };
+    // without public keys, the report does not look at the FBAS
+    let fbas = cmd
+        .nodes_path
+        .as_ref()
+        .map_or_else(Fbas::new, |nodes_path| {
+            let fbas = load_fbas(Some(nodes_path), false, false, None);
+            assert_eq!(
+                fbas.number_of_nodes(),
+                scores.len(),
+                "Expected one score per node of the FBAS"
+            );
+            fbas
+        });
+    let allocation = create_reward_report(
+        id_score_reward,
+        &fbas,
+        cmd.nodes_path.is_some(),
+        cmd.ascending,
+    );
+    if let Some(format) = cmd.format {
+        write_records(
+            reward_records(&allocation, None, None),
+            format,
+            // the scores were computed elsewhere
+            "external",
+            cmd.precision,
+        );
+        return;
+    }
+    println!("List of Distributions as (NodeId, PK, Score, Reward, Rank):\n {allocation:?}");
+}
+
+/// Reads the scores CSV and returns the scores indexed by NodeId
+fn load_scores(o_scores_path: Option<&PathBuf>) -> Vec<Score> {
+    let scores = if let Some(scores_path) = o_scores_path {
+        info!("Reading scores CSV from file...");
+        read_scores_csv(fs::File::open(scores_path).expect("Failed to open the scores file"))
+    } else {
+        info!("Reading scores CSV from STDIN...");
+        read_scores_csv(io::stdin())
+    };
+    info!("Loaded scores of {} nodes.", scores.len());
+    scores
+}
+
+fn load_fbas(
+    o_nodes_path: Option<&PathBuf>,
+    ignore_inactive_nodes: bool,
+    base64: bool,
+    o_nodes_b64: Option<&str>,
+) -> Fbas {
+    let fbas = if let Some(encoded) = o_nodes_b64 {
+        info!("Reading base64-encoded FBAS JSON from the command line...");
+        load_fbas_from_base64(encoded, ignore_inactive_nodes)
+    } else if base64 {
+        info!("Reading base64-encoded FBAS JSON from STDIN...");
+        let mut encoded = String::new();
+        io::stdin()
+            .read_to_string(&mut encoded)
+            .expect("Failed to read from STDIN");
+        load_fbas_from_base64(&encoded, ignore_inactive_nodes)
+    } else if let Some(nodes_path) = o_nodes_path {
         info!("Reading FBAS JSON from file...");
         let mut fbas = Fbas::from_json_file(nodes_path);
         if ignore_inactive_nodes {
@@ -206,36 +1623,107 @@ fn load_fbas(o_nodes_path: Option<&PathBuf>, ignore_inactive_nodes: bool) -> Fba
     fbas
 }
 
-/// Rank nodes using either S-S Power Index or NodeRank and return a sorted list of nodes
+/// Removes the nodes marked as `"active" == false` in the status file from the FBAS, matching
+/// them by public key. Nodes missing from the status file are kept.
+fn without_nodes_inactive_in_status(fbas: &Fbas, status_path: &Path) -> Fbas {
+    let inactive_nodes =
+        fbas_analyzer::FilteredNodes::from_json_file(status_path, |v| v["active"] == false);
+    let fbas = fbas.without_nodes_pretty(&inactive_nodes.into_pretty_vec());
+    info!(
+        "{} nodes remain after filtering by the status file.",
+        fbas.number_of_nodes()
+    );
+    fbas
+}
+
+fn load_fbas_from_base64(encoded: &str, ignore_inactive_nodes: bool) -> Fbas {
+    let decoded = STANDARD
+        .decode(encoded.trim())
+        .unwrap_or_else(|e| panic!("Input is not valid base64: {e}"));
+    let json = String::from_utf8(decoded)
+        .unwrap_or_else(|e| panic!("Decoded base64 input is not valid UTF-8: {e}"));
+    let mut fbas = Fbas::from_json_str(&json);
+    if ignore_inactive_nodes {
+        let inactive_nodes =
+            fbas_analyzer::FilteredNodes::from_json_str(&json, |v| v["active"] == false);
+        fbas = fbas.without_nodes_pretty(&inactive_nodes.into_pretty_vec());
+    }
+    fbas
+}
+
+/// Rank nodes using either S-S Power Index, Banzhaf index or NodeRank and return a sorted list of
+/// nodes
 fn compute_influence(
     node_ids: &[NodeId],
     fbas: &Fbas,
     alg: RankingAlg,
+    options: AlgOptions,
     use_pks: bool,
     qi_check: bool,
 ) -> Vec<NodeRanking> {
-    let rankings = rank_nodes(fbas, alg, qi_check);
-    create_node_ranking_report(node_ids, rankings, fbas, use_pks)
+    let rankings = match alg {
+        RankingAlg::BanzhafIndexEnum(top_tier) if options.absolute_banzhaf => {
+            compute_banzhaf_absolute(fbas, top_tier, qi_check)
+        }
+        RankingAlg::NodeRank if options.lscc_only => {
+            let config = RankConfig {
+                lscc_only: true,
+                ..Default::default()
+            };
+            rank_nodes_with_config(fbas, alg, qi_check, config)
+        }
+        RankingAlg::NodeRank if options.pagerank_backend != PageRankBackend::Internal => {
+            compute_node_rank_for_fbas_with_backend(
+                node_ids,
+                fbas,
+                qi_check,
+                options.pagerank_backend,
+            )
+            .0
+        }
+        alg => {
+            // a passed top tier has just been derived from the FBAS, see with_top_tier
+            let config = RankConfig {
+                seed: options.seed,
+                strict: false,
+                ..Default::default()
+            };
+            rank_nodes_with_config(fbas, alg, qi_check, config)
+        }
+    };
+    create_node_ranking_report(node_ids, scaled(rankings, options.scale), fbas, use_pks)
+}
+
+/// Scales the scores if --scale is passed
+fn scaled(scores: Vec<Score>, scale: Option<ScoreScale>) -> Vec<Score> {
+    match scale {
+        Some(scale) => scale_scores(&scores, scale),
+        None => scores,
+    }
+}
+
+/// Scales the scores and the bounds of their confidence intervals if --scale is passed
+fn scaled_with_ci(
+    scores_with_ci: Vec<(Score, (f64, f64))>,
+    scale: Option<ScoreScale>,
+) -> Vec<(Score, (f64, f64))> {
+    match scale {
+        Some(scale) => scale_scores_with_ci(&scores_with_ci, scale),
+        None => scores_with_ci,
+    }
 }
 
 /// Distribute the reward between nodes based on their contribution as calculated by a ranking
 /// algorithm and return a sorted list
 fn distribute_rewards(
     algo: RankingAlg,
-    nodes: &[NodeId],
     fbas: &Fbas,
     reward_value: f64,
+    config: DistConfig,
     use_pks: bool,
+    ascending: bool,
     qi_check: bool,
-) -> Vec<(NodeId, PublicKey, Score, Reward)> {
-    let allocation = match algo {
-        RankingAlg::NodeRank => graph_theory_distribution(nodes, fbas, reward_value, qi_check),
-        RankingAlg::PowerIndexEnum(tt) => {
-            exact_game_theory_distribution(fbas, reward_value, tt, qi_check)
-        }
-        RankingAlg::PowerIndexApprox(samples) => {
-            approx_game_theory_distribution(samples, fbas, reward_value, qi_check)
-        }
-    };
-    create_reward_report(allocation, fbas, use_pks)
+) -> Vec<NodeReward> {
+    let allocation = reward_distribution_with_config(algo, fbas, reward_value, qi_check, config);
+    create_reward_report(allocation, fbas, use_pks, ascending)
 }
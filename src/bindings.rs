@@ -0,0 +1,182 @@
+//! Entry points for running rankings from other runtimes. The functions here take and return
+//! JSON strings so that they do not depend on any file or terminal I/O.
+
+use crate::*;
+use fbas_analyzer::{Fbas, NodeId};
+
+#[cfg(feature = "python")]
+use pyo3::{exceptions::PyValueError, prelude::*};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+/// Parses an FBAS in stellarbeat.org "nodes" format. Unlike Fbas::from_json_str, malformed JSON
+/// is reported as an error instead of a panic.
+fn parse_fbas(nodes_json: &str) -> Result<Fbas, String> {
+    serde_json::from_str(nodes_json).map_err(|e| format!("Invalid FBAS JSON: {e}"))
+}
+
+/// Maps the algorithm names used by the CLI to a RankingAlg. The top tier of the exact game is
+/// computed from the FBAS' minimal quorums.
+#[cfg_attr(target_arch = "wasm32", allow(unused_variables))]
+fn parse_ranking_alg(fbas: &Fbas, alg: &str, samples: usize) -> Result<RankingAlg, String> {
+    match alg {
+        "node-rank" => Ok(RankingAlg::NodeRank),
+        #[cfg(not(target_arch = "wasm32"))]
+        "power-index-enum" => {
+            let min_qs = fbas_analyzer::find_minimal_quorums(fbas);
+            let top_tier: Vec<NodeId> =
+                fbas_analyzer::involved_nodes(&min_qs).into_iter().collect();
+            Ok(RankingAlg::PowerIndexEnum(Some(top_tier)))
+        }
+        "power-index-approx" => Ok(RankingAlg::PowerIndexApprox(samples, None)),
+        #[cfg(target_arch = "wasm32")]
+        "power-index-enum" => {
+            Err("power-index-enum needs GMP, which is not available in wasm builds".into())
+        }
+        _ => Err(format!("Unknown ranking algorithm: {alg}")),
+    }
+}
+
+/// The top tier of parse_ranking_alg is derived from the same FBAS, so validating it would only
+/// find the minimal quorums again
+fn derived_top_tier_config() -> RankConfig {
+    RankConfig {
+        strict: false,
+        ..Default::default()
+    }
+}
+
+/// Parses an FBAS in stellarbeat.org "nodes" format, ranks its nodes using `alg` (one of
+/// "node-rank", "power-index-enum" or "power-index-approx") and returns the rankings as a JSON
+/// array of [id, pk, score, rank] sorted by score. `samples` is only used by
+/// "power-index-approx". Quorum intersection is not checked.
+pub fn rank_fbas_json_to_string(
+    nodes_json: &str,
+    alg: &str,
+    samples: usize,
+) -> Result<String, String> {
+    let fbas = parse_fbas(nodes_json)?;
+    let alg = parse_ranking_alg(&fbas, alg, samples)?;
+    let node_ids: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+    let scores = rank_nodes_with_config(&fbas, alg, false, derived_top_tier_config());
+    let rankings = create_node_ranking_report(&node_ids, scores, &fbas, true);
+    serde_json::to_string(&rankings).map_err(|e| e.to_string())
+}
+
+/// WASM export of `rank_fbas_json_to_string`. Returns the rankings as a JSON string or throws
+/// the error message.
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn rank_fbas_json(nodes_json: &str, alg: &str, samples: u32) -> Result<JsValue, JsValue> {
+    rank_fbas_json_to_string(nodes_json, alg, samples as usize)
+        .map(|json| JsValue::from_str(&json))
+        .map_err(|e| JsValue::from_str(&e))
+}
+
+/// Python export of `rank_nodes`. Returns a list of (node_id, public_key, score) sorted by score.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name = "rank")]
+fn py_rank(
+    nodes_json: &str,
+    alg: &str,
+    samples: usize,
+    qi_check: bool,
+) -> PyResult<Vec<(NodeId, PublicKey, Score)>> {
+    let fbas = parse_fbas(nodes_json).map_err(PyValueError::new_err)?;
+    let alg = parse_ranking_alg(&fbas, alg, samples).map_err(PyValueError::new_err)?;
+    let node_ids: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+    let scores = rank_nodes_with_config(&fbas, alg, qi_check, derived_top_tier_config());
+    Ok(create_node_ranking_report(&node_ids, scores, &fbas, true)
+        .into_iter()
+        .map(|(node, pk, score, _)| (node, pk, score))
+        .collect())
+}
+
+/// Python export of `reward_distribution`. Returns a list of (node_id, public_key, score, reward)
+/// sorted by score.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name = "distribute")]
+fn py_distribute(
+    nodes_json: &str,
+    alg: &str,
+    reward: Reward,
+    samples: usize,
+    qi_check: bool,
+) -> PyResult<Vec<(NodeId, PublicKey, Score, Reward)>> {
+    let fbas = parse_fbas(nodes_json).map_err(PyValueError::new_err)?;
+    let alg = parse_ranking_alg(&fbas, alg, samples).map_err(PyValueError::new_err)?;
+    let config = DistConfig {
+        strict: false,
+        ..Default::default()
+    };
+    let allocation = reward_distribution_with_config(alg, &fbas, reward, qi_check, config);
+    Ok(create_reward_report(allocation, &fbas, true, false)
+        .into_iter()
+        .map(|(node, pk, score, reward, _)| (node, pk, score, reward))
+        .collect())
+}
+
+/// The `pyfbas` Python module
+#[cfg(feature = "python")]
+#[pymodule]
+pub fn pyfbas(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(py_rank, m)?)?;
+    m.add_function(wrap_pyfunction!(py_distribute, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rank_fbas_json_returns_serialized_rankings() {
+        let input = std::fs::read_to_string("test_data/paper_example.json").unwrap();
+        let actual = rank_fbas_json_to_string(&input, "power-index-enum", 0).unwrap();
+        let rankings: Vec<NodeRanking> = serde_json::from_str(&actual).unwrap();
+        assert_eq!(5, rankings.len());
+        assert_eq!((0, 0.466, 1), (rankings[0].0, rankings[0].2, rankings[0].3));
+        assert!(rank_fbas_json_to_string(&input, "page-rank", 0).is_err());
+    }
+
+    #[test]
+    fn malformed_fbas_json_is_an_error() {
+        let actual = rank_fbas_json_to_string(r#"[{ "publicKey": "n0""#, "node-rank", 0);
+        assert!(actual.unwrap_err().starts_with("Invalid FBAS JSON"));
+    }
+
+    #[cfg(feature = "python")]
+    #[test]
+    fn pyfbas_module_ranks_and_distributes() {
+        let input = std::fs::read_to_string("test_data/trivial.json").unwrap();
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let module = PyModule::new(py, "pyfbas").unwrap();
+            pyfbas(&module).unwrap();
+            let rankings: Vec<(NodeId, PublicKey, Score)> = module
+                .getattr("rank")
+                .unwrap()
+                .call1((input.as_str(), "power-index-enum", 0, true))
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert_eq!(3, rankings.len());
+            assert!(rankings.iter().all(|r| r.2 == 0.333));
+            let rewards: Vec<(NodeId, PublicKey, Score, Reward)> = module
+                .getattr("distribute")
+                .unwrap()
+                .call1((input.as_str(), "node-rank", 9.0, 0, true))
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert!(rewards.iter().all(|r| r.3 == 3.0));
+            let malformed = module
+                .getattr("rank")
+                .unwrap()
+                .call1(("[", "node-rank", 0, true));
+            assert!(malformed.unwrap_err().is_instance_of::<PyValueError>(py));
+        });
+    }
+}
@@ -1,26 +1,219 @@
 use crate::*;
-use fbas_analyzer::{Fbas, NodeId};
+use fbas_analyzer::{to_public_keys, Fbas, NodeId};
+use rand::Rng;
+use std::{collections::HashMap, str::FromStr};
 
-/// Distribute rewards according to NodeRank scores and return a list of NodeId, score, reward
+/// Method used to turn scores into integer rewards that sum up exactly to the budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Apportionment {
+    /// Largest remainder method: every node gets the integer part of its quota and the remaining
+    /// units go to the nodes with the largest fractional parts
+    Hamilton,
+    /// Sainte-Laguë divisor method: units are assigned one by one to the node with the highest
+    /// score/(2s + 1), where s is the number of units the node already has
+    Webster,
+}
+
+impl FromStr for Apportionment {
+    type Err = &'static str;
+    fn from_str(method: &str) -> Result<Self, Self::Err> {
+        match method.to_lowercase().as_ref() {
+            "hamilton" => Ok(Apportionment::Hamilton),
+            "webster" => Ok(Apportionment::Webster),
+            _ => Err("Unknown apportionment method"),
+        }
+    }
+}
+
+/// How scores are represented in a distribution
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScoreRepr {
+    /// The score truncated to three decimal places
+    #[default]
+    Float,
+    /// The exact score as a "numerator/denominator" string
+    RationalString,
+}
+
+/// Optional settings of a distribution
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DistConfig {
+    /// Every score is raised to this power before the scores are normalised, i.e. the rewards are
+    /// proportional to score^reward_exponent. Values above 1 concentrate the rewards on the
+    /// highest scores, values below 1 flatten them. Defaults to 1 (proportional rewards).
+    pub reward_exponent: f64,
+    /// Seed of the permutations sampled by the approximation, see RankConfig::seed
+    pub seed: Option<u64>,
+    /// Validate a passed top tier before ranking, see RankConfig::strict
+    pub strict: bool,
+}
+
+impl Default for DistConfig {
+    fn default() -> Self {
+        Self {
+            reward_exponent: 1.0,
+            seed: None,
+            strict: true,
+        }
+    }
+}
+
+/// Distribute rewards using the scores of the passed ranking algorithm and return a list of
+/// NodeId, score, reward
+pub fn reward_distribution(
+    alg: RankingAlg,
+    fbas: &Fbas,
+    reward: Reward,
+    qi_check: bool,
+) -> Vec<(NodeId, Score, Reward)> {
+    reward_distribution_with_rank_config(alg, fbas, reward, qi_check, RankConfig::default())
+}
+
+fn reward_distribution_with_rank_config(
+    alg: RankingAlg,
+    fbas: &Fbas,
+    reward: Reward,
+    qi_check: bool,
+    config: RankConfig,
+) -> Vec<(NodeId, Score, Reward)> {
+    if fbas.number_of_nodes() == 0 {
+        return Vec::new();
+    }
+    match alg {
+        RankingAlg::NodeRank => {
+            let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+            graph_theory_distribution(&all_nodes, fbas, reward, true, qi_check)
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        RankingAlg::PowerIndexEnum(tt) => {
+            exact_game_theory_distribution(fbas, reward, tt, qi_check)
+        }
+        alg @ (RankingAlg::PowerIndexApprox(..) | RankingAlg::BanzhafIndexEnum(_)) => {
+            let scores = rank_nodes_with_config(fbas, alg, qi_check, config);
+            allocate_reward_to_players(scores, reward)
+        }
+    }
+}
+
+/// Same as reward_distribution but with optional settings, e.g. an exponent applied to the scores
+/// or the seed of the approximation.
+/// The returned scores are the ones of the ranking algorithm, not the exponentiated ones.
+pub fn reward_distribution_with_config(
+    alg: RankingAlg,
+    fbas: &Fbas,
+    reward: Reward,
+    qi_check: bool,
+    config: DistConfig,
+) -> Vec<(NodeId, Score, Reward)> {
+    let exponent = config.reward_exponent;
+    assert!(
+        exponent.is_finite() && exponent >= 0.0,
+        "Reward exponent must be a non-negative number"
+    );
+    let rank_config = RankConfig {
+        seed: config.seed,
+        strict: config.strict,
+        ..Default::default()
+    };
+    if exponent == 1.0 && config.seed.is_none() {
+        return reward_distribution_with_rank_config(alg, fbas, reward, qi_check, rank_config);
+    }
+    let scores = match alg {
+        RankingAlg::NodeRank => {
+            let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+            compute_node_rank_for_fbas(&all_nodes, fbas, qi_check)
+        }
+        alg => rank_nodes_with_config(fbas, alg, qi_check, rank_config),
+    };
+    let weights: Vec<Score> = scores.iter().map(|s| s.powf(exponent)).collect();
+    allocate_reward_by_factors(&scores, &normalize_scores(&weights), reward)
+}
+
+/// Ranks the nodes once and distributes every budget according to the scores. Returns a list of
+/// NodeId, score, rewards where the i-th reward belongs to the i-th budget.
+/// The nodes are only used by NodeRank; the power indices are always computed for all nodes.
+pub fn distribute_multiple_budgets(
+    alg: RankingAlg,
+    nodes: &[NodeId],
+    fbas: &Fbas,
+    budgets: &[Reward],
+    qi_check: bool,
+) -> Vec<(NodeId, Score, Vec<Reward>)> {
+    distribute_multiple_budgets_with_config(
+        alg,
+        nodes,
+        fbas,
+        budgets,
+        qi_check,
+        RankConfig::default(),
+    )
+}
+
+/// Same as distribute_multiple_budgets but with optional settings of the ranking run. As the
+/// approximation samples its permutations once, all budgets are based on the same scores; pass a
+/// seed to make these scores reproducible.
+pub fn distribute_multiple_budgets_with_config(
+    alg: RankingAlg,
+    nodes: &[NodeId],
+    fbas: &Fbas,
+    budgets: &[Reward],
+    qi_check: bool,
+    config: RankConfig,
+) -> Vec<(NodeId, Score, Vec<Reward>)> {
+    let (scores, reward_factors) = match alg {
+        RankingAlg::NodeRank => {
+            let scores = compute_node_rank_for_fbas(nodes, fbas, qi_check);
+            let reward_factors = normalize_scores(&scores);
+            (scores, reward_factors)
+        }
+        alg => {
+            let scores = rank_nodes_with_config(fbas, alg, qi_check, config);
+            (scores.clone(), scores)
+        }
+    };
+    let mut table: Vec<(NodeId, Score, Vec<Reward>)> = scores
+        .iter()
+        .enumerate()
+        .map(|(node, &score)| (node, score, Vec::with_capacity(budgets.len())))
+        .collect();
+    for &budget in budgets {
+        let rewards = allocate_reward_to_players(reward_factors.clone(), budget);
+        for (row, (_, _, reward)) in table.iter_mut().zip(rewards) {
+            row.2.push(reward);
+        }
+    }
+    table
+}
+
+/// Distribute rewards according to NodeRank scores and return a list of NodeId, score, reward.
+/// The scores are normalised to sum up to 1 unless `normalize` is false, in which case every node
+/// gets its raw score times the reward and the rewards need not sum up to the budget.
 pub fn graph_theory_distribution(
     nodes: &[NodeId],
     fbas: &Fbas,
     reward: Reward,
+    normalize: bool,
     qi_check: bool,
 ) -> Vec<(NodeId, Score, Reward)> {
     let mut rewards = Vec::default();
     let scores = compute_node_rank_for_fbas(nodes, fbas, qi_check);
-    let node_rank_sum: Score = scores.iter().map(|&v| v as Score).sum();
-    for (node, node_score) in scores.iter().enumerate() {
-        // normalise values nr/sum(nr)
-        let reward_factor = node_score / node_rank_sum;
+    let reward_factors = if normalize {
+        normalize_scores(&scores)
+    } else {
+        scores.clone()
+    };
+    for (node, reward_factor) in reward_factors.iter().enumerate() {
         let reward = round_to_three_places(reward_factor * reward);
         rewards.push((node, scores[node], reward));
     }
+    if normalize {
+        assign_residual_to_top_node(&mut rewards, reward);
+    }
     rewards
 }
 
 /// Distribute rewards proportionally to SS power index and return a map of NodeId, score, reward
+#[cfg(not(target_arch = "wasm32"))]
 pub fn exact_game_theory_distribution(
     fbas: &Fbas,
     reward: Reward,
@@ -37,6 +230,60 @@ pub fn exact_game_theory_distribution(
     allocate_reward_to_players(scores, reward)
 }
 
+/// Same as exact_game_theory_distribution but only the passed nodes are players, e.g. a candidate
+/// set of validators. All other nodes are not part of the game and get a score of 0; coalitions
+/// only win if the players among them contain a quorum.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn exact_game_theory_distribution_for_players(
+    fbas: &Fbas,
+    reward: Reward,
+    players: &[NodeId],
+    qi_check: bool,
+) -> Vec<(NodeId, Score, Reward)> {
+    let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+    assert!(
+        players.iter().all(|&p| p < all_nodes.len()),
+        "Players must be nodes of the FBAS"
+    );
+    let game = CooperativeGame::init_from_fbas_with_top_tier(&all_nodes, players, fbas);
+    let scores = game.compute_exact_ss_power_index_for_game(qi_check);
+    allocate_reward_to_players(scores, reward)
+}
+
+/// Same as exact_game_theory_distribution but returns the scores in the passed representation,
+/// e.g. as exact fractions for auditing. The rewards are the same in either case, which is why
+/// the exact fractions are enumerated in addition to the float scores.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn exact_game_theory_distribution_with_repr(
+    fbas: &Fbas,
+    reward: Reward,
+    top_tier: Option<Vec<NodeId>>,
+    qi_check: bool,
+    score_repr: ScoreRepr,
+) -> Vec<(NodeId, String, Reward)> {
+    let rewards = exact_game_theory_distribution(fbas, reward, top_tier.clone(), qi_check);
+    let scores: Vec<String> = match score_repr {
+        ScoreRepr::Float => rewards.iter().map(|r| r.1.to_string()).collect(),
+        ScoreRepr::RationalString => {
+            let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+            let game = if let Some(tt) = top_tier {
+                CooperativeGame::init_from_fbas_with_top_tier(&all_nodes, &tt, fbas)
+            } else {
+                new_game_from_fbas(fbas)
+            };
+            game.compute_exact_ss_power_index_as_rationals(qi_check)
+                .iter()
+                .map(|score| score.to_string())
+                .collect()
+        }
+    };
+    rewards
+        .into_iter()
+        .zip(scores)
+        .map(|((node, _, reward), score)| (node, score, reward))
+        .collect()
+}
+
 /// Distribute rewards proportionally to SS power index and return a map of NodeId, score, reward
 pub fn approx_game_theory_distribution(
     num_samples: usize,
@@ -49,20 +296,340 @@ pub fn approx_game_theory_distribution(
     allocate_reward_to_players(scores, reward)
 }
 
-fn new_game_from_fbas(fbas: &Fbas) -> CooperativeGame {
+fn new_game_from_fbas(fbas: &Fbas) -> CooperativeGame<'_> {
     let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
     CooperativeGame::init_from_fbas(&all_nodes, fbas)
 }
 
+/// Guarantees every organisation, given as the list of its members, floor_per_org and splits the
+/// rest of the reward proportionally to the sum of the members' scores.
+/// Returns a list of organisation index, organisation score (sum of its members' scores), reward
+pub fn distribute_org_floor(
+    fbas: &Fbas,
+    orgs: &[Vec<NodeId>],
+    reward: Reward,
+    floor_per_org: Reward,
+    alg: RankingAlg,
+    qi_check: bool,
+) -> Vec<(usize, Score, Reward)> {
+    assert!(floor_per_org >= 0.0, "The floor must not be negative");
+    let floors = floor_per_org * orgs.len() as Reward;
+    assert!(
+        floors <= reward,
+        "The floors of all organisations exceed the reward"
+    );
+    let scores = rank_nodes(fbas, alg, qi_check);
+    let org_scores: Vec<Score> = orgs
+        .iter()
+        .map(|members| members.iter().map(|&node| scores[node]).sum())
+        .collect();
+    allocate_reward_by_factors(&org_scores, &normalize_scores(&org_scores), reward - floors)
+        .into_iter()
+        .map(|(org, score, share)| (org, score, floor_per_org + share))
+        .collect()
+}
+
+/// Distributes the budget according to both score vectors and compares the rewards per node.
+/// Returns the mean and the max absolute difference between a node's rewards, i.e., what the
+/// approximation error costs the operators in units of the budget.
+pub fn reward_allocation_error(
+    approx_scores: &[Score],
+    exact_scores: &[Score],
+    budget: Reward,
+) -> (f64, f64) {
+    assert_eq!(
+        approx_scores.len(),
+        exact_scores.len(),
+        "Both score vectors must contain all nodes"
+    );
+    if exact_scores.is_empty() {
+        return (0.0, 0.0);
+    }
+    let approx_rewards = allocate_reward_to_players(approx_scores.to_vec(), budget);
+    let exact_rewards = allocate_reward_to_players(exact_scores.to_vec(), budget);
+    let errors: Vec<f64> = approx_rewards
+        .iter()
+        .zip(exact_rewards.iter())
+        .map(|(approx, exact)| (approx.2 - exact.2).abs())
+        .collect();
+    let mean = errors.iter().sum::<f64>() / errors.len() as f64;
+    let max = errors.iter().cloned().fold(0.0, f64::max);
+    (mean, max)
+}
+
+/// Distributes the reward proportionally to scores computed elsewhere, e.g. by external tooling,
+/// and returns a list of NodeId, score, reward. The scores are indexed by NodeId and need not sum
+/// up to 1.
+pub fn apportion_rewards(scores: &[Score], reward: Reward) -> Vec<(NodeId, Score, Reward)> {
+    assert!(
+        scores.iter().all(|s| *s >= 0.0),
+        "Scores must not be negative"
+    );
+    allocate_reward_by_factors(scores, &normalize_scores(scores), reward)
+}
+
+/// State of a distribution that is repeated in rounds, e.g. one round per payout period
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DistributionState {
+    /// Dust left over by rounding the shares of the previous round. It is added to the budget of
+    /// the next round instead of being assigned to a single node.
+    pub carry_over: Reward,
+}
+
+/// Distributes budget plus the carry-over of the previous round proportionally to the scores.
+/// Every share is rounded down to three decimal places; whatever is left becomes the carry-over of
+/// the next round. Returns a list of NodeId, score, reward
+pub fn distribute_with_carry(
+    state: &mut DistributionState,
+    scores: &[Score],
+    budget: Reward,
+) -> Vec<(NodeId, Score, Reward)> {
+    assert!(budget >= 0.0, "The budget must not be negative");
+    let total = budget + state.carry_over;
+    let rewards: Vec<(NodeId, Score, Reward)> = normalize_scores(scores)
+        .iter()
+        .enumerate()
+        .map(|(node, factor)| (node, scores[node], round_to_three_places(factor * total)))
+        .collect();
+    let distributed: Reward = rewards.iter().map(|r| r.2).sum();
+    state.carry_over = total - distributed;
+    rewards
+}
+
+/// Returns the reward every node gains per unit added to the budget, i.e. the derivative of its
+/// reward with respect to the budget, as a list of NodeId, marginal reward. As the rewards are
+/// proportional to the scores, this is each node's share of the sum of all scores, ignoring the
+/// rounding of the rewards.
+pub fn marginal_reward_per_node(
+    fbas: &Fbas,
+    alg: RankingAlg,
+    qi_check: bool,
+) -> Vec<(NodeId, f64)> {
+    let scores = rank_nodes(fbas, alg, qi_check);
+    normalize_scores(&scores).into_iter().enumerate().collect()
+}
+
+/// Splits the budget equally among the nodes, regardless of their scores, e.g. as a baseline for
+/// power-weighted rewards. Returns a list of NodeId, reward
+pub fn equal_split_rewards(nodes: &[NodeId], budget: Reward) -> Vec<(NodeId, Reward)> {
+    if nodes.is_empty() {
+        return Vec::new();
+    }
+    let share = round_to_three_places(budget / nodes.len() as Reward);
+    nodes.iter().map(|&node| (node, share)).collect()
+}
+
+/// Estimates every node's expected reward if each node fails independently with failure_prob.
+/// Every sample draws the failed nodes, removes them from the FBAS and distributes the budget
+/// among the remaining ones; failed nodes get nothing. The rewards are averaged over all samples
+/// and not rounded. A passed top tier is recomputed for every sample; qi_check applies to every
+/// remaining FBAS. The same seed always yields the same estimate. Returns a list of NodeId,
+/// expected reward
+pub fn expected_reward_under_failure(
+    fbas: &Fbas,
+    alg: RankingAlg,
+    budget: Reward,
+    failure_prob: f64,
+    qi_check: bool,
+    samples: usize,
+    seed: Option<u64>,
+) -> Vec<(NodeId, Reward)> {
+    assert!(
+        (0.0..=1.0).contains(&failure_prob),
+        "Failure probability must be in [0, 1]"
+    );
+    assert!(samples > 0, "Need at least one sample");
+    let pks = to_public_keys(0..fbas.number_of_nodes(), fbas);
+    let ids: HashMap<&PublicKey, NodeId> = pks.iter().enumerate().map(|(v, pk)| (pk, v)).collect();
+    let mut rng = seeded_rng(seed);
+    let mut totals = vec![Reward::default(); fbas.number_of_nodes()];
+    for _ in 0..samples {
+        let failed: Vec<PublicKey> = pks
+            .iter()
+            .filter(|_| rng.gen_bool(failure_prob))
+            .cloned()
+            .collect();
+        let remaining = fbas.without_nodes_pretty(&failed);
+        let sub_pks = to_public_keys(0..remaining.number_of_nodes(), &remaining);
+        let alg = with_recomputed_top_tier(&alg, &remaining);
+        for (node, _, reward) in reward_distribution(alg, &remaining, budget, qi_check) {
+            totals[ids[&sub_pks[node]]] += reward;
+        }
+    }
+    totals
+        .into_iter()
+        .map(|total| total / samples as Reward)
+        .enumerate()
+        .collect()
+}
+
 fn allocate_reward_to_players(scores: Vec<Score>, reward: Reward) -> Vec<(NodeId, Score, Reward)> {
+    allocate_reward_by_factors(&scores, &scores, reward)
+}
+
+/// Every node gets its reward factor times the reward; the factors are expected to sum up to 1
+/// and to be ordered like the scores
+fn allocate_reward_by_factors(
+    scores: &[Score],
+    reward_factors: &[Score],
+    reward: Reward,
+) -> Vec<(NodeId, Score, Reward)> {
     let mut rewards = Vec::default();
-    for (node, node_score) in scores.iter().enumerate() {
-        let share = round_to_three_places(node_score * reward);
+    for (node, reward_factor) in reward_factors.iter().enumerate() {
+        let share = round_to_three_places(reward_factor * reward);
         rewards.push((node, scores[node], share));
     }
+    assign_residual_to_top_node(&mut rewards, reward);
     rewards
 }
 
+/// Rounding the scores and shares to three decimal places leaves some dust. Add whatever is left
+/// of the budget to the share of the node with the highest score (the lowest NodeId in case of a
+/// tie) so that the shares sum up to the budget.
+/// Nothing is reconciled if nothing was distributed, e.g. because all scores are 0 in an FBAS
+/// without quorums, or if the residual is larger than the rounding could have caused.
+fn assign_residual_to_top_node(rewards: &mut [(NodeId, Score, Reward)], budget: Reward) {
+    let distributed: Reward = rewards.iter().map(|r| r.2).sum();
+    let residual = budget - distributed;
+    // every node's score and share are off by less than 0.001 (per unit of the budget)
+    let max_dust = rewards.len() as Reward * 0.001 * budget.abs().max(1.0);
+    if distributed == 0.0 || rewards.iter().all(|r| r.1 == 0.0) || residual.abs() > max_dust {
+        return;
+    }
+    let top_node =
+        rewards
+            .iter()
+            .enumerate()
+            .fold(None, |top: Option<(usize, Score)>, (idx, r)| match top {
+                Some((_, top_score)) if top_score >= r.1 => top,
+                _ => Some((idx, r.1)),
+            });
+    if let Some((idx, _)) = top_node {
+        // the other shares are truncated to three places, so the reconciled one is rounded to
+        // them as well instead of carrying the float error of the subtraction
+        rewards[idx].2 = ((rewards[idx].2 + residual) * 1000.0).round() / 1000.0;
+    }
+}
+
+/// Apportion total_units integer reward units proportionally to the scores using the passed
+/// method and return a list of NodeId, score, units
+pub fn allocate_integer_rewards(
+    scores: &[Score],
+    total_units: u64,
+    method: Apportionment,
+) -> Vec<(NodeId, Score, u64)> {
+    match method {
+        Apportionment::Hamilton => allocate_integer_rewards_hamilton(scores, total_units),
+        Apportionment::Webster => allocate_integer_rewards_webster(scores, total_units),
+    }
+}
+
+/// Largest remainder apportionment of total_units proportionally to the scores.
+/// Ties are broken in favour of the higher score, then the lower NodeId.
+pub fn allocate_integer_rewards_hamilton(
+    scores: &[Score],
+    total_units: u64,
+) -> Vec<(NodeId, Score, u64)> {
+    let weights = apportionment_weights(scores);
+    let weight_sum: f64 = weights.iter().sum();
+    let quotas: Vec<f64> = weights
+        .iter()
+        .map(|w| w / weight_sum * total_units as f64)
+        .collect();
+    let mut units: Vec<u64> = quotas.iter().map(|q| q.floor() as u64).collect();
+    let assigned: u64 = units.iter().sum();
+    let mut by_remainder: Vec<NodeId> = (0..scores.len()).collect();
+    by_remainder.sort_by(|&x, &y| {
+        let rem_x = quotas[x] - quotas[x].floor();
+        let rem_y = quotas[y] - quotas[y].floor();
+        rem_y
+            .partial_cmp(&rem_x)
+            .unwrap()
+            .then(weights[y].partial_cmp(&weights[x]).unwrap())
+            .then(x.cmp(&y))
+    });
+    for &node in by_remainder
+        .iter()
+        .take(total_units.saturating_sub(assigned) as usize)
+    {
+        units[node] += 1;
+    }
+    zip_units(scores, units)
+}
+
+/// Number of basis points the shares of allocate_basis_points sum up to
+pub const TOTAL_BASIS_POINTS: u32 = 10_000;
+
+/// Shares of the scores in basis points (1/10000) as returned by the largest remainder
+/// apportionment of TOTAL_BASIS_POINTS, so that they sum up to exactly TOTAL_BASIS_POINTS
+pub fn allocate_basis_points(scores: &[Score]) -> Vec<(NodeId, Score, u32)> {
+    allocate_integer_rewards_hamilton(scores, TOTAL_BASIS_POINTS as u64)
+        .into_iter()
+        .map(|(node, score, units)| (node, score, units as u32))
+        .collect()
+}
+
+/// Sainte-Laguë (Webster) apportionment of total_units proportionally to the scores.
+/// Starts from the rounded quotas and then adds or removes single units according to the divisor
+/// criterion until the budget is met, which yields the same result as assigning units one by one.
+pub fn allocate_integer_rewards_webster(
+    scores: &[Score],
+    total_units: u64,
+) -> Vec<(NodeId, Score, u64)> {
+    let weights = apportionment_weights(scores);
+    if weights.is_empty() {
+        return Vec::default();
+    }
+    let weight_sum: f64 = weights.iter().sum();
+    let mut units: Vec<u64> = weights
+        .iter()
+        .map(|w| (w / weight_sum * total_units as f64).round() as u64)
+        .collect();
+    let mut assigned: u64 = units.iter().sum();
+    while assigned < total_units {
+        let next = (0..weights.len())
+            .max_by(|&x, &y| {
+                let x_avg = weights[x] / (units[x] as f64 + 0.5);
+                let y_avg = weights[y] / (units[y] as f64 + 0.5);
+                x_avg.partial_cmp(&y_avg).unwrap().then(y.cmp(&x))
+            })
+            .unwrap();
+        units[next] += 1;
+        assigned += 1;
+    }
+    while assigned > total_units {
+        let next = (0..weights.len())
+            .filter(|&v| units[v] > 0)
+            .min_by(|&x, &y| {
+                let x_avg = weights[x] / (units[x] as f64 - 0.5);
+                let y_avg = weights[y] / (units[y] as f64 - 0.5);
+                x_avg.partial_cmp(&y_avg).unwrap().then(y.cmp(&x))
+            })
+            .unwrap();
+        units[next] -= 1;
+        assigned -= 1;
+    }
+    zip_units(scores, units)
+}
+
+/// Scores used as apportionment weights. If all scores are zero, all nodes are weighted equally.
+fn apportionment_weights(scores: &[Score]) -> Vec<f64> {
+    let score_sum: Score = scores.iter().sum();
+    if score_sum > 0.0 {
+        scores.to_vec()
+    } else {
+        vec![1.0; scores.len()]
+    }
+}
+
+fn zip_units(scores: &[Score], units: Vec<u64>) -> Vec<(NodeId, Score, u64)> {
+    units
+        .into_iter()
+        .enumerate()
+        .map(|(node, share)| (node, scores[node], share))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -70,6 +637,201 @@ mod tests {
     use fbas_analyzer::Fbas;
     use std::path::Path;
 
+    #[test]
+    fn dust_is_carried_over_to_next_round() {
+        let scores = [1.0, 1.0, 1.0];
+        let mut state = DistributionState::default();
+        let first = distribute_with_carry(&mut state, &scores, 1.0);
+        assert!(first.iter().all(|r| r.2 == 0.333));
+        assert_abs_diff_eq!(0.001, state.carry_over, epsilon = 1e-9);
+        // 0.9985 / 3 alone would be rounded down to 0.332 per node
+        let second = distribute_with_carry(&mut state, &scores, 0.9985);
+        assert!(second.iter().all(|r| r.2 == 0.333));
+        assert_abs_diff_eq!(0.0005, state.carry_over, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn marginal_rewards_of_symmetric_nodes_are_equal() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
+        let actual = marginal_reward_per_node(&fbas, RankingAlg::PowerIndexEnum(None), true);
+        assert_eq!(
+            vec![0, 1, 2],
+            actual.iter().map(|m| m.0).collect::<Vec<_>>()
+        );
+        for (_, marginal) in actual {
+            assert_abs_diff_eq!(1.0 / 3.0, marginal, epsilon = 1e-9);
+        }
+    }
+
+    #[test]
+    fn power_weighted_rewards_deviate_from_equal_split() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let nodes: Vec<NodeId> = (0..fbas.number_of_nodes()).collect();
+        let equal = equal_split_rewards(&nodes, 10.0);
+        assert!(equal.iter().all(|&(_, reward)| reward == 2.0));
+        let weighted = reward_distribution(RankingAlg::PowerIndexEnum(None), &fbas, 10.0, true);
+        assert!(weighted[0].2 > equal[0].1);
+        for node in 1..nodes.len() {
+            assert!(weighted[node].2 < equal[node].1);
+        }
+        assert!(equal_split_rewards(&[], 10.0).is_empty());
+    }
+
+    #[test]
+    fn expected_reward_without_failures_is_the_distribution() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
+        let alg = RankingAlg::PowerIndexEnum(None);
+        let expected = reward_distribution(alg.clone(), &fbas, 10.0, true);
+        let actual = expected_reward_under_failure(&fbas, alg, 10.0, 0.0, true, 5, Some(1));
+        assert_eq!(expected.len(), actual.len());
+        for ((node, _, reward), (actual_node, expected_reward)) in expected.into_iter().zip(actual)
+        {
+            assert_eq!(node, actual_node);
+            assert_abs_diff_eq!(reward, expected_reward, epsilon = 1e-9);
+        }
+        let all_failed =
+            expected_reward_under_failure(&fbas, RankingAlg::NodeRank, 10.0, 1.0, true, 5, None);
+        assert!(all_failed.iter().all(|&(_, reward)| reward == 0.0));
+        let some_failed = expected_reward_under_failure(
+            &fbas,
+            RankingAlg::PowerIndexEnum(None),
+            10.0,
+            0.5,
+            false,
+            20,
+            Some(1),
+        );
+        assert!(some_failed.iter().map(|r| r.1).sum::<Reward>() < 10.0);
+    }
+
+    #[test]
+    fn apportion_rewards_of_external_scores() {
+        let actual = apportion_rewards(&[2.0, 1.0, 1.0], 100.0);
+        let expected = vec![(0, 2.0, 50.0), (1, 1.0, 25.0), (2, 1.0, 25.0)];
+        assert_eq!(expected, actual);
+        let rewards: Reward = apportion_rewards(&[0.3, 0.3, 0.3], 10.0)
+            .iter()
+            .map(|r| r.2)
+            .sum();
+        assert_abs_diff_eq!(10.0, rewards, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn reward_allocation_error_scales_with_budget() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let exact = rank_nodes(&fbas, RankingAlg::PowerIndexEnum(None), true);
+        let mut approx = exact.clone();
+        approx[0] -= 0.05;
+        approx[1] += 0.05;
+        let (mean_small, max_small) = reward_allocation_error(&approx, &exact, 100.0);
+        let (mean_large, max_large) = reward_allocation_error(&approx, &exact, 1000.0);
+        assert_abs_diff_eq!(5.0, max_small, epsilon = 0.01);
+        assert_abs_diff_eq!(2.0, mean_small, epsilon = 0.01);
+        assert_abs_diff_eq!(10.0 * max_small, max_large, epsilon = 0.1);
+        assert_abs_diff_eq!(10.0 * mean_small, mean_large, epsilon = 0.1);
+        assert_eq!((0.0, 0.0), reward_allocation_error(&exact, &exact, 100.0));
+    }
+
+    #[test]
+    fn empty_fbas_yields_empty_distributions() {
+        let fbas = Fbas::new();
+        for alg in [
+            RankingAlg::NodeRank,
+            RankingAlg::PowerIndexEnum(None),
+            RankingAlg::PowerIndexApprox(10, None),
+            RankingAlg::BanzhafIndexEnum(None),
+        ] {
+            assert!(reward_distribution(alg.clone(), &fbas, 10.0, true).is_empty());
+            let config = DistConfig {
+                reward_exponent: 2.0,
+                ..Default::default()
+            };
+            assert!(
+                reward_distribution_with_config(alg.clone(), &fbas, 10.0, true, config).is_empty()
+            );
+            assert!(distribute_multiple_budgets(alg, &[], &fbas, &[10.0], true).is_empty());
+        }
+        assert!(exact_game_theory_distribution_with_repr(
+            &fbas,
+            10.0,
+            None,
+            true,
+            ScoreRepr::RationalString
+        )
+        .is_empty());
+        assert!(exact_game_theory_distribution_for_players(&fbas, 10.0, &[], true).is_empty());
+    }
+
+    #[test]
+    fn single_node_gets_the_whole_reward() {
+        let mut fbas = Fbas::new();
+        fbas.add_generic_node(fbas_analyzer::QuorumSet::new(vec![0], vec![], 1));
+        let reward = 10.0;
+        for alg in [
+            RankingAlg::NodeRank,
+            RankingAlg::PowerIndexEnum(None),
+            RankingAlg::PowerIndexApprox(10, None),
+            RankingAlg::BanzhafIndexEnum(None),
+        ] {
+            let expected = vec![(0, 1.0, reward)];
+            assert_eq!(
+                expected,
+                reward_distribution(alg.clone(), &fbas, reward, true),
+                "{alg:?}"
+            );
+            let budgets =
+                distribute_multiple_budgets(alg.clone(), &[0], &fbas, &[reward, 1.0], true);
+            assert_eq!(vec![(0, 1.0, vec![reward, 1.0])], budgets, "{alg:?}");
+        }
+        let exact = exact_game_theory_distribution_with_repr(
+            &fbas,
+            reward,
+            None,
+            true,
+            ScoreRepr::RationalString,
+        );
+        assert_eq!(vec![(0, String::from("1"), reward)], exact);
+    }
+
+    #[test]
+    fn only_explicit_players_are_rewarded() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let actual = exact_game_theory_distribution_for_players(&fbas, 1.0, &[0, 1, 2], true);
+        assert_eq!(0.0, actual[3].1);
+        assert_eq!(0.0, actual[4].1);
+        assert_eq!(0.0, actual[3].2 + actual[4].2);
+        assert!(actual[..3].iter().all(|(_, score, _)| *score > 0.0));
+    }
+
+    #[test]
+    fn org_floor_distribution_guarantees_floor() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let orgs = vec![vec![0, 1, 2], vec![3, 4]];
+        let reward = 100.0;
+        let floor = 20.0;
+        let actual = distribute_org_floor(
+            &fbas,
+            &orgs,
+            reward,
+            floor,
+            RankingAlg::PowerIndexEnum(None),
+            true,
+        );
+        assert_eq!(2, actual.len());
+        assert!(actual.iter().all(|(_, _, r)| *r >= floor));
+        assert!(actual[0].2 > actual[1].2);
+        let total: Reward = actual.iter().map(|r| r.2).sum();
+        assert_abs_diff_eq!(reward, total, epsilon = 1e-9);
+    }
+
+    #[test]
+    #[should_panic(expected = "exceed the reward")]
+    fn org_floors_must_not_exceed_reward() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let orgs = vec![vec![0, 1, 2], vec![3, 4]];
+        distribute_org_floor(&fbas, &orgs, 10.0, 6.0, RankingAlg::NodeRank, true);
+    }
+
     #[test]
     fn allocate_rewards_simple_fbas_noderank() {
         let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
@@ -77,23 +839,163 @@ mod tests {
         let reward = 1.0;
         let qi_check = true;
         let noderanks = compute_node_rank_for_fbas(&all_nodes, &fbas, qi_check);
-        let actual = graph_theory_distribution(&all_nodes, &fbas, reward, qi_check);
+        let actual = graph_theory_distribution(&all_nodes, &fbas, reward, true, qi_check);
+        // the dust left by truncating the shares goes to the first of the equally ranked nodes
+        let top_share = 0.334;
         let expected = vec![
-            (0, noderanks[0], round_to_three_places(reward / 3.0)),
+            (0, noderanks[0], top_share),
             (1, noderanks[1], round_to_three_places(reward / 3.0)),
             (2, noderanks[1], round_to_three_places(reward / 3.0)),
         ];
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn rewards_for_multiple_budgets_scale_linearly() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
+        let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+        let budgets = [1.0, 10.0, 250.0];
+        let qi_check = true;
+        for alg in [RankingAlg::NodeRank, RankingAlg::PowerIndexEnum(None)] {
+            let actual =
+                distribute_multiple_budgets(alg.clone(), &all_nodes, &fbas, &budgets, qi_check);
+            let single = reward_distribution(alg, &fbas, budgets[1], qi_check);
+            assert_eq!(3, actual.len());
+            for (node, score, rewards) in actual.iter() {
+                assert_eq!(single[*node].1, *score);
+                assert_eq!(single[*node].2, rewards[1]);
+                // the share per unit of budget is the same up to the rounding dust
+                let share = rewards[2] / budgets[2];
+                for (budget, reward) in budgets.iter().zip(rewards) {
+                    assert_abs_diff_eq!(share, reward / budget, epsilon = 0.002);
+                }
+            }
+            for (i, budget) in budgets.iter().enumerate() {
+                let total: Reward = actual.iter().map(|row| row.2[i]).sum();
+                assert_abs_diff_eq!(*budget, total, epsilon = 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn approx_rewards_for_multiple_budgets_share_the_samples() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+        let budgets = [1.0, 10.0, 1000.0];
+        let config = RankConfig {
+            seed: Some(7),
+            ..Default::default()
+        };
+        let alg = RankingAlg::PowerIndexApprox(500, None);
+        let actual = distribute_multiple_budgets_with_config(
+            alg.clone(),
+            &all_nodes,
+            &fbas,
+            &budgets,
+            true,
+            config,
+        );
+        let again =
+            distribute_multiple_budgets_with_config(alg, &all_nodes, &fbas, &budgets, true, config);
+        assert_eq!(actual, again);
+        for (_, score, rewards) in actual.iter() {
+            for (budget, reward) in budgets.iter().skip(1).zip(rewards.iter().skip(1)) {
+                // rewards of larger budgets are multiples of the same score, apart from the dust
+                // left by rounding to three places that goes to the top node
+                assert_abs_diff_eq!(score * budget, reward, epsilon = 0.01);
+            }
+        }
+    }
+
+    #[test]
+    fn exact_distribution_with_rational_scores() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let qi_check = true;
+        let floats = exact_game_theory_distribution(&fbas, 15.0, None, qi_check);
+        let rationals = exact_game_theory_distribution_with_repr(
+            &fbas,
+            15.0,
+            None,
+            qi_check,
+            ScoreRepr::RationalString,
+        );
+        assert_eq!("7/15", rationals[0].1);
+        assert_eq!("2/15", rationals[4].1);
+        for (float, rational) in floats.iter().zip(rationals) {
+            assert_eq!(float.2, rational.2);
+        }
+        let as_floats =
+            exact_game_theory_distribution_with_repr(&fbas, 15.0, None, qi_check, ScoreRepr::Float);
+        assert_eq!(floats[0].1.to_string(), as_floats[0].1);
+    }
+
+    #[test]
+    fn reward_exponent_concentrates_rewards() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let reward = 100.0;
+        let qi_check = true;
+        let alg = RankingAlg::PowerIndexEnum(None);
+        let linear = reward_distribution_with_config(
+            alg.clone(),
+            &fbas,
+            reward,
+            qi_check,
+            DistConfig::default(),
+        );
+        assert_eq!(
+            reward_distribution(alg.clone(), &fbas, reward, qi_check),
+            linear
+        );
+        let squared = reward_distribution_with_config(
+            alg.clone(),
+            &fbas,
+            reward,
+            qi_check,
+            DistConfig {
+                reward_exponent: 2.0,
+                ..Default::default()
+            },
+        );
+        let flattened = reward_distribution_with_config(
+            alg,
+            &fbas,
+            reward,
+            qi_check,
+            DistConfig {
+                reward_exponent: 0.5,
+                ..Default::default()
+            },
+        );
+        assert!(squared[0].2 > linear[0].2);
+        assert!(flattened[0].2 < linear[0].2);
+        for dist in [&linear, &squared, &flattened] {
+            let total: Reward = dist.iter().map(|r| r.2).sum();
+            assert_abs_diff_eq!(reward, total, epsilon = 1e-9);
+        }
+        assert_eq!(linear[0].1, squared[0].1);
+        let flattened = reward_distribution_with_config(
+            RankingAlg::PowerIndexEnum(None),
+            &fbas,
+            10000.0,
+            qi_check,
+            DistConfig {
+                reward_exponent: 0.5,
+                ..Default::default()
+            },
+        );
+        assert_eq!(3187.82, flattened[0].2);
+    }
+
     #[test]
     fn allocate_rewards_simple_fbas_exact_powerindex() {
         let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
         let reward = 1.0;
         let qi_check = true;
         let actual = exact_game_theory_distribution(&fbas, reward, None, qi_check);
+        // the dust left by truncating the shares goes to the first of the equally ranked nodes
+        let top_share = 0.334;
         let expected = vec![
-            (0, 0.333, round_to_three_places(reward / 3.0)),
+            (0, 0.333, top_share),
             (1, 0.333, round_to_three_places(reward / 3.0)),
             (2, 0.333, round_to_three_places(reward / 3.0)),
         ];
@@ -123,11 +1025,102 @@ mod tests {
         let top_tier = vec![0, 1, 2];
         let qi_check = true;
         let actual = exact_game_theory_distribution(&fbas, reward, Some(top_tier), qi_check);
+        // the dust left by truncating the shares goes to the first of the equally ranked nodes
+        let top_share = 0.334;
         let expected = vec![
-            (0, 0.333, round_to_three_places(reward / 3.0)),
+            (0, 0.333, top_share),
             (1, 0.333, round_to_three_places(reward / 3.0)),
             (2, 0.333, round_to_three_places(reward / 3.0)),
         ];
         assert_eq!(expected, actual);
     }
+    #[test]
+    fn basis_points_sum_up_to_total() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
+        let scores = rank_nodes(&fbas, RankingAlg::PowerIndexEnum(None), true);
+        let basis_points: Vec<u32> = allocate_basis_points(&scores).iter().map(|r| r.2).collect();
+        assert_eq!(vec![3334, 3333, 3333], basis_points);
+        let skewed = allocate_basis_points(&[0.467, 0.133, 0.133, 0.133, 0.133]);
+        assert_eq!(TOTAL_BASIS_POINTS, skewed.iter().map(|r| r.2).sum::<u32>());
+    }
+
+    #[test]
+    fn hamilton_and_webster_differ_on_skewed_scores() {
+        let scores = vec![0.7, 0.12, 0.1, 0.08];
+        let total_units = 3;
+        let hamilton = allocate_integer_rewards(&scores, total_units, Apportionment::Hamilton);
+        let webster = allocate_integer_rewards(&scores, total_units, Apportionment::Webster);
+        let hamilton_units: Vec<u64> = hamilton.iter().map(|r| r.2).collect();
+        let webster_units: Vec<u64> = webster.iter().map(|r| r.2).collect();
+        assert_eq!(vec![2, 1, 0, 0], hamilton_units);
+        assert_eq!(vec![3, 0, 0, 0], webster_units);
+        assert_eq!(total_units, hamilton_units.iter().sum::<u64>());
+        assert_eq!(total_units, webster_units.iter().sum::<u64>());
+    }
+
+    #[test]
+    #[should_panic(expected = "does not match")]
+    fn passed_top_tier_is_validated_unless_lenient() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
+        let alg = RankingAlg::BanzhafIndexEnum(Some(vec![0, 1]));
+        let lenient = DistConfig {
+            strict: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            3,
+            reward_distribution_with_config(alg.clone(), &fbas, 1.0, true, lenient).len()
+        );
+        reward_distribution_with_config(alg, &fbas, 1.0, true, DistConfig::default());
+    }
+
+    #[test]
+    fn no_residual_is_assigned_without_quorums() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/no_quorum.json"));
+        let allocation = exact_game_theory_distribution(&fbas, 10.0, None, true);
+        assert!(allocation.iter().all(|(_, _, reward)| *reward == 0.0));
+        let expected = expected_reward_under_failure(
+            &fbas,
+            RankingAlg::PowerIndexEnum(None),
+            10.0,
+            0.5,
+            true,
+            20,
+            Some(1),
+        );
+        assert!(expected.iter().all(|(_, reward)| *reward == 0.0));
+    }
+
+    #[test]
+    fn distributed_rewards_sum_up_to_budget() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
+        let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+        let reward = 1.0;
+        let qi_check = true;
+        let by_node_rank = graph_theory_distribution(&all_nodes, &fbas, reward, true, qi_check);
+        let by_power_index = exact_game_theory_distribution(&fbas, reward, None, qi_check);
+        for allocation in [by_node_rank, by_power_index] {
+            let total: Reward = allocation.iter().map(|r| r.2).sum();
+            assert_eq!(1.0, total);
+        }
+    }
+
+    #[test]
+    fn unnormalized_noderank_distribution_uses_raw_scores() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
+        let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+        let reward = 1.0;
+        let qi_check = true;
+        let normalized = graph_theory_distribution(&all_nodes, &fbas, reward, true, qi_check);
+        let raw = graph_theory_distribution(&all_nodes, &fbas, reward, false, qi_check);
+        let normalized_total: Reward = normalized.iter().map(|r| r.2).sum();
+        let raw_total: Reward = raw.iter().map(|r| r.2).sum();
+        assert_eq!(1.0, normalized_total);
+        // every node has a NodeRank of 0.666
+        assert_eq!(
+            vec![0.666; 3],
+            raw.iter().map(|r| r.2).collect::<Vec<Reward>>()
+        );
+        assert!(raw_total > normalized_total);
+    }
 }
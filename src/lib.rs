@@ -1,3 +1,4 @@
+pub mod bindings;
 pub mod dist;
 pub mod rank;
 pub mod report;
@@ -16,12 +17,64 @@ use fbas_analyzer::NodeId;
 
 pub type Coalition = fbas_analyzer::NodeIdSet;
 
+/// Optional settings of a ranking run
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RankConfig {
+    /// Cache the results of quorum containment checks for the duration of the run. Only takes
+    /// effect if the coalitions are drawn from at most 128 nodes (e.g. the top tier in the exact
+    /// case); off by default to avoid the memory overhead for huge games.
+    pub memoize_quorums: bool,
+    /// Compute exact power indices in a single pass over the winning coalitions instead of
+    /// collecting them in a set first. Bounds the memory to one coalition at a time, but a
+    /// player's criticality can no longer be looked up in the set of winning coalitions and has
+    /// to be checked with a further quorum containment check.
+    pub stream_coalitions: bool,
+    /// Seed of the permutations sampled by the approximation. The same seed always yields the
+    /// same scores; if None, the RNG is seeded from entropy.
+    pub seed: Option<u64>,
+    /// Only compute NodeRank scores for the nodes that are part of a minimal quorum, all other
+    /// nodes get 0. Saves building quorum set maps for leaf nodes in large FBASs. Only affects
+    /// NodeRank.
+    pub only_involved: bool,
+    /// Only compute NodeRank scores for the largest strongly connected component of the trust
+    /// graph, all other nodes get 0. PageRank then ignores links leaving the component. Only
+    /// affects NodeRank.
+    pub lscc_only: bool,
+    /// Keep the players' critical coalitions of a game once they have been enumerated so that
+    /// further exact indices of the same game, e.g. Shapley-Shubik and Banzhaf, reuse them. Off
+    /// by default as the coalitions stay in memory for the lifetime of the game.
+    pub cache_coalitions: bool,
+    /// Check that a top tier passed to a power index equals the nodes that are part of at least
+    /// one minimal quorum before ranking. On by default; turn it off to skip finding the minimal
+    /// quorums if the top tier is known to be correct.
+    pub strict: bool,
+}
+
+impl Default for RankConfig {
+    fn default() -> Self {
+        Self {
+            memoize_quorums: false,
+            stream_coalitions: false,
+            seed: None,
+            only_involved: false,
+            lscc_only: false,
+            cache_coalitions: false,
+            strict: true,
+        }
+    }
+}
+
 /// Algorithm to use when ranking nodes
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum RankingAlg {
     /// An extension of PageRank. See the function 'rank_nodes_using_node_rank' for more
     NodeRank,
+    #[cfg(not(target_arch = "wasm32"))]
     PowerIndexEnum(Option<Vec<NodeId>>),
-    /// Expects the number of samples to use
-    PowerIndexApprox(usize),
+    /// Expects the number of samples to use and optionally the top tier, in which case only
+    /// orderings of the top tier are sampled
+    PowerIndexApprox(usize, Option<Vec<NodeId>>),
+    /// Normalised Banzhaf index computed via enumeration. Optionally expects the top tier, same as
+    /// PowerIndexEnum
+    BanzhafIndexEnum(Option<Vec<NodeId>>),
 }
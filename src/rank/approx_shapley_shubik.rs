@@ -2,13 +2,15 @@ use crate::*;
 use bit_set::BitSet;
 use fbas_analyzer::{Fbas, NodeId};
 use log::{info, trace};
-use rand::seq::SliceRandom;
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 
 impl<'a> CooperativeGame<'a> {
     /// Calculates an approximation of the Shapley-Shubik Index for the players of the game using
     /// a sampling algorithm introduced by [Catro et. al](Polynomial calculation of the Shapley value based on
     /// sampling).
     /// A coalition is winning if it contains a quorum in the FBAS, otherwise losing
+    /// The permutations are drawn using the seed in the game's config, if any
     /// See C. Ndolo Master's thesis for details
     pub(crate) fn compute_approx_ss_power_index_for_game(
         &self,
@@ -23,35 +25,192 @@ impl<'a> CooperativeGame<'a> {
             );
         }
         info!("Starting calculation of power indices via approximation.");
-        let sample_permutations = generate_sample_permutations(num_samples, &self.players);
+        let sampled_players = self.sampled_players();
+        let sample_permutations =
+            generate_sample_permutations(num_samples, &sampled_players, self.config.seed);
+        let cache = self.new_quorum_cache(&sampled_players);
         let power_indices: Vec<Score> = self
             .players
             .iter()
             .map(|&p| {
+                if !sampled_players.contains(&p) {
+                    return Score::default();
+                }
                 Self::compute_approx_ss_power_index_for_player(
                     p,
-                    sample_permutations.clone().into_iter(),
+                    sample_permutations.clone(),
                     num_samples,
                     self.fbas,
+                    cache.as_ref(),
                 )
             })
             .collect();
         power_indices
     }
 
+    /// Returns, per player, the marginal contribution (0 or 1) of the player in each of the
+    /// sampled permutations, i.e. the matrix the approximated power index is the row mean of.
+    /// The same permutations are used for all players. Pass a seed to make the samples
+    /// reproducible.
+    pub(crate) fn compute_approx_ss_contributions(
+        &self,
+        num_samples: usize,
+        seed: Option<u64>,
+        qi_check: bool,
+    ) -> Vec<Vec<u8>> {
+        if qi_check {
+            trace!("Ensuring the FBAS has quorum intersection.");
+            assert!(
+                fbas_analyzer::all_intersect(&fbas_analyzer::find_minimal_quorums(self.fbas)),
+                "FBAS lacks quorum intersection!"
+            );
+        }
+        info!("Collecting marginal contributions of {num_samples} sampled permutations.");
+        let sampled_players = self.sampled_players();
+        let sample_permutations = generate_sample_permutations(num_samples, &sampled_players, seed);
+        let cache = self.new_quorum_cache(&sampled_players);
+        self.players
+            .iter()
+            .map(|&p| {
+                if !sampled_players.contains(&p) {
+                    return vec![0; num_samples];
+                }
+                let mut samples = sample_permutations.clone();
+                let mut contributions = Vec::with_capacity(num_samples);
+                while let Some(sample) = samples.next_permutation() {
+                    let pred = pred_of_player_i(p, sample);
+                    contributions.push(compute_player_i_marginal_contribution(
+                        p,
+                        &pred,
+                        self.fbas,
+                        cache.as_ref(),
+                    ) as u8);
+                }
+                contributions
+            })
+            .collect()
+    }
+
+    /// Approximates the power indices with as many samples as needed for the widest confidence
+    /// interval (normal approximation, see approx_power_indices_with_normal_ci) of any player to
+    /// be at most target_ci_width. Starts with ADAPTIVE_INITIAL_SAMPLES permutations and doubles
+    /// their number until the target or max_samples is reached; the contributions of the earlier
+    /// permutations are kept, so every round only samples the new ones.
+    /// Returns the scores along with the number of samples used
+    pub(crate) fn compute_approx_ss_adaptive(
+        &self,
+        target_ci_width: f64,
+        confidence: f64,
+        max_samples: usize,
+        qi_check: bool,
+    ) -> (Vec<Score>, usize) {
+        assert!(target_ci_width > 0.0, "Target CI width must be positive");
+        assert!(
+            confidence > 0.0 && confidence < 1.0,
+            "Confidence level must be in (0, 1)"
+        );
+        assert!(max_samples > 0, "At least one ordering must be sampled");
+        if qi_check {
+            trace!("Ensuring the FBAS has quorum intersection.");
+            assert!(
+                fbas_analyzer::all_intersect(&fbas_analyzer::find_minimal_quorums(self.fbas)),
+                "FBAS lacks quorum intersection!"
+            );
+        }
+        let z = standard_normal_quantile(1.0 - (1.0 - confidence) / 2.0);
+        let sampled_players = self.sampled_players();
+        let mut sampler = PermutationSampler::new(max_samples, &sampled_players, self.config.seed);
+        let cache = self.new_quorum_cache(&sampled_players);
+        // hits per player, indexed like self.players
+        let mut hits = vec![0usize; self.players.len()];
+        let mut drawn = 0;
+        let mut target = ADAPTIVE_INITIAL_SAMPLES.min(max_samples);
+        loop {
+            while drawn < target {
+                let sample = sampler
+                    .next_permutation()
+                    .expect("The sampler holds max_samples permutations");
+                for (idx, &p) in self.players.iter().enumerate() {
+                    if sampled_players.contains(&p) {
+                        let pred = pred_of_player_i(p, sample);
+                        hits[idx] += compute_player_i_marginal_contribution(
+                            p,
+                            &pred,
+                            self.fbas,
+                            cache.as_ref(),
+                        );
+                    }
+                }
+                drawn += 1;
+            }
+            let widest_ci = hits
+                .iter()
+                .map(|&h| 2.0 * z * (bernoulli_variance(h, drawn) / drawn as f64).sqrt())
+                .fold(0.0, f64::max);
+            info!("Widest CI after {drawn} samples: {widest_ci}");
+            if widest_ci <= target_ci_width || drawn >= max_samples {
+                break;
+            }
+            target = (2 * drawn).min(max_samples);
+        }
+        let scores = hits
+            .iter()
+            .map(|&h| round_to_three_places(h as f64 / drawn as f64))
+            .collect();
+        (scores, drawn)
+    }
+
+    /// Approximates the power indices along with the sample variance of every player's marginal
+    /// contributions. A high variance means that the player's estimate converges slowly and
+    /// needs more samples.
+    /// Returns a list of (score, variance) with index 0 = node 0's values
+    pub(crate) fn compute_approx_ss_with_variance(
+        &self,
+        num_samples: usize,
+        qi_check: bool,
+        seed: Option<u64>,
+    ) -> Vec<(Score, f64)> {
+        self.compute_approx_ss_contributions(num_samples, seed, qi_check)
+            .iter()
+            .map(|samples| {
+                let hits: usize = samples.iter().map(|&c| c as usize).sum();
+                let mean = hits as f64 / num_samples as f64;
+                let variance = if num_samples < 2 {
+                    0.0
+                } else {
+                    samples
+                        .iter()
+                        .map(|&c| (c as f64 - mean).powi(2))
+                        .sum::<f64>()
+                        / (num_samples - 1) as f64
+                };
+                (round_to_three_places(mean), variance)
+            })
+            .collect()
+    }
+
+    /// The players whose orderings are sampled: the top tier if one was passed, otherwise all
+    /// players. Players outside the top tier are never pivotal and thus get a score of 0.
+    fn sampled_players(&self) -> Vec<NodeId> {
+        self.top_tier
+            .clone()
+            .unwrap_or_else(|| self.players.clone())
+    }
+
     /// player: ID of player whose score we are computing
     /// permutation_samples: randomly chosen orders of the grand coalition
     /// The estimate is equal to the sum of player's contribution each colution/samples
     fn compute_approx_ss_power_index_for_player(
         player: usize,
-        permutation_samples: impl Iterator<Item = Vec<usize>>,
+        mut permutation_samples: PermutationSampler,
         total_samples: usize,
         fbas: &Fbas,
+        cache: Option<&QuorumCache>,
     ) -> Score {
         let mut estimate = Score::default();
-        for sample in permutation_samples {
-            let pred = pred_of_player_i(player, &sample);
-            let contribution = compute_player_i_marginal_contribution(player, &pred, fbas);
+        while let Some(sample) = permutation_samples.next_permutation() {
+            let pred = pred_of_player_i(player, sample);
+            let contribution = compute_player_i_marginal_contribution(player, &pred, fbas, cache);
             estimate += contribution as f64;
         }
         estimate /= total_samples as f64;
@@ -59,6 +218,20 @@ impl<'a> CooperativeGame<'a> {
     }
 }
 
+/// Number of permutations the adaptive approximation starts with
+const ADAPTIVE_INITIAL_SAMPLES: usize = 100;
+
+/// Sample variance of samples 0/1 contributions of which hits are 1
+fn bernoulli_variance(hits: usize, samples: usize) -> f64 {
+    if samples < 2 {
+        return 0.0;
+    }
+    let mean = hits as f64 / samples as f64;
+    // sum of (c - mean)^2 over hits ones and (samples - hits) zeros
+    (hits as f64 * (1.0 - mean).powi(2) + (samples - hits) as f64 * mean.powi(2))
+        / (samples - 1) as f64
+}
+
 /// Given a permutation O, Pre^i(O) is the set of predecessors of the
 /// player i in the order O, i.e. Pre^i(O) = {O(1), . . . , O(k − 1)}, if i = O(k))
 fn pred_of_player_i(i: usize, permutation: &[usize]) -> Vec<NodeId> {
@@ -71,32 +244,145 @@ fn pred_of_player_i(i: usize, permutation: &[usize]) -> Vec<NodeId> {
 /// Expects the predecessors of player as a permutation
 /// Return v(pre union player) - v(pred)
 /// 1 when pred is losing but union contains a quorums, 0 otherwise
-fn compute_player_i_marginal_contribution(player: usize, pred: &[usize], fbas: &Fbas) -> usize {
+fn compute_player_i_marginal_contribution(
+    player: usize,
+    pred: &[usize],
+    fbas: &Fbas,
+    cache: Option<&QuorumCache>,
+) -> usize {
     let predecessor: BitSet = pred.iter().copied().collect();
     let mut pred_union_player = predecessor.clone();
     pred_union_player.insert(player);
     usize::from(
-        fbas_analyzer::contains_quorum(&pred_union_player, fbas)
-            && !fbas_analyzer::contains_quorum(&predecessor, fbas),
+        contains_quorum(&pred_union_player, fbas, cache)
+            && !contains_quorum(&predecessor, fbas, cache),
     )
 }
 
+/// Draws random permutations of the players by shuffling a single buffer in place
+/// (Fisher-Yates), so that no permutation has to be allocated unless it is iterated as an owned
+/// `Vec`. Every shuffle starts from the previous permutation. A clone replays the same
+/// permutations as the original from the point it was cloned at, which is how every player gets
+/// to see the same samples.
+#[derive(Debug, Clone)]
+pub struct PermutationSampler {
+    rng: ChaCha8Rng,
+    permutation: Vec<NodeId>,
+    remaining: usize,
+}
+
+impl PermutationSampler {
+    /// Sampler of no_samples permutations of players. The permutations are reproducible if a seed
+    /// is passed, otherwise the RNG is seeded from entropy.
+    pub fn new(no_samples: usize, players: &[NodeId], seed: Option<u64>) -> Self {
+        Self {
+            rng: seeded_rng(seed),
+            permutation: players.into(),
+            remaining: no_samples,
+        }
+    }
+    /// Shuffles the buffer and returns it, or None once all samples have been drawn
+    pub fn next_permutation(&mut self) -> Option<&[NodeId]> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        // Complexity 0(n) per shuffle
+        self.permutation.shuffle(&mut self.rng);
+        Some(&self.permutation)
+    }
+}
+
+impl Iterator for PermutationSampler {
+    type Item = Vec<NodeId>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_permutation()
+            .map(|permutation| permutation.to_vec())
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
 /// We create the grand coalition, and randomly select no_samples permutations of it
 /// Done by shuffling the grand coalition no_sample many times
 /// Bitset wont work here because of order
+/// The permutations are reproducible if a seed is passed, otherwise the RNG is seeded from entropy
 fn generate_sample_permutations(
     no_samples: usize,
     players: &[NodeId],
-) -> (impl IntoIterator<Item = Vec<NodeId>> + Clone) {
-    let mut grand_coalition: Vec<usize> = players.into();
-    // Complexity 0(n) per shuffle
-    (0..no_samples)
-        .collect::<Vec<_>>()
-        .into_iter()
-        .map(move |_| {
-            grand_coalition.shuffle(&mut rand::thread_rng());
-            grand_coalition.clone()
+    seed: Option<u64>,
+) -> PermutationSampler {
+    PermutationSampler::new(no_samples, players, seed)
+}
+
+/// Seeds the RNG from the seed if passed, from entropy otherwise.
+/// Unlike StdRng, whose algorithm may change between rand releases, ChaCha8Rng's output for a
+/// seed is fixed by the rand_chacha version, so seeded runs can be replayed.
+pub(crate) fn seeded_rng(seed: Option<u64>) -> ChaCha8Rng {
+    if let Some(seed) = seed {
+        ChaCha8Rng::seed_from_u64(seed)
+    } else {
+        ChaCha8Rng::from_entropy()
+    }
+}
+
+/// Number of samples needed so that, with probability `confidence`, every player's estimate
+/// is within `target_error` of its true Shapley-Shubik index.
+/// Follows the sample size analysis of Castro et al.: by the central limit theorem the estimate
+/// of each player is approximately normally distributed, so m >= z^2 * sigma^2 / e^2 samples
+/// suffice. The variance sigma^2 of a player's marginal contributions is unknown a priori, but
+/// since contributions are either 0 or 1 it is at most 1/4. A union bound over all players
+/// divides the error probability by num_players.
+pub fn castro_sample_bound(num_players: usize, target_error: f64, confidence: f64) -> usize {
+    assert!(target_error > 0.0, "Target error must be positive");
+    assert!(
+        confidence > 0.0 && confidence < 1.0,
+        "Confidence level must be in (0, 1)"
+    );
+    let alpha = (1.0 - confidence) / num_players.max(1) as f64;
+    let z = standard_normal_quantile(1.0 - alpha / 2.0);
+    let max_variance = 0.25;
+    (z * z * max_variance / (target_error * target_error)).ceil() as usize
+}
+
+/// Percentile bootstrap confidence intervals for the approximated power indices
+/// contributions: per player marginal contributions as returned by compute_approx_ss_contributions
+/// confidence: confidence level of the intervals, e.g. 0.95
+/// resamples: number of bootstrap resamples to draw
+/// Returns one (lower, upper) bound pair per player
+pub fn bootstrap_ci(
+    contributions: &[Vec<u8>],
+    confidence: f64,
+    resamples: usize,
+    seed: Option<u64>,
+) -> Vec<(f64, f64)> {
+    assert!(
+        confidence > 0.0 && confidence < 1.0,
+        "Confidence level must be in (0, 1)"
+    );
+    let mut rng = seeded_rng(seed);
+    let alpha = 1.0 - confidence;
+    contributions
+        .iter()
+        .map(|samples| {
+            if samples.is_empty() || resamples == 0 {
+                return (Score::default(), Score::default());
+            }
+            let mut means: Vec<f64> = (0..resamples)
+                .map(|_| {
+                    let hits: usize = (0..samples.len())
+                        .map(|_| samples[rng.gen_range(0..samples.len())] as usize)
+                        .sum();
+                    hits as f64 / samples.len() as f64
+                })
+                .collect();
+            means.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            let lower = ((alpha / 2.0) * (resamples - 1) as f64).round() as usize;
+            let upper = ((1.0 - alpha / 2.0) * (resamples - 1) as f64).round() as usize;
+            (means[lower], means[upper])
         })
+        .collect()
 }
 
 #[cfg(test)]
@@ -109,15 +395,45 @@ mod tests {
     #[test]
     fn generate_correct_num_of_samples() {
         let players = vec![]; // empty vec because we are just checking for the len
-        let actual = generate_sample_permutations(6, &players);
-        assert_eq!(actual.into_iter().size_hint(), (6, Some(6)));
+        let actual = generate_sample_permutations(6, &players, None);
+        assert_eq!(actual.size_hint(), (6, Some(6)));
+    }
+
+    #[test]
+    fn seeded_samples_are_reproducible() {
+        let players = vec![0, 1, 2, 3, 4];
+        let first: Vec<Vec<NodeId>> = generate_sample_permutations(10, &players, Some(7)).collect();
+        let second: Vec<Vec<NodeId>> =
+            generate_sample_permutations(10, &players, Some(7)).collect();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn sampler_draws_same_permutations_as_shuffling_copies() {
+        let players = vec![0, 1, 2, 3, 4];
+        let mut grand_coalition = players.clone();
+        let mut rng = seeded_rng(Some(7));
+        let expected: Vec<Vec<NodeId>> = (0..20)
+            .map(|_| {
+                grand_coalition.shuffle(&mut rng);
+                grand_coalition.clone()
+            })
+            .collect();
+        let mut sampler = PermutationSampler::new(20, &players, Some(7));
+        let mut actual = Vec::new();
+        while let Some(permutation) = sampler.next_permutation() {
+            actual.push(permutation.to_vec());
+        }
+        assert_eq!(expected, actual);
+        let owned: Vec<Vec<NodeId>> = PermutationSampler::new(20, &players, Some(7)).collect();
+        assert_eq!(expected, owned);
     }
 
     #[test]
     fn permutations_predecessors() {
         let player = 0;
-        let permutations = vec![vec![0, 1, 2, 3], vec![3, 2, 1, 0]];
-        let expected = vec![vec![], vec![3, 2, 1]];
+        let permutations = [vec![0, 1, 2, 3], vec![3, 2, 1, 0]];
+        let expected = [vec![], vec![3, 2, 1]];
         for i in 0..permutations.len() {
             let actual = pred_of_player_i(player, &permutations[i]);
             assert_eq!(expected[i], actual);
@@ -128,12 +444,12 @@ mod tests {
     fn permutations_worth() {
         // U = {0, 1}, {0, 2}, {1, 2} {0, 1, 2}
         let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
-        let predecessors = vec![vec![0, 1], vec![2, 1], vec![1, 2, 0], vec![1]];
+        let predecessors = [vec![0, 1], vec![2, 1], vec![1, 2, 0], vec![1]];
         let players = [2, 0, 0, 2];
         let expected = [0, 0, 0, 1];
         for i in 0..predecessors.len() {
             let actual =
-                compute_player_i_marginal_contribution(players[i], &predecessors[i], &fbas);
+                compute_player_i_marginal_contribution(players[i], &predecessors[i], &fbas, None);
             assert_eq!(expected[i], actual);
         }
     }
@@ -143,13 +459,9 @@ mod tests {
         let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
         let qi_check = true;
         let tt = CooperativeGame::get_involved_nodes(&fbas, qi_check);
-        let samples = generate_sample_permutations(100, &tt);
-        let actual = CooperativeGame::compute_approx_ss_power_index_for_player(
-            0,
-            samples.into_iter(),
-            100,
-            &fbas,
-        );
+        let samples = generate_sample_permutations(100, &tt, None);
+        let actual =
+            CooperativeGame::compute_approx_ss_power_index_for_player(0, samples, 100, &fbas, None);
         let expected = 1.0 / 3.0;
         // a and b equal if |a - b| <= epsilon
         assert_abs_diff_eq!(expected, actual, epsilon = 0.2f64);
@@ -160,13 +472,9 @@ mod tests {
         let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
         let qi_check = true;
         let tt = CooperativeGame::get_involved_nodes(&fbas, qi_check);
-        let samples = generate_sample_permutations(100, &tt);
-        let actual = CooperativeGame::compute_approx_ss_power_index_for_player(
-            0,
-            samples.into_iter(),
-            100,
-            &fbas,
-        );
+        let samples = generate_sample_permutations(100, &tt, None);
+        let actual =
+            CooperativeGame::compute_approx_ss_power_index_for_player(0, samples, 100, &fbas, None);
         let expected = 1.0 / 3.0;
         assert_abs_diff_eq!(expected, actual, epsilon = 0.2f64);
     }
@@ -178,7 +486,7 @@ mod tests {
         let game = CooperativeGame::init_from_fbas(&all_nodes, &fbas);
         let samples = 100;
         let qi_check = true;
-        let expected = vec![1.0 / 3.0, 1.0 / 3.0, 1.0 / 3.0];
+        let expected = [1.0 / 3.0, 1.0 / 3.0, 1.0 / 3.0];
         let actual = game.compute_approx_ss_power_index_for_game(samples, qi_check);
         for e in 0..expected.len() {
             assert_abs_diff_eq!(expected[e], actual[e], epsilon = 0.2f64);
@@ -246,15 +554,52 @@ mod tests {
                     ]
                 }
             }]"#;
-        let fbas = Fbas::from_json_str(&input);
+        let fbas = Fbas::from_json_str(input);
         let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
         let game = CooperativeGame::init_from_fbas(&all_nodes, &fbas);
         let samples = 100;
         let qi_check = true;
-        let expected = vec![7.0 / 15.0, 4.0 / 30.0, 4.0 / 30.0, 4.0 / 30.0, 4.0 / 30.0];
+        let expected = [7.0 / 15.0, 4.0 / 30.0, 4.0 / 30.0, 4.0 / 30.0, 4.0 / 30.0];
         let actual = game.compute_approx_ss_power_index_for_game(samples, qi_check);
         for (i, _) in expected.iter().enumerate() {
             assert_abs_diff_eq!(expected[i], actual[i], epsilon = 0.2f64);
         }
     }
+
+    #[test]
+    fn bootstrap_ci_brackets_index_of_symmetric_game() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
+        let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+        let game = CooperativeGame::init_from_fbas(&all_nodes, &fbas);
+        let contributions = game.compute_approx_ss_contributions(1000, Some(1), true);
+        let actual = bootstrap_ci(&contributions, 0.99, 500, Some(1));
+        assert_eq!(actual.len(), 3);
+        for (low, high) in actual {
+            assert!(low <= 1.0 / 3.0 && 1.0 / 3.0 <= high);
+        }
+    }
+
+    #[test]
+    fn dominant_node_has_higher_variance() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+        let game = CooperativeGame::init_from_fbas(&all_nodes, &fbas);
+        let actual = game.compute_approx_ss_with_variance(1000, true, Some(2));
+        assert_abs_diff_eq!(actual[0].0, 7.0 / 15.0, epsilon = 0.05);
+        // Bernoulli variance p(1 - p): ~0.249 for node 0 (7/15) and ~0.116 for the others (2/15)
+        assert_abs_diff_eq!(actual[0].1, 0.249, epsilon = 0.02);
+        for (_, variance) in actual.iter().skip(1) {
+            assert_abs_diff_eq!(*variance, 0.116, epsilon = 0.03);
+            assert!(actual[0].1 > *variance);
+        }
+    }
+
+    #[test]
+    fn castro_bound_grows_with_precision_and_confidence() {
+        let loose = castro_sample_bound(5, 0.1, 0.9);
+        let precise = castro_sample_bound(5, 0.01, 0.9);
+        let confident = castro_sample_bound(5, 0.1, 0.99);
+        assert!(precise > loose);
+        assert!(confident > loose);
+    }
 }
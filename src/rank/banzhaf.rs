@@ -0,0 +1,149 @@
+use crate::*;
+use fbas_analyzer::{Fbas, NodeId};
+use log::info;
+
+impl<'a> CooperativeGame<'a> {
+    /// Counts the swings of every player, i.e. the number of winning coalitions of the top tier
+    /// that become losing when the player leaves them.
+    /// Returns the swings with index 0 = node 0's swings and the size of the top tier
+    fn compute_banzhaf_swings(&self, qi_check: bool) -> (Vec<usize>, usize) {
+        let top_tier = if let Some(tt) = self.top_tier.clone() {
+            info!("Game already initialised with involved nodes..");
+            tt
+        } else {
+            Self::get_involved_nodes(self.fbas, qi_check)
+        };
+        info!("Starting calculation of Banzhaf indices via enumeration.");
+        let critical_coalitions = self.critical_coalitions(&top_tier);
+        let swings = self
+            .players
+            .iter()
+            .map(|&p| critical_coalitions[&p].len())
+            .collect();
+        (swings, top_tier.len())
+    }
+
+    /// Calculates the absolute Banzhaf index of every player, i.e. its swings / 2^(n-1), where n
+    /// is the size of the top tier. Nodes outside the top tier never swing, so considering them
+    /// would not change the index.
+    /// Returns a list of scores with index 0 = node 0's score
+    pub(crate) fn compute_banzhaf_absolute_for_game(&self, qi_check: bool) -> Vec<Score> {
+        let (swings, num_players) = self.compute_banzhaf_swings(qi_check);
+        if num_players == 0 {
+            return vec![Score::default(); swings.len()];
+        }
+        let num_coalitions = 2f64.powi(num_players as i32 - 1);
+        swings
+            .into_iter()
+            .map(|s| round_to_three_places(s as f64 / num_coalitions))
+            .collect()
+    }
+
+    /// Calculates the normalised Banzhaf index of every player, i.e. its share of all swings. A
+    /// swing is a winning coalition the player is critical for, see player_is_critical.
+    /// Returns a list of scores with index 0 = node 0's score
+    pub(crate) fn compute_banzhaf_index_for_game(&self, qi_check: bool) -> Vec<Score> {
+        let (swings, _) = self.compute_banzhaf_swings(qi_check);
+        let total_swings: usize = swings.iter().sum();
+        if total_swings == 0 {
+            return vec![Score::default(); swings.len()];
+        }
+        swings
+            .into_iter()
+            .map(|s| round_to_three_places(s as f64 / total_swings as f64))
+            .collect()
+    }
+}
+
+/// Number of swings of every node, i.e. the number of winning coalitions of the top tier in which
+/// the node is critical. Both Banzhaf indices are derived from these counts.
+/// Returns a list of counts with index 0 = node 0's count
+pub fn swing_counts(fbas: &Fbas, qi_check: bool) -> Vec<usize> {
+    new_game(fbas, None).compute_banzhaf_swings(qi_check).0
+}
+
+/// Absolute Banzhaf index of every node: the number of its swings divided by 2^(n-1).
+/// Unlike the normalised index, it does not depend on the other nodes' power and can thus be
+/// compared across FBASs of different sizes.
+/// Returns a list of scores with index 0 = node 0's score
+pub fn compute_banzhaf_absolute(
+    fbas: &Fbas,
+    top_tier: Option<Vec<NodeId>>,
+    qi_check: bool,
+) -> Vec<Score> {
+    new_game(fbas, top_tier).compute_banzhaf_absolute_for_game(qi_check)
+}
+
+/// Normalised Banzhaf index of every node: the number of its swings divided by the swings of all
+/// nodes, so that the scores sum up to 1.
+/// Returns a list of scores with index 0 = node 0's score
+pub fn compute_banzhaf_normalized(
+    fbas: &Fbas,
+    top_tier: Option<Vec<NodeId>>,
+    qi_check: bool,
+) -> Vec<Score> {
+    new_game(fbas, top_tier).compute_banzhaf_index_for_game(qi_check)
+}
+
+fn new_game(fbas: &Fbas, top_tier: Option<Vec<NodeId>>) -> CooperativeGame<'_> {
+    let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+    if let Some(tt) = top_tier {
+        CooperativeGame::init_from_fbas_with_top_tier(&all_nodes, &tt, fbas)
+    } else {
+        CooperativeGame::init_from_fbas(&all_nodes, fbas)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn absolute_and_normalized_banzhaf_for_symmetric_game() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
+        let absolute = compute_banzhaf_absolute(&fbas, None, true);
+        let normalized = compute_banzhaf_normalized(&fbas, None, true);
+        // every node swings in 2 of the 4 coalitions of the others
+        assert_eq!(vec![0.5, 0.5, 0.5], absolute);
+        assert_eq!(vec![0.333, 0.333, 0.333], normalized);
+        assert_ne!(absolute, normalized);
+    }
+
+    #[test]
+    fn rank_nodes_dispatches_to_banzhaf_index() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let game = new_game(&fbas, None);
+        assert_eq!(
+            game.compute_banzhaf_index_for_game(true),
+            rank_nodes(&fbas, RankingAlg::BanzhafIndexEnum(None), true)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "FBAS lacks quorum intersection!")]
+    fn banzhaf_index_checks_quorum_intersection_like_shapley_shubik() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/two_clusters.json"));
+        rank_nodes(&fbas, RankingAlg::BanzhafIndexEnum(None), true);
+    }
+
+    #[test]
+    fn central_node_has_most_swings() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        assert_eq!(vec![7, 3, 3, 3, 3], swing_counts(&fbas, true));
+    }
+
+    #[test]
+    fn banzhaf_for_game_in_paper() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        // node 0 swings in all 7 winning coalitions, every other node in 3 of them
+        assert_eq!(
+            vec![0.437, 0.187, 0.187, 0.187, 0.187],
+            compute_banzhaf_absolute(&fbas, None, true)
+        );
+        assert_eq!(
+            vec![0.368, 0.157, 0.157, 0.157, 0.157],
+            compute_banzhaf_normalized(&fbas, Some(vec![0, 1, 2, 3, 4]), true)
+        );
+    }
+}
@@ -1,15 +1,18 @@
 use crate::*;
-use fbas_analyzer::NodeId;
+use fbas_analyzer::{Fbas, NodeId};
 use itertools::Itertools;
 use log::info;
-use rug::Integer;
+#[cfg(not(target_arch = "wasm32"))]
+use rug::{Integer, Rational};
 use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
 
 impl<'a> CooperativeGame<'a> {
     /// Calculates the Shapley-Shubik Index for the players of the game
     /// Returns a list of scores with index 0 = node 0's score
     /// A coalition is winning if it contains a quorum in the FBAS, otherwise losing
     /// See C. Ndolo Master's thesis for details
+    #[cfg(not(target_arch = "wasm32"))]
     pub(crate) fn compute_exact_ss_power_index_for_game(&self, qi_check: bool) -> Vec<Score> {
         // Because the TT is computed out of this function, we assume the check for     QI has
         // already been done if we got this far
@@ -19,15 +22,41 @@ impl<'a> CooperativeGame<'a> {
         } else {
             Self::get_involved_nodes(self.fbas, qi_check)
         };
+        if !self.forced.is_empty() {
+            return self.compute_exact_ss_power_index_with_forced(&top_tier);
+        }
+        if let Some(threshold) = self.flat_threshold_of_top_tier(&top_tier) {
+            info!("Top tier is a flat threshold game, computing power indices via DP.");
+            let weights = vec![1; top_tier.len()];
+            let tt_indices = weighted_majority_ss_power_index(&weights, threshold);
+            return self
+                .players
+                .iter()
+                .map(|p| {
+                    top_tier
+                        .iter()
+                        .position(|v| v == p)
+                        .map_or(Score::default(), |i| tt_indices[i])
+                })
+                .collect();
+        }
+        self.compute_exact_ss_power_index_via_enumeration(&top_tier)
+    }
+
+    /// Enumerates all coalitions of the top tier to find the players' critical coalitions
+    /// Returns a list of scores with index 0 = node 0's score
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn compute_exact_ss_power_index_via_enumeration(
+        &self,
+        top_tier: &[NodeId],
+    ) -> Vec<Score> {
+        if self.config.stream_coalitions {
+            return self.compute_exact_ss_power_index_via_streaming(top_tier);
+        }
         info!("Starting calculation of power indices via enumeration.");
         let num_players = top_tier.len();
         let total_factorial = n_factorial(top_tier.len());
-        let winning_coalitions = self.find_winning_coalitions(&top_tier);
-        let players_critical_coalitions: HashMap<NodeId, Vec<Coalition>> = self
-            .players
-            .iter()
-            .map(|v| (*v, Self::player_is_critical(*v, &winning_coalitions)))
-            .collect();
+        let players_critical_coalitions = self.critical_coalitions(top_tier);
         let power_indices: Vec<Score> = self
             .players
             .iter()
@@ -42,38 +71,180 @@ impl<'a> CooperativeGame<'a> {
         power_indices
     }
 
+    /// Same as compute_exact_ss_power_index_via_enumeration but visits every winning coalition once
+    /// without keeping it in memory. A player is critical for a winning coalition if the coalition
+    /// without the player contains no quorum, which is checked directly instead of being looked
+    /// up in the set of winning coalitions.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn compute_exact_ss_power_index_via_streaming(&self, top_tier: &[NodeId]) -> Vec<Score> {
+        info!("Starting calculation of power indices via streamed enumeration.");
+        let num_players = top_tier.len();
+        let total_factorial = n_factorial(num_players);
+        let cache = self.new_quorum_cache(top_tier);
+        let mut sums: HashMap<NodeId, Rational> = HashMap::new();
+        for w in winning_subsets(top_tier.to_vec(), self.fbas, cache.as_ref()) {
+            let share = share_of_orderings(&w, num_players, &total_factorial);
+            for player in w.iter() {
+                let mut w_without_player = w.clone();
+                w_without_player.remove(player);
+                if !contains_quorum(&w_without_player, self.fbas, cache.as_ref()) {
+                    *sums.entry(player).or_default() += &share;
+                }
+            }
+        }
+        self.players
+            .iter()
+            .map(|p| sums.get(p).map_or(Score::default(), rational_to_score))
+            .collect()
+    }
+
+    /// Enumerates the coalitions of the top tier nodes that are not forced. A coalition is winning
+    /// if it contains a quorum together with the forced nodes, and only the non-forced top tier
+    /// nodes count as players when weighting the critical coalitions.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn compute_exact_ss_power_index_with_forced(&self, top_tier: &[NodeId]) -> Vec<Score> {
+        info!("Starting calculation of power indices with forced nodes.");
+        let forced: Coalition = self.forced.iter().copied().collect();
+        let free: Vec<NodeId> = top_tier
+            .iter()
+            .filter(|&&v| !forced.contains(v))
+            .copied()
+            .collect();
+        let num_players = free.len();
+        let total_factorial = n_factorial(num_players);
+        let cache = self.new_quorum_cache(top_tier);
+        let is_winning = |coalition: &Coalition| {
+            let mut with_forced = coalition.clone();
+            with_forced.union_with(&forced);
+            contains_quorum(&with_forced, self.fbas, cache.as_ref())
+        };
+        let mut sums: HashMap<NodeId, Rational> = HashMap::new();
+        for coalition in free.into_iter().powerset() {
+            let w: Coalition = coalition.into_iter().collect();
+            if w.is_empty() || !is_winning(&w) {
+                continue;
+            }
+            let share = share_of_orderings(&w, num_players, &total_factorial);
+            for player in w.iter() {
+                let mut w_without_player = w.clone();
+                w_without_player.remove(player);
+                if !is_winning(&w_without_player) {
+                    *sums.entry(player).or_default() += &share;
+                }
+            }
+        }
+        self.players
+            .iter()
+            .map(|p| sums.get(p).map_or(Score::default(), rational_to_score))
+            .collect()
+    }
+
+    /// Same as compute_exact_ss_power_index_for_game but sums up the players' shares of the
+    /// orderings as exact fractions instead of truncated floats
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn compute_exact_ss_power_index_as_rationals(
+        &self,
+        qi_check: bool,
+    ) -> Vec<Rational> {
+        let top_tier = if let Some(tt) = self.top_tier.clone() {
+            tt
+        } else {
+            Self::get_involved_nodes(self.fbas, qi_check)
+        };
+        let num_players = top_tier.len();
+        let total_factorial = n_factorial(num_players);
+        let critical_coalitions = self.critical_coalitions(&top_tier);
+        self.players
+            .iter()
+            .map(|&p| {
+                critical_coalitions[&p]
+                    .iter()
+                    .map(|w| share_of_orderings(w, num_players, &total_factorial))
+                    .sum()
+            })
+            .collect()
+    }
+
+    /// Returns the threshold if every node in the top tier has the same flat quorum set whose
+    /// validators are exactly the top tier. A coalition of the top tier is then winning iff it
+    /// has at least threshold members, i.e. the game is a weighted majority game with unit weights.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn flat_threshold_of_top_tier(&self, top_tier: &[NodeId]) -> Option<usize> {
+        let mut sorted_tt = top_tier.to_vec();
+        sorted_tt.sort_unstable();
+        let mut threshold = None;
+        for &node in top_tier {
+            let qset = self.fbas.get_quorum_set(node)?;
+            let mut validators = qset.validators.clone();
+            validators.sort_unstable();
+            if !qset.inner_quorum_sets.is_empty()
+                || validators != sorted_tt
+                || qset.threshold == 0
+                || threshold.is_some_and(|t| t != qset.threshold)
+            {
+                return None;
+            }
+            threshold = Some(qset.threshold);
+        }
+        threshold
+    }
+
     /// winning_coalitions: a player's winning coalitions used to find their power index
     /// num_players: number of players in the top tier
     /// total_factorial: The factorial of num_players
+    #[cfg(not(target_arch = "wasm32"))]
     fn computer_power_index_for_player(
         winning_coalitions: Option<&Vec<Coalition>>,
         num_players: usize,
         total_factorial: Integer,
     ) -> Score {
         if let Some(critical_coalitions) = winning_coalitions {
-            round_to_three_places(
-                critical_coalitions
-                    .iter()
-                    .map(|w| value_added_to_one_coalition(w, num_players, total_factorial.clone()))
-                    .sum(),
-            )
+            let index: Rational = critical_coalitions
+                .iter()
+                .map(|w| share_of_orderings(w, num_players, &total_factorial))
+                .sum();
+            rational_to_score(&index)
         } else {
             Score::default()
         }
     }
 
+    /// Every player's critical coalitions among the winning coalitions of the top tier. If
+    /// config.cache_coalitions is set, they are only enumerated by the first call for a top tier
+    /// and shared with all further calls on the game.
+    pub(crate) fn critical_coalitions(
+        &self,
+        top_tier: &[NodeId],
+    ) -> Rc<HashMap<NodeId, Vec<Coalition>>> {
+        if let Some(cache) = self.coalition_cache.borrow().as_ref() {
+            if self.config.cache_coalitions && cache.top_tier == top_tier {
+                info!("Reusing the critical coalitions of an earlier run.");
+                return Rc::clone(&cache.critical_coalitions);
+            }
+        }
+        let winning_coalitions = self.find_winning_coalitions(top_tier);
+        let critical_coalitions: Rc<HashMap<NodeId, Vec<Coalition>>> = Rc::new(
+            self.players
+                .iter()
+                .map(|&v| (v, Self::player_is_critical(v, &winning_coalitions)))
+                .collect(),
+        );
+        if self.config.cache_coalitions {
+            *self.coalition_cache.borrow_mut() = Some(CoalitionCache {
+                top_tier: top_tier.to_vec(),
+                critical_coalitions: Rc::clone(&critical_coalitions),
+            });
+        }
+        critical_coalitions
+    }
+
     /// We construct the power set based on the players in the top tier
     /// If a coalition contains a quorum, it is a winning coalition
     pub(crate) fn find_winning_coalitions(&self, top_tier: &[NodeId]) -> HashSet<Coalition> {
-        let all_coalitions = top_tier.iter().copied().powerset();
-        all_coalitions
-            .into_iter()
-            .filter(|s| {
-                let quorum = s.clone().into_iter().collect();
-                fbas_analyzer::contains_quorum(&quorum, self.fbas)
-            })
-            .map(|s| s.into_iter().collect())
-            .collect()
+        #[cfg(test)]
+        tests::WINNING_COALITION_SEARCHES.with(|searches| searches.set(searches.get() + 1));
+        let cache = self.new_quorum_cache(top_tier);
+        winning_subsets(top_tier.to_vec(), self.fbas, cache.as_ref()).collect()
     }
 
     /// Get a player's winning coalitions, i.e. the quorums that contain the player and lose quorum
@@ -99,32 +270,158 @@ impl<'a> CooperativeGame<'a> {
     }
 }
 
+/// Streams the winning coalitions, i.e. the subsets of the top tier that contain a quorum, one at
+/// a time. Unlike the set of winning coalitions used by the exact power index, the coalitions are
+/// never held in memory together, so looking up whether a (sub- or super)set of a coalition is
+/// winning requires another quorum containment check.
+pub fn winning_coalitions_iter(
+    fbas: &Fbas,
+    qi_check: bool,
+) -> impl Iterator<Item = Coalition> + '_ {
+    let top_tier = CooperativeGame::get_involved_nodes(fbas, qi_check);
+    winning_subsets(top_tier, fbas, None)
+}
+
+/// Streams the winning coalitions by extending every minimal quorum with the subsets of the
+/// remaining top tier nodes, so that no losing coalition is ever checked. A coalition is only
+/// yielded for the first minimal quorum it contains, which deduplicates the supersets shared by
+/// several minimal quorums without remembering the yielded coalitions. Only the minimal quorums
+/// are held in memory.
+pub fn winning_coalitions_from_minimal(
+    fbas: &Fbas,
+    qi_check: bool,
+) -> impl Iterator<Item = Coalition> {
+    let min_quorums = fbas_analyzer::find_minimal_quorums(fbas);
+    if qi_check {
+        assert!(
+            fbas_analyzer::all_intersect(&min_quorums),
+            "FBAS lacks quorum intersection!"
+        );
+    }
+    let top_tier = fbas_analyzer::involved_nodes(&min_quorums);
+    let min_quorums = Rc::new(min_quorums);
+    (0..min_quorums.len()).flat_map(move |i| {
+        let min_quorums = Rc::clone(&min_quorums);
+        let quorum = min_quorums[i].clone();
+        let others: Vec<NodeId> = top_tier.difference(&quorum).collect();
+        others
+            .into_iter()
+            .powerset()
+            .map(move |extension| {
+                let mut coalition = quorum.clone();
+                coalition.extend(extension);
+                coalition
+            })
+            .filter(move |coalition| {
+                !min_quorums[..i]
+                    .iter()
+                    .any(|earlier| earlier.is_subset(coalition))
+            })
+    })
+}
+
+/// Lazily enumerates the subsets of nodes that contain a quorum
+fn winning_subsets<'a>(
+    nodes: Vec<NodeId>,
+    fbas: &'a Fbas,
+    cache: Option<&'a QuorumCache>,
+) -> impl Iterator<Item = Coalition> + 'a {
+    nodes
+        .into_iter()
+        .powerset()
+        .map(|s| s.into_iter().collect())
+        .filter(move |coalition| contains_quorum(coalition, fbas, cache))
+}
+
 /// Implementation of the SSPI for one coalition
 /// coalition: BitSet of player IDs
 /// num_players: Total number of players in the game
 /// fact_total: Factorial of total number of players in the game
+#[cfg(not(target_arch = "wasm32"))]
 pub(crate) fn value_added_to_one_coalition(
     coalition: &Coalition,
     num_players: usize,
     fact_total: Integer,
 ) -> Score {
+    let share = share_of_orderings(coalition, num_players, &fact_total);
+    // It's now safe to return to a primitive data type under the assumption that the reduced
+    // numerator and denominator fit in 64 bits
+    share.numer().to_f64() / share.denom().to_f64()
+}
+
+/// Exact share of all orderings of the players in which the last member of the coalition to join
+/// completes it, i.e. (|coalition| - 1)! * (num_players - |coalition|)! / num_players!
+#[cfg(not(target_arch = "wasm32"))]
+fn share_of_orderings(coalition: &Coalition, num_players: usize, fact_total: &Integer) -> Rational {
     let set_size = CooperativeGame::coalitions_cardinatily(coalition);
-    let set_size_minus_one_factorial = n_factorial(set_size - 1);
-    let n_minus_set_size_factorial = n_factorial(num_players - set_size);
-    let dividend = set_size_minus_one_factorial * n_minus_set_size_factorial;
-    let gcd = dividend.clone().gcd(&fact_total);
-    let numerator = dividend / gcd.clone();
-    let denominator = fact_total / gcd;
-    // It's now safe to return to a primitive data type under the assumption that num/gcd <  denom/gcd and fits in 64 bits
-    numerator.to_f64() / denominator.to_f64()
+    let dividend = n_factorial(set_size - 1) * n_factorial(num_players - set_size);
+    Rational::from((dividend, fact_total.clone()))
+}
+
+/// Converts an exactly summed up power index to a score truncated to three places, so that all
+/// exact algorithms round the same way
+#[cfg(not(target_arch = "wasm32"))]
+fn rational_to_score(index: &Rational) -> Score {
+    round_to_three_places(index.numer().to_f64() / index.denom().to_f64())
+}
+/// Shapley-Shubik index of a weighted majority game, i.e. a coalition is winning iff the sum of
+/// its members' weights is at least the quota.
+/// For every player, a DP over the generating polynomial counts the coalitions of the other
+/// players by size and weight (capped at the quota). The player is pivotal for the coalitions
+/// whose weight is below the quota but reaches it once the player joins.
+/// Returns a list of scores with index i = the i-th weight's score
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn weighted_majority_ss_power_index(weights: &[usize], quota: usize) -> Vec<Score> {
+    let num_players = weights.len();
+    let total_factorial = n_factorial(num_players);
+    (0..num_players)
+        .map(|player| {
+            // counts[k][w]: number of coalitions of k other players with weight w
+            let mut counts = vec![vec![Integer::new(); quota + 1]; num_players];
+            counts[0][0] = Integer::from(1);
+            for (other, &weight) in weights.iter().enumerate() {
+                if other == player {
+                    continue;
+                }
+                for k in (0..num_players - 1).rev() {
+                    for w in (0..=quota).rev() {
+                        if counts[k][w] != 0 {
+                            let added = counts[k][w].clone();
+                            counts[k + 1][(w + weight).min(quota)] += added;
+                        }
+                    }
+                }
+            }
+            let mut pivotal_orderings = Integer::new();
+            for (k, by_weight) in counts.iter().enumerate() {
+                let orderings = n_factorial(k) * n_factorial(num_players - k - 1);
+                for (w, count) in by_weight.iter().enumerate() {
+                    if w < quota && w + weights[player] >= quota {
+                        pivotal_orderings += count * orderings.clone();
+                    }
+                }
+            }
+            rational_to_score(&Rational::from((
+                pivotal_orderings,
+                total_factorial.clone(),
+            )))
+        })
+        .collect()
 }
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use approx::*;
     use fbas_analyzer::{bitset, Fbas, NodeId};
+    use std::cell::Cell;
     use std::path::Path;
 
+    thread_local! {
+        /// Number of times the winning coalitions have been enumerated on this thread
+        pub(super) static WINNING_COALITION_SEARCHES: Cell<usize> = const { Cell::new(0) };
+    }
+
     #[test]
     fn all_winning_sets_in_fbas() {
         let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
@@ -134,6 +431,9 @@ mod tests {
             fbas: &fbas,
             players: fbas.all_nodes().iter().collect(),
             top_tier: None,
+            config: RankConfig::default(),
+            coalition_cache: Default::default(),
+            forced: Vec::new(),
         };
         let actual = game.find_winning_coalitions(&top_tier);
         let expected = HashSet::from([
@@ -256,14 +556,137 @@ mod tests {
                     ]
                 }
             }]"#;
-        let fbas = Fbas::from_json_str(&input);
+        let fbas = Fbas::from_json_str(input);
         let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
         let game = CooperativeGame::init_from_fbas(&all_nodes, &fbas);
         let qi_check = true;
-        let expected = vec![7.0 / 15.0, 4.0 / 30.0, 4.0 / 30.0, 4.0 / 30.0, 4.0 / 30.0];
+        let expected = [7.0 / 15.0, 4.0 / 30.0, 4.0 / 30.0, 4.0 / 30.0, 4.0 / 30.0];
         let actual = game.compute_exact_ss_power_index_for_game(qi_check);
         for (i, _) in expected.iter().enumerate() {
             assert_relative_eq!(round_to_three_places(expected[i]), actual[i]);
         }
     }
+
+    #[test]
+    fn forcing_a_node_into_every_coalition_changes_the_other_indices() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+        let unforced = CooperativeGame::init_from_fbas(&all_nodes, &fbas)
+            .compute_exact_ss_power_index_for_game(true);
+        let game = CooperativeGame::init_from_fbas_with_forced(&all_nodes, &[0], &fbas);
+        assert_eq!(vec![1, 2, 3, 4], game.players);
+        let forced = game.compute_exact_ss_power_index_for_game(true);
+        // with node 0 always present, {1, 2} and {3, 4} are the minimal winning coalitions
+        for (&before, &after) in unforced[1..].iter().zip(forced.iter()) {
+            assert_abs_diff_eq!(0.25, after, epsilon = 0.001);
+            assert!(after > before);
+        }
+    }
+
+    #[test]
+    fn flat_threshold_game_via_dp_matches_enumeration() {
+        let validators = r#"["node0", "node1", "node2", "node3", "node4"]"#;
+        let input: String = (0..5)
+            .map(|i| {
+                format!(
+                    r#"{{ "publicKey": "node{i}", "quorumSet": {{ "threshold": 3, "validators": {validators} }} }}"#
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(",");
+        let fbas = Fbas::from_json_str(&format!("[{input}]"));
+        let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+        let game = CooperativeGame::init_from_fbas(&all_nodes, &fbas);
+        assert_eq!(Some(3), game.flat_threshold_of_top_tier(&all_nodes));
+        let enumerated = game.compute_exact_ss_power_index_via_enumeration(&all_nodes);
+        let via_dp = game.compute_exact_ss_power_index_for_game(true);
+        assert_eq!(vec![0.2; 5], via_dp);
+        assert_eq!(enumerated, via_dp);
+    }
+
+    #[test]
+    fn weighted_majority_index_matches_known_values() {
+        // [3; 2, 1, 1]: the heavy player is pivotal in 4 of 6 orderings
+        let actual = weighted_majority_ss_power_index(&[2, 1, 1], 3);
+        assert_eq!(vec![0.666, 0.166, 0.166], actual);
+    }
+
+    #[test]
+    fn paper_example_is_not_a_flat_threshold_game() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+        let game = CooperativeGame::init_from_fbas(&all_nodes, &fbas);
+        assert_eq!(None, game.flat_threshold_of_top_tier(&all_nodes));
+    }
+
+    #[test]
+    fn rational_power_indices_are_exact() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+        let game = CooperativeGame::init_from_fbas(&all_nodes, &fbas);
+        let actual = game.compute_exact_ss_power_index_as_rationals(true);
+        let expected = vec![
+            Rational::from((7, 15)),
+            Rational::from((2, 15)),
+            Rational::from((2, 15)),
+            Rational::from((2, 15)),
+            Rational::from((2, 15)),
+        ];
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn streamed_winning_coalitions_equal_materialized_set() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+        let game = CooperativeGame::init_from_fbas(&all_nodes, &fbas);
+        let materialized = game.find_winning_coalitions(&all_nodes);
+        let streamed: Vec<Coalition> = winning_coalitions_iter(&fbas, true).collect();
+        assert_eq!(materialized.len(), streamed.len());
+        assert_eq!(materialized, streamed.into_iter().collect());
+    }
+
+    #[test]
+    fn coalitions_from_minimal_quorums_equal_materialized_set() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+        let game = CooperativeGame::init_from_fbas(&all_nodes, &fbas);
+        let materialized = game.find_winning_coalitions(&all_nodes);
+        let streamed: Vec<Coalition> = winning_coalitions_from_minimal(&fbas, true).collect();
+        assert_eq!(materialized.len(), streamed.len());
+        assert_eq!(materialized, streamed.into_iter().collect());
+    }
+
+    #[test]
+    fn streamed_and_materialized_power_indices_agree() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+        let mut game = CooperativeGame::init_from_fbas(&all_nodes, &fbas);
+        let materialized = game.compute_exact_ss_power_index_for_game(true);
+        game.config.stream_coalitions = true;
+        let streamed = game.compute_exact_ss_power_index_for_game(true);
+        assert_eq!(materialized, streamed);
+    }
+
+    #[test]
+    fn cached_game_enumerates_winning_coalitions_once() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+        for (cache_coalitions, expected_searches) in [(true, 1), (false, 2)] {
+            let mut game = CooperativeGame::init_from_fbas(&all_nodes, &fbas);
+            game.config.cache_coalitions = cache_coalitions;
+            WINNING_COALITION_SEARCHES.with(|searches| searches.set(0));
+            let shapley = game.compute_exact_ss_power_index_for_game(true);
+            let banzhaf = game.compute_banzhaf_index_for_game(true);
+            assert_eq!(
+                expected_searches,
+                WINNING_COALITION_SEARCHES.with(|searches| searches.get())
+            );
+            assert_eq!(
+                rank_nodes(&fbas, RankingAlg::PowerIndexEnum(None), true),
+                shapley
+            );
+            assert_eq!(compute_banzhaf_normalized(&fbas, None, true), banzhaf);
+        }
+    }
 }
@@ -1,9 +1,32 @@
 mod approx_shapley_shubik;
+mod banzhaf;
 mod exact_shapley_shubik;
 mod node_rank;
 mod ranking;
 mod util;
 
-pub(crate) use node_rank::compute_node_rank_for_fbas;
+pub(crate) use approx_shapley_shubik::seeded_rng;
+pub use approx_shapley_shubik::{bootstrap_ci, castro_sample_bound, PermutationSampler};
+pub use banzhaf::{compute_banzhaf_absolute, compute_banzhaf_normalized, swing_counts};
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use exact_shapley_shubik::value_added_to_one_coalition;
+pub use exact_shapley_shubik::{winning_coalitions_from_minimal, winning_coalitions_iter};
+#[cfg(not(target_arch = "wasm32"))]
+pub use node_rank::compute_node_rank_rational;
+pub use node_rank::{
+    amplification_factors, compute_node_rank_for_fbas_with_backend,
+    compute_node_rank_for_fbas_with_pagerank, noderank_amplification, rank_nodes_using_page_rank,
+    PageRankBackend,
+};
+pub(crate) use node_rank::{
+    compute_node_rank_for_fbas, compute_node_rank_for_involved_nodes, compute_node_rank_for_lscc,
+};
 pub use ranking::*;
 pub(crate) use util::*;
+pub use util::{
+    compute_top_tier, fbas_seed, largest_strongly_connected_component, merge_fbas,
+    minimal_quorum_membership, minimal_quorums_with_pks, nodes_with_empty_quorum_sets,
+    normalize_scores, quorum_intersection_witness, quorum_set_hashes, scale_scores,
+    scale_scores_with_ci, self_trusting_nodes, symmetric_node_groups, trusting_nodes,
+    unreachable_nodes, validate_top_tier, ScoreScale,
+};
@@ -1,8 +1,41 @@
 use crate::*;
 
+#[cfg(not(target_arch = "wasm32"))]
+use fbas_analyzer::simulation::Graph;
 use fbas_analyzer::{Fbas, NodeId, QuorumSet};
 use log::trace;
+#[cfg(not(target_arch = "wasm32"))]
+use rug::Rational;
 use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+/// Implementation of PageRank the NodeRank scores are based on.
+///
+/// Neither implementation uses a damping factor. `Internal` runs a fixed number of 100 iterations
+/// while `Analyzer` stops once no score changes by more than max(1/n^2, 0.00001) between two
+/// iterations (at most max(2n, 1000) iterations). The scores of both may therefore differ in the
+/// last decimal places, particularly for FBASs whose trust graph does not converge quickly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PageRankBackend {
+    /// fbas_analyzer's `Fbas::rank_nodes`
+    #[default]
+    Internal,
+    /// fbas_analyzer's graph-based `Graph::get_rank_scores`
+    #[cfg(not(target_arch = "wasm32"))]
+    Analyzer,
+}
+
+impl FromStr for PageRankBackend {
+    type Err = &'static str;
+    fn from_str(backend: &str) -> Result<Self, Self::Err> {
+        match backend.to_lowercase().as_ref() {
+            "internal" => Ok(PageRankBackend::Internal),
+            #[cfg(not(target_arch = "wasm32"))]
+            "analyzer" => Ok(PageRankBackend::Analyzer),
+            _ => Err("Unknown PageRank backend"),
+        }
+    }
+}
 
 /// NodeRank is an extension of PageRank proposed by Kim et al. in the paper 'Is Stellar as Secure
 /// As You Think?'.
@@ -11,6 +44,27 @@ pub(crate) fn compute_node_rank_for_fbas(
     fbas: &Fbas,
     qi_check: bool,
 ) -> Vec<Score> {
+    compute_node_rank_for_fbas_with_pagerank(nodes, fbas, qi_check).0
+}
+
+/// Computes the NodeRank scores and also returns the underlying PageRank scores of all nodes in
+/// the FBAS as (NodeRank scores, PageRank scores)
+pub fn compute_node_rank_for_fbas_with_pagerank(
+    nodes: &[NodeId],
+    fbas: &Fbas,
+    qi_check: bool,
+) -> (Vec<Score>, Vec<Score>) {
+    compute_node_rank_for_fbas_with_backend(nodes, fbas, qi_check, PageRankBackend::Internal)
+}
+
+/// Same as `compute_node_rank_for_fbas_with_pagerank` but computes the PageRank scores using the
+/// passed backend, e.g. to cross-check the scores against the graph-based implementation
+pub fn compute_node_rank_for_fbas_with_backend(
+    nodes: &[NodeId],
+    fbas: &Fbas,
+    qi_check: bool,
+    backend: PageRankBackend,
+) -> (Vec<Score>, Vec<Score>) {
     if qi_check {
         trace!("Ensuring the FBAS has quorum intersection.");
         assert!(
@@ -18,7 +72,7 @@ pub(crate) fn compute_node_rank_for_fbas(
             "FBAS lacks quorum intersection!"
         );
     }
-    let page_rank_scores = fbas.rank_nodes();
+    let page_rank_scores = page_rank_scores(fbas, backend);
     // A map of <NodeID, [qsets node is in]>
     let sets_involving_node: HashMap<NodeId, HashSet<QuorumSet>> = nodes
         .iter()
@@ -36,7 +90,218 @@ pub(crate) fn compute_node_rank_for_fbas(
             )
         })
         .collect();
+    (nr_scores, page_rank_scores)
+}
+
+/// PageRank scores of all nodes in the FBAS. A node links to every node in its quorum set.
+fn page_rank_scores(fbas: &Fbas, backend: PageRankBackend) -> Vec<Score> {
+    match backend {
+        PageRankBackend::Internal => fbas.rank_nodes(),
+        #[cfg(not(target_arch = "wasm32"))]
+        PageRankBackend::Analyzer => {
+            let outlinks: Vec<Vec<NodeId>> = (0..fbas.number_of_nodes())
+                .map(|v| {
+                    fbas.get_quorum_set(v)
+                        .map(|qset| qset.contained_nodes().into_iter().collect())
+                        .unwrap_or_default()
+                })
+                .collect();
+            Graph::new(outlinks).get_rank_scores()
+        }
+    }
+}
+
+/// PageRank scores of all nodes in the FBAS after 100 iterations, without damping.
+///
+/// If `threshold_weighted` is false, a node splits its score equally among all nodes in its
+/// quorum set, as in `PageRankBackend::Internal`. Otherwise, each contained node gets a share
+/// proportional to its weight in the quorum set, i.e., the product of threshold/|Q| of every
+/// (inner) quorum set on the way to the node, so that nodes the truster depends on more receive
+/// more of its score. The shares of a node always sum up to its score.
+pub fn rank_nodes_using_page_rank(fbas: &Fbas, threshold_weighted: bool) -> Vec<Score> {
+    if !threshold_weighted {
+        return page_rank_scores(fbas, PageRankBackend::Internal);
+    }
+    let runs = 100;
+    let n = fbas.number_of_nodes();
+    // (trusted node, share of the truster's score) per node
+    let links: Vec<Vec<(NodeId, Score)>> = (0..n)
+        .map(|v| {
+            let qset = match fbas.get_quorum_set(v) {
+                Some(qset) => qset,
+                None => return vec![],
+            };
+            let weights: Vec<(NodeId, Score)> = qset
+                .contained_nodes()
+                .iter()
+                .map(|w| (w, node_weight_in_quorum_set(w, &qset)))
+                .collect();
+            let total: Score = weights.iter().map(|(_, weight)| weight).sum();
+            if total > 0.0 {
+                weights
+                    .into_iter()
+                    .map(|(w, weight)| (w, weight / total))
+                    .collect()
+            } else {
+                vec![]
+            }
+        })
+        .collect();
+    let mut scores: Vec<Score> = vec![1.0 / n as Score; n];
+    for _ in 0..runs {
+        let mut next_scores = vec![0.0; n];
+        for (v, node_links) in links.iter().enumerate() {
+            for &(w, share) in node_links {
+                next_scores[w] += scores[v] * share;
+            }
+        }
+        scores = next_scores;
+    }
+    scores
+}
+
+/// Same as compute_node_rank_for_fbas but the NodeRank is only computed for the nodes that are
+/// part of a minimal quorum; all other nodes get a score of 0. Quorum sets are only mapped to
+/// the nodes that have a PageRank score, i.e., that are (transitively) trusted by other nodes.
+/// Leaf nodes nobody trusts pass their initial score on in the first iteration and contribute
+/// nothing to anyone's NodeRank afterwards, so leaving out their quorum sets does not change the
+/// scores of the involved nodes. PageRank itself still runs on all nodes as the trust of the leaf
+/// nodes is what gives the top tier its score.
+pub(crate) fn compute_node_rank_for_involved_nodes(
+    nodes: &[NodeId],
+    fbas: &Fbas,
+    qi_check: bool,
+) -> Vec<Score> {
+    let min_quorums = fbas_analyzer::find_minimal_quorums(fbas);
+    if qi_check {
+        trace!("Ensuring the FBAS has quorum intersection.");
+        assert!(
+            fbas_analyzer::all_intersect(&min_quorums),
+            "FBAS lacks quorum intersection!"
+        );
+    }
+    let involved = fbas_analyzer::involved_nodes(&min_quorums);
+    let page_rank_scores = page_rank_scores(fbas, PageRankBackend::default());
+    let trusted: Vec<NodeId> = (0..fbas.number_of_nodes())
+        .filter(|&v| page_rank_scores[v] > 0.0)
+        .collect();
+    let mut sets_generators_map: HashMap<String, HashSet<NodeId>> = HashMap::default();
+    for &v in trusted.iter() {
+        sets_generators_map
+            .entry(quorum_set_hash(fbas, v))
+            .or_default()
+            .insert(v);
+    }
+    nodes
+        .iter()
+        .map(|&v| {
+            if !involved.contains(v) {
+                return Score::default();
+            }
+            let sets_involving_node: HashSet<QuorumSet> = trusted
+                .iter()
+                .filter_map(|&creator| fbas.get_quorum_set(creator))
+                .filter(|qset| qset.contained_nodes().contains(v))
+                .collect();
+            compute_node_rank(
+                v,
+                Some(&sets_involving_node),
+                &sets_generators_map,
+                &page_rank_scores,
+            )
+        })
+        .collect()
+}
+
+/// Same as compute_node_rank_for_fbas but only the largest strongly connected component (LSCC) of
+/// the trust graph is ranked; all other nodes get a score of 0. PageRank only runs on the nodes
+/// of the LSCC and ignores links to nodes outside of it, so that disconnected or peripheral
+/// clusters do not distort the scores. See largest_strongly_connected_component for how the
+/// component is derived from the quorum sets.
+pub(crate) fn compute_node_rank_for_lscc(
+    nodes: &[NodeId],
+    fbas: &Fbas,
+    qi_check: bool,
+) -> Vec<Score> {
+    if qi_check {
+        trace!("Ensuring the FBAS has quorum intersection.");
+        assert!(
+            fbas_analyzer::all_intersect(&fbas_analyzer::find_minimal_quorums(fbas)),
+            "FBAS lacks quorum intersection!"
+        );
+    }
+    let lscc: HashSet<NodeId> = largest_strongly_connected_component(fbas)
+        .into_iter()
+        .collect();
+    let lscc_nodes: Vec<NodeId> = lscc.iter().copied().collect();
+    let page_rank_scores = fbas_analyzer::preprocessing::rank_nodes(&lscc_nodes, fbas);
+    let sets_generators_map = map_quorum_sets_to_generators(fbas);
+    nodes
+        .iter()
+        .map(|&v| {
+            if lscc.contains(&v) {
+                let sets_involving_node = all_quorum_sets_containing_node(v, fbas);
+                compute_node_rank(
+                    v,
+                    Some(&sets_involving_node),
+                    &sets_generators_map,
+                    &page_rank_scores,
+                )
+            } else {
+                Score::default()
+            }
+        })
+        .collect()
+}
+
+/// Same as compute_node_rank_for_fbas but the scores are exact rationals and not rounded. Only
+/// the quorum set weights (T/|Q|) are exact: the PageRank scores they are multiplied with are
+/// still computed as floats and converted to rationals as they are.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn compute_node_rank_rational(nodes: &[NodeId], fbas: &Fbas, qi_check: bool) -> Vec<Rational> {
+    if qi_check {
+        trace!("Ensuring the FBAS has quorum intersection.");
+        assert!(
+            fbas_analyzer::all_intersect(&fbas_analyzer::find_minimal_quorums(fbas)),
+            "FBAS lacks quorum intersection!"
+        );
+    }
+    let page_rank_scores = page_rank_scores(fbas, PageRankBackend::default());
+    let sets_generators_map = map_quorum_sets_to_generators(fbas);
+    nodes
+        .iter()
+        .map(|&v| {
+            let mut node_rank = Rational::new();
+            for set in all_quorum_sets_containing_node(v, fbas) {
+                let creators = get_list_of_creators_for_quorum_set(&set, &sets_generators_map);
+                let pr_sum: Rational = creators
+                    .iter()
+                    .map(|&c| Rational::from_f64(page_rank_scores[c]).unwrap_or_default())
+                    .sum();
+                node_rank += pr_sum * node_weight_in_quorum_set_rational(v, &set);
+            }
+            node_rank
+        })
+        .collect()
+}
+
+/// How much NodeRank boosts (> 1) or dampens (< 1) every node relative to its PageRank score,
+/// i.e., NodeRank / PageRank per node. Nodes without a PageRank score get 0.
+pub fn noderank_amplification(fbas: &Fbas, qi_check: bool) -> Vec<f64> {
+    let all_nodes: Vec<NodeId> = (0..fbas.number_of_nodes()).collect();
+    let (nr_scores, pr_scores) =
+        compute_node_rank_for_fbas_with_pagerank(&all_nodes, fbas, qi_check);
+    amplification_factors(&nr_scores, &pr_scores)
+}
+
+/// NodeRank / PageRank of every node given both scores indexed by NodeId. Nodes without a
+/// PageRank score get 0.
+pub fn amplification_factors(nr_scores: &[Score], pr_scores: &[Score]) -> Vec<f64> {
     nr_scores
+        .iter()
+        .zip(pr_scores)
+        .map(|(nr, pr)| if *pr > 0.0 { nr / pr } else { 0.0 })
+        .collect()
 }
 
 /// Given a node ID, returns the NodeRank score of the node
@@ -69,6 +334,7 @@ fn compute_node_rank(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use approx::*;
     use std::path::Path;
 
     #[test]
@@ -89,6 +355,96 @@ mod tests {
         assert_eq!(actual, expected);
     }
     #[test]
+    fn node_rank_with_pagerank_returns_both_scores() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
+        let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+        let (nr_scores, pr_scores) =
+            compute_node_rank_for_fbas_with_pagerank(&all_nodes, &fbas, true);
+        assert_eq!(
+            nr_scores,
+            compute_node_rank_for_fbas(&all_nodes, &fbas, true)
+        );
+        assert_eq!(pr_scores, fbas.rank_nodes());
+    }
+    #[test]
+    fn pagerank_backends_agree_on_ordering_of_trivial_fbas() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
+        let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+        let (internal, _) = compute_node_rank_for_fbas_with_backend(
+            &all_nodes,
+            &fbas,
+            true,
+            PageRankBackend::Internal,
+        );
+        let (analyzer, _) = compute_node_rank_for_fbas_with_backend(
+            &all_nodes,
+            &fbas,
+            true,
+            PageRankBackend::Analyzer,
+        );
+        let ordering = |scores: &[Score]| {
+            let mut nodes = all_nodes.clone();
+            nodes.sort_by(|&a, &b| scores[b].partial_cmp(&scores[a]).unwrap().then(a.cmp(&b)));
+            nodes
+        };
+        assert_eq!(ordering(&internal), ordering(&analyzer));
+        for (x, y) in internal.iter().zip(analyzer.iter()) {
+            assert_abs_diff_eq!(x, y, epsilon = 0.002);
+        }
+    }
+    #[test]
+    fn involved_only_node_rank_matches_full_computation_for_top_tier() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/correct.json"));
+        let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+        let full = compute_node_rank_for_fbas(&all_nodes, &fbas, true);
+        let involved_only = compute_node_rank_for_involved_nodes(&all_nodes, &fbas, true);
+        let top_tier = fbas_analyzer::involved_nodes(&fbas_analyzer::find_minimal_quorums(&fbas));
+        for node in all_nodes {
+            if top_tier.contains(node) {
+                assert_eq!(full[node], involved_only[node]);
+            } else {
+                assert_eq!(0.0, involved_only[node]);
+            }
+        }
+    }
+    #[test]
+    fn rational_node_rank_matches_float_node_rank() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
+        let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+        let expected = compute_node_rank_for_fbas(&all_nodes, &fbas, true);
+        let actual: Vec<Score> = compute_node_rank_rational(&all_nodes, &fbas, true)
+            .iter()
+            .map(|score| round_to_three_places(score.to_f64()))
+            .collect();
+        assert_eq!(expected, actual);
+    }
+    #[test]
+    fn threshold_weighted_page_rank_can_change_ordering() {
+        // node 0 depends on node 1 alone but on only one of nodes 2, 3 and 4
+        let fbas = Fbas::from_json_str(
+            r#"[
+            { "publicKey": "n0", "quorumSet": { "threshold": 2, "validators": ["n1"],
+                "innerQuorumSets": [{ "threshold": 1, "validators": ["n2", "n3", "n4"] }] } },
+            { "publicKey": "n1", "quorumSet": { "threshold": 1, "validators": ["n0"] } },
+            { "publicKey": "n2", "quorumSet": { "threshold": 1, "validators": ["n0"] } },
+            { "publicKey": "n3", "quorumSet": { "threshold": 1, "validators": ["n0"] } },
+            { "publicKey": "n4", "quorumSet": { "threshold": 2, "validators": ["n0", "n2"] } }
+        ]"#,
+        );
+        let uniform = rank_nodes_using_page_rank(&fbas, false);
+        let weighted = rank_nodes_using_page_rank(&fbas, true);
+        assert!(uniform[2] > uniform[1]);
+        assert!(weighted[1] > weighted[2]);
+        assert_abs_diff_eq!(1.0, weighted.iter().sum::<Score>(), epsilon = 0.000001);
+    }
+
+    #[test]
+    fn parse_pagerank_backend() {
+        assert_eq!(Ok(PageRankBackend::Analyzer), "Analyzer".parse());
+        assert_eq!(Ok(PageRankBackend::Internal), "internal".parse());
+        assert!("foo".parse::<PageRankBackend>().is_err());
+    }
+    #[test]
     // test case: same quorum set is created by two nodes with PR scores 0.01 and 0.02
     fn node_rank_from_paper_example() {
         let mut fbas = Fbas::new();
@@ -112,4 +468,33 @@ mod tests {
         let expected = 0.011; // calculated by self
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn noderank_amplification_of_nonsymmetric_fbas() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let actual = noderank_amplification(&fbas, true);
+        assert_eq!(fbas.number_of_nodes(), actual.len());
+        assert!(actual.iter().all(|a| a.is_finite() && *a > 0.0));
+        assert_eq!(
+            vec![0.0, 2.0],
+            amplification_factors(&[0.5, 0.5], &[0.0, 0.25])
+        );
+    }
+
+    #[test]
+    fn node_rank_for_lscc_ignores_smaller_cluster() {
+        let mut fbas = Fbas::new();
+        for _ in 0..4 {
+            fbas.add_generic_node(QuorumSet::new(vec![0, 1, 2, 3], vec![], 3));
+        }
+        for _ in 0..2 {
+            fbas.add_generic_node(QuorumSet::new(vec![4, 5], vec![], 2));
+        }
+        let all_nodes: Vec<NodeId> = (0..fbas.number_of_nodes()).collect();
+        let full = compute_node_rank_for_fbas(&all_nodes, &fbas, false);
+        assert!(full[4] > 0.0 && full[5] > 0.0);
+        let actual = compute_node_rank_for_lscc(&all_nodes, &fbas, false);
+        assert!(actual[..4].iter().all(|&score| score > 0.0));
+        assert_eq!(vec![0.0, 0.0], actual[4..]);
+    }
 }
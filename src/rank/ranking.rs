@@ -1,40 +1,602 @@
 use crate::*;
 
-use fbas_analyzer::{Fbas, NodeId};
+use fbas_analyzer::{find_minimal_quorums, involved_nodes, to_public_keys, Fbas, NodeId};
+use std::collections::BTreeMap;
+#[cfg(not(target_arch = "wasm32"))]
+use std::collections::HashMap;
 
 pub fn rank_nodes(fbas: &Fbas, ranking_algo: RankingAlg, qi_check: bool) -> Vec<Score> {
+    rank_nodes_with_config(fbas, ranking_algo, qi_check, RankConfig::default())
+}
+
+/// Same as rank_nodes but with optional settings of the run, e.g. memoization
+pub fn rank_nodes_with_config(
+    fbas: &Fbas,
+    ranking_algo: RankingAlg,
+    qi_check: bool,
+    config: RankConfig,
+) -> Vec<Score> {
+    try_rank_nodes_with_config(fbas, ranking_algo, qi_check, config)
+        .unwrap_or_else(|e| panic!("{e}"))
+}
+
+/// Same as rank_nodes_with_config but returns an error instead of panicking if config.strict is
+/// set and the top tier passed to a power index is not the top tier of the FBAS
+pub fn try_rank_nodes_with_config(
+    fbas: &Fbas,
+    ranking_algo: RankingAlg,
+    qi_check: bool,
+    config: RankConfig,
+) -> Result<Vec<Score>, RankingError> {
     let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
-    match ranking_algo {
+    if all_nodes.is_empty() {
+        return Ok(Vec::new());
+    }
+    if config.strict {
+        match &ranking_algo {
+            #[cfg(not(target_arch = "wasm32"))]
+            RankingAlg::PowerIndexEnum(Some(tt)) => validate_top_tier(fbas, tt)?,
+            RankingAlg::PowerIndexApprox(_, Some(tt)) | RankingAlg::BanzhafIndexEnum(Some(tt)) => {
+                validate_top_tier(fbas, tt)?
+            }
+            _ => {}
+        }
+    }
+    if is_single_node_quorum(fbas) {
+        // The only node is the only player and critical to the only winning coalition, no need
+        // to go through the games or PageRank
+        return Ok(vec![1.0]);
+    }
+    let scores = match ranking_algo {
+        #[cfg(not(target_arch = "wasm32"))]
         RankingAlg::PowerIndexEnum(top_tier) => {
-            if let Some(tt) = top_tier {
-                CooperativeGame::compute_exact_ss_power_index_for_game(
-                    &CooperativeGame::init_from_fbas_with_top_tier(&all_nodes, &tt, fbas),
-                    qi_check,
-                )
+            let mut game = if let Some(tt) = top_tier {
+                CooperativeGame::init_from_fbas_with_top_tier(&all_nodes, &tt, fbas)
             } else {
-                CooperativeGame::compute_exact_ss_power_index_for_game(
-                    &CooperativeGame::init_from_fbas(&all_nodes, fbas),
-                    qi_check,
-                )
+                CooperativeGame::init_from_fbas(&all_nodes, fbas)
+            };
+            game.config = config;
+            game.compute_exact_ss_power_index_for_game(qi_check)
+        }
+        RankingAlg::PowerIndexApprox(samples, top_tier) => {
+            let mut game = if let Some(tt) = top_tier {
+                CooperativeGame::init_from_fbas_with_top_tier(&all_nodes, &tt, fbas)
+            } else {
+                CooperativeGame::init_from_fbas(&all_nodes, fbas)
+            };
+            game.config = config;
+            game.compute_approx_ss_power_index_for_game(samples, qi_check)
+        }
+        RankingAlg::BanzhafIndexEnum(top_tier) => {
+            let mut game = if let Some(tt) = top_tier {
+                CooperativeGame::init_from_fbas_with_top_tier(&all_nodes, &tt, fbas)
+            } else {
+                CooperativeGame::init_from_fbas(&all_nodes, fbas)
+            };
+            game.config = config;
+            game.compute_banzhaf_index_for_game(qi_check)
+        }
+        RankingAlg::NodeRank if config.lscc_only => {
+            compute_node_rank_for_lscc(&all_nodes, fbas, qi_check)
+        }
+        RankingAlg::NodeRank if config.only_involved => {
+            compute_node_rank_for_involved_nodes(&all_nodes, fbas, qi_check)
+        }
+        RankingAlg::NodeRank => compute_node_rank_for_fbas(&all_nodes, fbas, qi_check),
+    };
+    Ok(scores)
+}
+
+/// True if the FBAS consists of a single node that forms a quorum on its own
+fn is_single_node_quorum(fbas: &Fbas) -> bool {
+    fbas.number_of_nodes() == 1 && fbas_analyzer::contains_quorum(&fbas.all_nodes(), fbas)
+}
+
+/// Removes every node from the FBAS one at a time and ranks the remaining nodes.
+/// Returns a list of (removed node, scores of the remaining nodes); the remaining nodes keep their
+/// relative order, so node i > removed node is at index i - 1.
+/// Note that this computes the ranking n times. For PowerIndexEnum, every run is exponential in
+/// the size of the (remaining) top tier. If a top tier is passed, it is recomputed for every
+/// sub-FBAS as the passed NodeIds are no longer valid after the removal.
+pub fn fragility_analysis(
+    fbas: &Fbas,
+    ranking_algo: RankingAlg,
+    qi_check: bool,
+) -> Vec<(NodeId, Vec<Score>)> {
+    let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+    let pks = to_public_keys(all_nodes.clone(), fbas);
+    all_nodes
+        .into_iter()
+        .map(|node| {
+            let sub_fbas = fbas.without_nodes_pretty(&[pks[node].clone()]);
+            let alg = with_recomputed_top_tier(&ranking_algo, &sub_fbas);
+            (node, rank_nodes(&sub_fbas, alg, qi_check))
+        })
+        .collect()
+}
+
+/// Replaces a passed top tier with the top tier of the FBAS, e.g. after the FBAS has changed
+pub(crate) fn with_recomputed_top_tier(ranking_algo: &RankingAlg, fbas: &Fbas) -> RankingAlg {
+    let top_tier = || {
+        let min_qs = find_minimal_quorums(fbas);
+        Some(involved_nodes(&min_qs).into_iter().collect())
+    };
+    match ranking_algo {
+        #[cfg(not(target_arch = "wasm32"))]
+        RankingAlg::PowerIndexEnum(Some(_)) => RankingAlg::PowerIndexEnum(top_tier()),
+        RankingAlg::BanzhafIndexEnum(Some(_)) => RankingAlg::BanzhafIndexEnum(top_tier()),
+        alg => alg.clone(),
+    }
+}
+
+/// Ranks every snapshot of a time series and aligns the scores by public key.
+/// Returns, per public key (sorted), the node's (date, score) in every snapshot in the order of
+/// the snapshots; nodes that are missing in a snapshot get a score of 0 for its date.
+/// As in fragility_analysis, a passed top tier is recomputed for every snapshot.
+pub fn rank_time_series(
+    snapshots: &[(String, Fbas)],
+    ranking_algo: RankingAlg,
+    qi_check: bool,
+) -> Vec<(PublicKey, Vec<(String, Score)>)> {
+    let mut series: BTreeMap<PublicKey, Vec<(String, Score)>> = BTreeMap::new();
+    for (i, (date, fbas)) in snapshots.iter().enumerate() {
+        let alg = with_recomputed_top_tier(&ranking_algo, fbas);
+        let scores = rank_nodes(fbas, alg, qi_check);
+        let pks = to_public_keys(0..scores.len(), fbas);
+        for (pk, score) in pks.into_iter().zip(scores) {
+            let node_series = series.entry(pk).or_insert_with(|| {
+                snapshots[..i]
+                    .iter()
+                    .map(|(date, _)| (date.clone(), Score::default()))
+                    .collect()
+            });
+            node_series.push((date.clone(), score));
+        }
+        for node_series in series.values_mut() {
+            if node_series.len() == i {
+                node_series.push((date.clone(), Score::default()));
             }
         }
-        RankingAlg::PowerIndexApprox(samples) => {
-            CooperativeGame::compute_approx_ss_power_index_for_game(
-                &CooperativeGame::init_from_fbas(&all_nodes, fbas),
-                samples,
-                qi_check,
+    }
+    series.into_iter().collect()
+}
+
+/// Checks whether the top tier without the node no longer contains a quorum, i.e. whether the node
+/// can veto every quorum. Needs a single quorum containment check on top of computing the top
+/// tier. Nodes outside the top tier are never critical.
+pub fn is_critical_in_grand_coalition(fbas: &Fbas, node: NodeId, qi_check: bool) -> bool {
+    let mut coalition: Coalition = CooperativeGame::get_involved_nodes(fbas, qi_check)
+        .into_iter()
+        .collect();
+    coalition.remove(node);
+    !fbas_analyzer::contains_quorum(&coalition, fbas)
+}
+
+/// Returns the smallest coalition in which the node is a swing player, i.e. the coalition
+/// contains a quorum but no longer does without the node. This is always one of the smallest
+/// minimal quorums containing the node; ties are broken by the lowest NodeIds. Returns None if
+/// the node is a dummy player, i.e. not part of any minimal quorum.
+pub fn smallest_critical_coalition(fbas: &Fbas, node: NodeId, qi_check: bool) -> Option<Coalition> {
+    let min_qs = find_minimal_quorums(fbas);
+    if qi_check {
+        assert!(
+            fbas_analyzer::all_intersect(&min_qs),
+            "FBAS lacks quorum intersection!"
+        );
+    }
+    min_qs
+        .into_iter()
+        .filter(|quorum| quorum.contains(node))
+        .min_by_key(|quorum| (quorum.len(), quorum.iter().collect::<Vec<NodeId>>()))
+}
+
+/// Breaks the node's exact Shapley-Shubik index down by the size of the coalitions it is
+/// critical for: maps each size to the sum of the node's (unrounded) shares of the orderings
+/// from its critical coalitions of that size. Sizes without a critical coalition are omitted, so
+/// a dummy player gets an empty map. The values sum up to the node's index.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn ss_contribution_by_size(
+    fbas: &Fbas,
+    node: NodeId,
+    qi_check: bool,
+) -> BTreeMap<usize, Score> {
+    let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+    let game = CooperativeGame::init_from_fbas(&all_nodes, fbas);
+    let top_tier = CooperativeGame::get_involved_nodes(fbas, qi_check);
+    let total_factorial = n_factorial(top_tier.len());
+    let winning_coalitions = game.find_winning_coalitions(&top_tier);
+    let mut contributions = BTreeMap::new();
+    for coalition in CooperativeGame::player_is_critical(node, &winning_coalitions) {
+        *contributions.entry(coalition.len()).or_default() +=
+            value_added_to_one_coalition(&coalition, top_tier.len(), total_factorial.clone());
+    }
+    contributions
+}
+
+/// Recomputes the exact Shapley-Shubik indices after changing the threshold of the node's quorum
+/// set by delta, e.g. to see how power shifts if the node requires one more or one fewer
+/// validator. The threshold is clamped to between 1 and the number of validators and inner quorum
+/// sets. The passed FBAS is left unchanged. Returns a list of scores with index 0 = node 0's score
+#[cfg(not(target_arch = "wasm32"))]
+pub fn ss_index_with_threshold_delta(
+    fbas: &Fbas,
+    node_id: NodeId,
+    delta: i32,
+    qi_check: bool,
+) -> Vec<Score> {
+    let mut perturbed = fbas.clone();
+    let mut quorum_set = perturbed
+        .get_quorum_set(node_id)
+        .unwrap_or_else(|| panic!("Node {node_id} has no quorum set"));
+    let max_threshold = quorum_set.validators.len() + quorum_set.inner_quorum_sets.len();
+    let threshold =
+        (quorum_set.threshold as i64 + delta as i64).clamp(1, max_threshold.max(1) as i64);
+    quorum_set.threshold = threshold as usize;
+    perturbed.swap_quorum_set(node_id, quorum_set);
+    rank_nodes(&perturbed, RankingAlg::PowerIndexEnum(None), qi_check)
+}
+
+/// Shapley-Shubik indices of organisations instead of nodes: the nodes of each organisation, given
+/// as a map of public key to organisation name, are merged into a single player. A coalition of
+/// organisations wins if their nodes together contain a quorum. Nodes that do not belong to any
+/// organisation are players on their own and listed under their public key. Only the top tier
+/// can contain a quorum, so players without top tier nodes get 0 and are left out of the
+/// enumeration, which goes through all 2^n coalitions of the remaining players.
+/// Returns the unrounded index of every player
+#[cfg(not(target_arch = "wasm32"))]
+pub fn organization_shapley(
+    fbas: &Fbas,
+    orgs: &HashMap<PublicKey, String>,
+    qi_check: bool,
+) -> HashMap<String, Score> {
+    let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+    let top_tier: Coalition = CooperativeGame::get_involved_nodes(fbas, qi_check)
+        .into_iter()
+        .collect();
+    let pks = to_public_keys(all_nodes.clone(), fbas);
+    // members in the top tier per player
+    let mut members: BTreeMap<String, Coalition> = BTreeMap::new();
+    for node in all_nodes {
+        let player = orgs.get(&pks[node]).unwrap_or(&pks[node]).clone();
+        let player_members = members.entry(player).or_default();
+        if top_tier.contains(node) {
+            player_members.insert(node);
+        }
+    }
+    let mut indices: HashMap<String, Score> = members
+        .iter()
+        .filter(|(_, m)| m.is_empty())
+        .map(|(player, _)| (player.clone(), Score::default()))
+        .collect();
+    let players: Vec<(String, Coalition)> =
+        members.into_iter().filter(|(_, m)| !m.is_empty()).collect();
+    let num_players = players.len();
+    assert!(
+        num_players < 32,
+        "Too many organisations to enumerate their coalitions"
+    );
+    let is_winning: Vec<bool> = (0..1usize << num_players)
+        .map(|mask| {
+            let mut nodes = Coalition::new();
+            for (i, (_, player_members)) in players.iter().enumerate() {
+                if mask & (1 << i) != 0 {
+                    nodes.union_with(player_members);
+                }
+            }
+            fbas_analyzer::contains_quorum(&nodes, fbas)
+        })
+        .collect();
+    let total_factorial = n_factorial(num_players);
+    for (i, (player, _)) in players.iter().enumerate() {
+        let index = (0..1usize << num_players)
+            .filter(|&mask| {
+                mask & (1 << i) != 0 && is_winning[mask] && !is_winning[mask & !(1 << i)]
+            })
+            .map(|mask| {
+                let coalition: Coalition =
+                    (0..num_players).filter(|j| mask & (1 << j) != 0).collect();
+                value_added_to_one_coalition(&coalition, num_players, total_factorial.clone())
+            })
+            .sum();
+        indices.insert(player.clone(), index);
+    }
+    indices
+}
+
+/// Approximates the Shapley-Shubik indices and puts bootstrapped confidence intervals on them.
+/// The scores and the intervals are derived from the same sampled permutations.
+/// Returns a list of (score, (lower bound, upper bound)) with index 0 = node 0's score
+pub fn approx_power_indices_with_ci(
+    fbas: &Fbas,
+    num_samples: usize,
+    confidence: f64,
+    resamples: usize,
+    seed: Option<u64>,
+    qi_check: bool,
+) -> Vec<(Score, (f64, f64))> {
+    let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+    let game = CooperativeGame::init_from_fbas(&all_nodes, fbas);
+    let contributions = game.compute_approx_ss_contributions(num_samples, seed, qi_check);
+    let intervals = bootstrap_ci(&contributions, confidence, resamples, seed);
+    contributions
+        .iter()
+        .zip(intervals)
+        .map(|(samples, ci)| {
+            let hits: usize = samples.iter().map(|&c| c as usize).sum();
+            (round_to_three_places(hits as f64 / num_samples as f64), ci)
+        })
+        .collect()
+}
+
+/// Approximates the Shapley-Shubik indices along with the sample variance of every node's marginal
+/// contributions, which shows the nodes whose estimates need more samples.
+/// Returns a list of (score, variance) with index 0 = node 0's values
+pub fn compute_approx_ss_with_variance(
+    fbas: &Fbas,
+    num_samples: usize,
+    qi_check: bool,
+    seed: Option<u64>,
+) -> Vec<(Score, f64)> {
+    let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+    let game = CooperativeGame::init_from_fbas(&all_nodes, fbas);
+    game.compute_approx_ss_with_variance(num_samples, qi_check, seed)
+}
+
+/// Approximates the Shapley-Shubik indices and puts confidence intervals on them using the normal
+/// approximation, i.e. score +- z * sqrt(variance / num_samples) with the sample variance of the
+/// marginal contributions, clamped to [0, 1]. Cheaper than bootstrapping as nothing is resampled.
+/// Returns a list of (score, (lower bound, upper bound)) with index 0 = node 0's score
+pub fn approx_power_indices_with_normal_ci(
+    fbas: &Fbas,
+    num_samples: usize,
+    confidence: f64,
+    seed: Option<u64>,
+    qi_check: bool,
+) -> Vec<(Score, (f64, f64))> {
+    assert!(
+        confidence > 0.0 && confidence < 1.0,
+        "Confidence level must be in (0, 1)"
+    );
+    let z = standard_normal_quantile(1.0 - (1.0 - confidence) / 2.0);
+    compute_approx_ss_with_variance(fbas, num_samples, qi_check, seed)
+        .into_iter()
+        .map(|(score, variance)| {
+            let margin = z * (variance / num_samples as f64).sqrt();
+            (
+                score,
+                ((score - margin).max(0.0), (score + margin).min(1.0)),
             )
+        })
+        .collect()
+}
+
+/// Approximates the Shapley-Shubik indices without having to guess the number of samples: the
+/// samples are doubled, reusing the earlier ones, until the widest normal confidence interval at
+/// the passed confidence level is at most target_ci_width, or max_samples is reached.
+/// Returns the scores with index 0 = node 0's score along with the number of samples used
+pub fn compute_approx_ss_adaptive(
+    fbas: &Fbas,
+    target_ci_width: f64,
+    confidence: f64,
+    max_samples: usize,
+    qi_check: bool,
+    seed: Option<u64>,
+) -> (Vec<Score>, usize) {
+    let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+    let mut game = CooperativeGame::init_from_fbas(&all_nodes, fbas);
+    game.config.seed = seed;
+    game.compute_approx_ss_adaptive(target_ci_width, confidence, max_samples, qi_check)
+}
+
+/// Computes the exact Shapley-Shubik indices of a game in which the forced nodes are part of every
+/// coalition, e.g. to model a mandatory hub validator. The forced nodes are not players and get a
+/// score of 0. Returns a list of scores with index 0 = node 0's score
+#[cfg(not(target_arch = "wasm32"))]
+pub fn compute_exact_ss_with_forced(fbas: &Fbas, forced: &[NodeId], qi_check: bool) -> Vec<Score> {
+    let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+    let game = CooperativeGame::init_from_fbas_with_forced(&all_nodes, forced, fbas);
+    let mut scores = vec![Score::default(); all_nodes.len()];
+    for (&player, score) in game
+        .players
+        .iter()
+        .zip(game.compute_exact_ss_power_index_for_game(qi_check))
+    {
+        scores[player] = score;
+    }
+    scores
+}
+
+/// Estimates the probability that the node is pivotal in a uniformly random ordering of the
+/// players, i.e. its Shapley-Shubik index, from the passed number of sampled orderings.
+/// Returns (estimate, standard error of the estimate). Unlike the approximated scores, the
+/// estimate is not rounded.
+pub fn pivotal_probability(
+    fbas: &Fbas,
+    node: NodeId,
+    samples: usize,
+    qi_check: bool,
+    seed: Option<u64>,
+) -> (f64, f64) {
+    let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+    assert!(node < all_nodes.len(), "Node must be part of the FBAS");
+    assert!(samples > 0, "At least one ordering must be sampled");
+    let game = CooperativeGame::init_from_fbas(&all_nodes, fbas);
+    let contributions = &game.compute_approx_ss_contributions(samples, seed, qi_check)[node];
+    let hits: usize = contributions.iter().map(|&c| c as usize).sum();
+    let estimate = hits as f64 / samples as f64;
+    let variance = if samples < 2 {
+        0.0
+    } else {
+        contributions
+            .iter()
+            .map(|&c| (c as f64 - estimate).powi(2))
+            .sum::<f64>()
+            / (samples - 1) as f64
+    };
+    (estimate, (variance / samples as f64).sqrt())
+}
+
+/// Finds how many samples the approximation needs until the ranking induced by its scores matches
+/// the one of the exact Shapley-Shubik indices. The number of samples is doubled, starting at 1,
+/// until the orders match; max_samples is tried last. Returns None if the orders still differ at
+/// max_samples. Nodes with equal exact scores may be ordered arbitrarily by the approximation.
+/// Note that this computes the exact indices, which is exponential in the size of the top tier.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn samples_to_match_exact_order(
+    fbas: &Fbas,
+    qi_check: bool,
+    seed: Option<u64>,
+    max_samples: usize,
+) -> Option<usize> {
+    let exact = rank_nodes(fbas, RankingAlg::PowerIndexEnum(None), qi_check);
+    let config = RankConfig {
+        seed,
+        ..Default::default()
+    };
+    let mut samples = 1;
+    loop {
+        let samples_to_try = samples.min(max_samples);
+        let approx = rank_nodes_with_config(
+            fbas,
+            RankingAlg::PowerIndexApprox(samples_to_try, None),
+            qi_check,
+            config,
+        );
+        if preserves_order(&exact, &approx) {
+            return Some(samples_to_try);
         }
-        RankingAlg::NodeRank => compute_node_rank_for_fbas(&all_nodes, fbas, qi_check),
+        if samples_to_try >= max_samples {
+            return None;
+        }
+        samples *= 2;
     }
 }
 
+/// True if every node scored strictly higher than another one in `reference` is also scored
+/// strictly higher in `scores`, i.e. the approximation recovered the order of the exact indices.
+/// Nodes with equal scores in `reference` may be ordered arbitrarily.
+pub fn preserves_order(reference: &[Score], scores: &[Score]) -> bool {
+    reference.iter().enumerate().all(|(i, &ref_i)| {
+        reference
+            .iter()
+            .enumerate()
+            .filter(|&(_, &ref_j)| ref_i > ref_j)
+            .all(|(j, _)| scores[i] > scores[j])
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use approx::*;
     use std::path::Path;
 
+    fn single_node_fbas() -> Fbas {
+        let mut fbas = Fbas::new();
+        fbas.add_generic_node(fbas_analyzer::QuorumSet::new(vec![0], vec![], 1));
+        fbas
+    }
+
+    #[test]
+    fn empty_fbas_yields_empty_rankings() {
+        let fbas = Fbas::new();
+        for alg in [
+            RankingAlg::NodeRank,
+            RankingAlg::PowerIndexEnum(None),
+            RankingAlg::PowerIndexEnum(Some(vec![])),
+            RankingAlg::PowerIndexApprox(10, None),
+            RankingAlg::BanzhafIndexEnum(None),
+        ] {
+            assert!(rank_nodes(&fbas, alg.clone(), true).is_empty(), "{alg:?}");
+        }
+        assert!(compute_banzhaf_absolute(&fbas, None, true).is_empty());
+        assert!(swing_counts(&fbas, true).is_empty());
+        assert!(fragility_analysis(&fbas, RankingAlg::NodeRank, true).is_empty());
+    }
+
+    #[test]
+    fn single_node_gets_all_the_power() {
+        let fbas = single_node_fbas();
+        for alg in [
+            RankingAlg::NodeRank,
+            RankingAlg::PowerIndexEnum(None),
+            RankingAlg::PowerIndexEnum(Some(vec![0])),
+            RankingAlg::PowerIndexApprox(10, None),
+            RankingAlg::BanzhafIndexEnum(None),
+        ] {
+            assert_eq!(vec![1.0], rank_nodes(&fbas, alg.clone(), true), "{alg:?}");
+        }
+    }
+
+    #[test]
+    fn smallest_critical_coalition_in_paper_example() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let expected: Coalition = [0, 1, 2].into_iter().collect();
+        assert_eq!(Some(expected), smallest_critical_coalition(&fbas, 0, true));
+        let coalition = smallest_critical_coalition(&fbas, 3, true).unwrap();
+        assert_eq!(3, coalition.len());
+        assert!(coalition.contains(3));
+    }
+
+    #[test]
+    fn lowering_threshold_shifts_power() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let unchanged = ss_index_with_threshold_delta(&fbas, 0, 0, true);
+        assert_eq!(
+            rank_nodes(&fbas, RankingAlg::PowerIndexEnum(None), true),
+            unchanged
+        );
+        // with a threshold of 1, node 0 forms a quorum on its own
+        let lowered = ss_index_with_threshold_delta(&fbas, 0, -2, true);
+        assert_ne!(unchanged, lowered);
+        assert_eq!(vec![1.0, 0.0, 0.0, 0.0, 0.0], lowered);
+        assert_eq!(lowered, ss_index_with_threshold_delta(&fbas, 0, -10, true));
+        assert_eq!(Some(3), fbas.get_quorum_set(0).map(|qset| qset.threshold));
+    }
+
+    #[test]
+    fn dummy_has_no_critical_coalition() {
+        let mut fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
+        let dummy = fbas.add_generic_node(fbas_analyzer::QuorumSet::new(vec![0, 1, 2], vec![], 2));
+        assert_eq!(None, smallest_critical_coalition(&fbas, dummy, true));
+    }
+
+    #[test]
+    fn normal_intervals_contain_exact_power_indices() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let exact = rank_nodes(&fbas, RankingAlg::PowerIndexEnum(None), true);
+        let actual = approx_power_indices_with_normal_ci(&fbas, 2000, 0.99, Some(3), true);
+        for (node, (score, (low, high))) in actual.into_iter().enumerate() {
+            assert!(low <= score && score <= high);
+            assert!(low <= exact[node] && exact[node] <= high, "{node}");
+        }
+    }
+
+    #[test]
+    fn few_samples_recover_exact_order_of_paper_example() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let samples = samples_to_match_exact_order(&fbas, true, Some(1), 1000);
+        assert!(samples.is_some_and(|s| s <= 64), "{samples:?}");
+    }
+
+    #[test]
+    fn preserves_order_ignores_ties_in_reference() {
+        let reference = [0.5, 0.25, 0.25];
+        assert!(preserves_order(&reference, &[0.4, 0.35, 0.25]));
+        assert!(!preserves_order(&reference, &[0.3, 0.35, 0.35]));
+        assert!(!preserves_order(&reference, &[0.3, 0.3, 0.3]));
+    }
+
+    #[test]
+    fn order_of_symmetric_nodes_is_recovered_with_any_number_of_samples() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
+        let exact = rank_nodes(&fbas, RankingAlg::PowerIndexEnum(None), true);
+        for samples in [1, 10, 100, 1000] {
+            let approx = rank_nodes(&fbas, RankingAlg::PowerIndexApprox(samples, None), true);
+            assert!(preserves_order(&exact, &approx), "{samples} samples");
+        }
+    }
+
     #[test]
     fn rank_nodes_with_noderank() {
         let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
@@ -55,8 +617,8 @@ mod tests {
     fn rank_nodes_with_approx_index() {
         let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
         let qi_check = false;
-        let actual = rank_nodes(&fbas, RankingAlg::PowerIndexApprox(100), qi_check);
-        let expected = vec![0.333, 0.333, 0.333];
+        let actual = rank_nodes(&fbas, RankingAlg::PowerIndexApprox(100, None), qi_check);
+        let expected = [0.333, 0.333, 0.333];
         for i in 0..expected.len() {
             assert_abs_diff_eq!(expected[i], actual[i], epsilon = 0.2f64);
         }
@@ -67,9 +629,227 @@ mod tests {
         let qi_check = true;
         let top_tier = CooperativeGame::get_involved_nodes(&fbas, qi_check);
         let actual = rank_nodes(&fbas, RankingAlg::PowerIndexEnum(Some(top_tier)), false);
-        let expected = vec![0.333, 0.333, 0.333];
+        let expected = [0.333, 0.333, 0.333];
         for i in 0..expected.len() {
             assert_abs_diff_eq!(expected[i], actual[i], epsilon = 0.2f64);
         }
     }
+    #[test]
+    fn memoized_and_plain_runs_agree() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let memoized = RankConfig {
+            memoize_quorums: true,
+            ..Default::default()
+        };
+        let plain = rank_nodes(&fbas, RankingAlg::PowerIndexEnum(None), true);
+        let cached =
+            rank_nodes_with_config(&fbas, RankingAlg::PowerIndexEnum(None), true, memoized);
+        assert_eq!(plain, cached);
+
+        let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+        let mut game = CooperativeGame::init_from_fbas(&all_nodes, &fbas);
+        let plain = game.compute_approx_ss_contributions(200, Some(5), true);
+        game.config = memoized;
+        let cached = game.compute_approx_ss_contributions(200, Some(5), true);
+        assert_eq!(plain, cached);
+    }
+
+    #[test]
+    fn approx_index_lies_in_its_ci() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
+        let actual = approx_power_indices_with_ci(&fbas, 500, 0.95, 200, Some(3), false);
+        assert_eq!(actual.len(), 3);
+        for (score, (low, high)) in actual {
+            assert!(low <= score + 0.001 && score <= high);
+        }
+    }
+
+    #[test]
+    fn time_series_aligns_scores_by_public_key() {
+        let snapshots: Vec<(String, Fbas)> = ["2023-01-01", "2023-02-01"]
+            .iter()
+            .map(|date| {
+                let path = format!("test_data/series/{date}.json");
+                (date.to_string(), Fbas::from_json_file(Path::new(&path)))
+            })
+            .collect();
+        let actual = rank_time_series(&snapshots, RankingAlg::PowerIndexEnum(None), true);
+        let expected: Vec<(PublicKey, Vec<(String, Score)>)> = vec![
+            (
+                "node0".into(),
+                vec![("2023-01-01".into(), 0.333), ("2023-02-01".into(), 0.5)],
+            ),
+            (
+                "node1".into(),
+                vec![("2023-01-01".into(), 0.333), ("2023-02-01".into(), 0.5)],
+            ),
+            (
+                "node2".into(),
+                vec![("2023-01-01".into(), 0.333), ("2023-02-01".into(), 0.0)],
+            ),
+        ];
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn veto_players_are_critical_in_grand_coalition() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        assert!(is_critical_in_grand_coalition(&fbas, 0, true));
+        assert!(!is_critical_in_grand_coalition(&fbas, 1, true));
+        let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
+        for node in 0..3 {
+            assert!(!is_critical_in_grand_coalition(&fbas, node, true));
+        }
+    }
+
+    #[test]
+    fn fragility_analysis_ranks_sub_fbas() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
+        let qi_check = true;
+        let actual = fragility_analysis(
+            &fbas,
+            RankingAlg::PowerIndexEnum(Some(vec![0, 1, 2])),
+            qi_check,
+        );
+        let expected: Vec<(NodeId, Vec<Score>)> =
+            (0..3).map(|node| (node, vec![0.5, 0.5])).collect();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn approx_power_index_with_precomputed_top_tier() {
+        let input = r#"[
+            {
+                "publicKey": "node0",
+                "quorumSet": { "threshold": 1, "validators": ["node0"] }
+            },
+            {
+                "publicKey": "node1",
+                "quorumSet": { "threshold": 1, "validators": ["node0"] }
+            },
+            {
+                "publicKey": "node2",
+                "quorumSet": { "threshold": 1, "validators": ["node0"] }
+            }
+        ]"#;
+        let fbas = Fbas::from_json_str(input);
+        let qi_check = true;
+        let computed = rank_nodes(&fbas, RankingAlg::PowerIndexApprox(50, None), qi_check);
+        let passed = rank_nodes(
+            &fbas,
+            RankingAlg::PowerIndexApprox(50, Some(vec![0])),
+            qi_check,
+        );
+        assert_eq!(vec![1.0, 0.0, 0.0], computed);
+        assert_eq!(computed, passed);
+
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let passed = rank_nodes(
+            &fbas,
+            RankingAlg::PowerIndexApprox(1000, Some(vec![0, 1, 2, 3, 4])),
+            qi_check,
+        );
+        let exact = rank_nodes(&fbas, RankingAlg::PowerIndexEnum(None), qi_check);
+        for (e, p) in exact.iter().zip(passed) {
+            assert_abs_diff_eq!(e, &p, epsilon = 0.1);
+        }
+    }
+
+    #[test]
+    fn wrong_top_tier_is_rejected_in_strict_mode() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
+        let alg = RankingAlg::PowerIndexEnum(Some(vec![0, 1]));
+        let expected = Err(RankingError::TopTierMismatch {
+            expected: vec![0, 1, 2],
+            actual: vec![0, 1],
+        });
+        let actual = try_rank_nodes_with_config(&fbas, alg.clone(), true, RankConfig::default());
+        assert_eq!(expected, actual);
+        let lenient = RankConfig {
+            strict: false,
+            ..Default::default()
+        };
+        assert!(try_rank_nodes_with_config(&fbas, alg, true, lenient).is_ok());
+    }
+
+    #[test]
+    fn correct_top_tier_is_accepted_in_strict_mode() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
+        let alg = RankingAlg::BanzhafIndexEnum(Some(vec![2, 0, 1]));
+        let actual = try_rank_nodes_with_config(&fbas, alg, true, RankConfig::default());
+        assert_eq!(Ok(vec![0.333, 0.333, 0.333]), actual);
+    }
+
+    #[test]
+    fn pivotal_probability_in_trivial_fbas() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
+        for node in 0..3 {
+            let (estimate, std_error) = pivotal_probability(&fbas, node, 1000, true, Some(3));
+            assert_abs_diff_eq!(1.0 / 3.0, estimate, epsilon = 0.05);
+            assert!(std_error > 0.0 && std_error < 0.02);
+        }
+    }
+
+    #[test]
+    fn adaptive_approximation_doubles_samples_for_tighter_target() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let max_samples = 100_000;
+        let (loose, loose_samples) =
+            compute_approx_ss_adaptive(&fbas, 0.2, 0.95, max_samples, true, Some(1));
+        assert!(loose_samples < max_samples / 10);
+        assert_eq!(5, loose.len());
+        let (_, tight_samples) =
+            compute_approx_ss_adaptive(&fbas, 0.1, 0.95, max_samples, true, Some(1));
+        assert!(tight_samples > loose_samples);
+        let (_, capped_samples) = compute_approx_ss_adaptive(&fbas, 1e-6, 0.95, 500, true, Some(1));
+        assert_eq!(500, capped_samples);
+    }
+
+    #[test]
+    fn organization_shapley_of_two_orgs_sums_up_to_one() {
+        // minimal quorums {0, 1, 2} and {0, 3, 4}: neither org contains a quorum on its own
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let pks = to_public_keys(0..5, &fbas);
+        let orgs: HashMap<PublicKey, String> = pks
+            .into_iter()
+            .enumerate()
+            .map(|(node, pk)| (pk, String::from(if node < 2 { "A" } else { "B" })))
+            .collect();
+        let actual = organization_shapley(&fbas, &orgs, true);
+        assert_eq!(2, actual.len());
+        assert_abs_diff_eq!(1.0, actual.values().sum::<Score>(), epsilon = 1e-9);
+        assert_abs_diff_eq!(0.5, actual["A"], epsilon = 1e-9);
+    }
+
+    #[test]
+    fn organization_shapley_lists_unaffiliated_nodes_by_public_key() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let pks = to_public_keys(0..5, &fbas);
+        // {0, 1, 2} contains a quorum, so A is a dictator
+        let orgs: HashMap<PublicKey, String> = pks[..3]
+            .iter()
+            .map(|pk| (pk.clone(), String::from("A")))
+            .collect();
+        let actual = organization_shapley(&fbas, &orgs, true);
+        assert_eq!(3, actual.len());
+        assert_abs_diff_eq!(1.0, actual["A"], epsilon = 1e-9);
+        assert_abs_diff_eq!(0.0, actual[&pks[3]], epsilon = 1e-9);
+    }
+
+    #[test]
+    fn contribution_by_size_sums_up_to_index() {
+        // node 0 is in both minimal quorums {0, 1, 2} and {0, 3, 4}, so it is critical for every
+        // winning coalition: two of size 3, four of size 4 and the grand coalition
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let actual = ss_contribution_by_size(&fbas, 0, true);
+        assert_eq!(
+            vec![3, 4, 5],
+            actual.keys().copied().collect::<Vec<usize>>()
+        );
+        assert_abs_diff_eq!(2.0 / 30.0, actual[&3], epsilon = 1e-9);
+        assert_abs_diff_eq!(4.0 / 20.0, actual[&4], epsilon = 1e-9);
+        assert_abs_diff_eq!(1.0 / 5.0, actual[&5], epsilon = 1e-9);
+        let index = rank_nodes(&fbas, RankingAlg::PowerIndexEnum(None), true)[0];
+        assert_abs_diff_eq!(index, actual.values().sum::<Score>(), epsilon = 0.001);
+    }
 }
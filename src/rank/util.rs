@@ -1,7 +1,10 @@
+use crate::{Coalition, PublicKey, RankingError, Score};
 use fbas_analyzer::*;
-use rug::Integer;
+#[cfg(not(target_arch = "wasm32"))]
+use rug::{Integer, Rational};
 use sha3::{Digest, Sha3_256};
 use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
 
 /// Iterates through all quorum sets and
 /// Returns a map of quorum set hashes and a list of nodes that created that quorum set
@@ -9,12 +12,7 @@ pub(crate) fn map_quorum_sets_to_generators(fbas: &Fbas) -> HashMap<String, Hash
     let mut generators: HashMap<String, HashSet<NodeId>> = HashMap::default();
     let nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
     for v in nodes.iter() {
-        let quorum_set = if let Some(qset) = fbas.get_quorum_set(*v) {
-            qset
-        } else {
-            QuorumSet::new_empty()
-        };
-        let quorum_set_hash = hex::encode(Sha3_256::digest(quorum_set.into_id_string().as_bytes()));
+        let quorum_set_hash = quorum_set_hash(fbas, *v);
         if let Some(hash) = generators.get_mut(&quorum_set_hash) {
             hash.insert(*v);
         } else {
@@ -24,6 +22,172 @@ pub(crate) fn map_quorum_sets_to_generators(fbas: &Fbas) -> HashMap<String, Hash
     generators
 }
 
+/// Sha3-256 hex of the node's quorum set; nodes without a quorum set get the hash of an empty one
+pub(crate) fn quorum_set_hash(fbas: &Fbas, node: NodeId) -> String {
+    let quorum_set = if let Some(qset) = fbas.get_quorum_set(node) {
+        qset
+    } else {
+        QuorumSet::new_empty()
+    };
+    hex::encode(Sha3_256::digest(quorum_set.into_id_string().as_bytes()))
+}
+
+/// Returns the quorum set hash of every node with index 0 = node 0's hash
+pub fn quorum_set_hashes(fbas: &Fbas) -> Vec<String> {
+    (0..fbas.all_nodes().len())
+        .map(|v| quorum_set_hash(fbas, v))
+        .collect()
+}
+
+/// Seed derived from the first 8 bytes of the Sha3-256 of the FBAS's JSON. The same FBAS always
+/// yields the same seed, so approximations of it can be replayed without passing a seed.
+pub fn fbas_seed(fbas: &Fbas) -> u64 {
+    let digest = Sha3_256::digest(fbas.to_json_string().as_bytes());
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    u64::from_le_bytes(bytes)
+}
+
+/// Returns the nodes whose quorum set is missing or has no validators and no inner quorum sets
+pub fn nodes_with_empty_quorum_sets(fbas: &Fbas) -> Vec<NodeId> {
+    (0..fbas.all_nodes().len())
+        .filter(|&v| {
+            fbas.get_quorum_set(v)
+                .is_none_or(|qset| qset.validators.is_empty() && qset.inner_quorum_sets.is_empty())
+        })
+        .collect()
+}
+
+/// Returns the nodes whose quorum set only contains the node itself
+pub fn self_trusting_nodes(fbas: &Fbas) -> Vec<NodeId> {
+    (0..fbas.all_nodes().len())
+        .filter(|&v| {
+            fbas.get_quorum_set(v)
+                .is_some_and(|qset| qset.validators == vec![v] && qset.inner_quorum_sets.is_empty())
+        })
+        .collect()
+}
+
+/// Returns the nodes that are not part of any minimal quorum. They can never be critical to a
+/// coalition and thus always have zero power.
+pub fn unreachable_nodes(fbas: &Fbas, min_qs: &[NodeIdSet]) -> Vec<NodeId> {
+    let involved = involved_nodes(min_qs);
+    (0..fbas.all_nodes().len())
+        .filter(|&v| !involved.contains(v))
+        .collect()
+}
+
+/// Returns the nodes whose quorum sets, including inner quorum sets, contain the node
+pub fn trusting_nodes(fbas: &Fbas, node: NodeId) -> Vec<NodeId> {
+    (0..fbas.number_of_nodes())
+        .filter(|&v| {
+            fbas.get_quorum_set(v)
+                .is_some_and(|qset| qset.contained_nodes().contains(node))
+        })
+        .collect()
+}
+
+/// Returns the top tier, i.e. the nodes that are part of at least one minimal quorum, sorted by
+/// NodeId. Unlike the rankings, this never asserts quorum intersection.
+pub fn compute_top_tier(fbas: &Fbas) -> Vec<NodeId> {
+    involved_nodes(&find_minimal_quorums(fbas))
+        .into_iter()
+        .collect()
+}
+
+/// Returns two disjoint minimal quorums proving that the FBAS lacks quorum intersection, or None
+/// if all of its minimal quorums, as found by find_minimal_quorums, intersect
+pub fn quorum_intersection_witness(min_qs: &[NodeIdSet]) -> Option<(Coalition, Coalition)> {
+    min_qs.iter().enumerate().find_map(|(i, a)| {
+        min_qs[i + 1..]
+            .iter()
+            .find(|b| a.is_disjoint(b))
+            .map(|b| (a.clone(), b.clone()))
+    })
+}
+
+/// Checks that top_tier, in any order, equals the top tier computed by compute_top_tier
+pub fn validate_top_tier(fbas: &Fbas, top_tier: &[NodeId]) -> Result<(), RankingError> {
+    let expected = compute_top_tier(fbas);
+    let mut actual = top_tier.to_vec();
+    actual.sort_unstable();
+    if expected == actual {
+        Ok(())
+    } else {
+        Err(RankingError::TopTierMismatch { expected, actual })
+    }
+}
+
+/// Returns the largest strongly connected component of the trust graph, sorted by NodeId. The
+/// trust graph has an edge from every node to every node in its quorum set, including the nodes
+/// of inner quorum sets; a strongly connected component is a maximal set of nodes that can all
+/// reach each other via these edges. If several components are equally large, the one containing
+/// the lowest NodeId is returned.
+pub fn largest_strongly_connected_component(fbas: &Fbas) -> Vec<NodeId> {
+    let mut sccs: Vec<Vec<NodeId>> =
+        preprocessing::partition_into_strongly_connected_components(&fbas.all_nodes(), fbas)
+            .into_iter()
+            .map(|scc| scc.into_iter().collect())
+            .collect();
+    sccs.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+    sccs.into_iter().next().unwrap_or_default()
+}
+
+/// Returns the minimal quorums with their members' public keys, each sorted by NodeId
+pub fn minimal_quorums_with_pks(fbas: &Fbas) -> Vec<Vec<PublicKey>> {
+    find_minimal_quorums(fbas)
+        .into_iter()
+        .map(|quorum| to_public_keys(&quorum, fbas))
+        .collect()
+}
+
+/// Returns, per node, the indices of the minimal quorums (in the order of find_minimal_quorums)
+/// that contain it, with index 0 = node 0's list
+pub fn minimal_quorum_membership(fbas: &Fbas) -> Vec<Vec<usize>> {
+    let mut membership = vec![Vec::new(); fbas.number_of_nodes()];
+    for (i, quorum) in find_minimal_quorums(fbas).iter().enumerate() {
+        for node in quorum.iter() {
+            membership[node].push(i);
+        }
+    }
+    membership
+}
+
+/// Unites the nodes of both FBASs by public key: the nodes of a keep their IDs, the nodes of b
+/// that are not in a are appended in their order. Quorum sets refer to public keys, so they are
+/// remapped to the new IDs. If both FBASs contain a public key, the node of a is kept.
+pub fn merge_fbas(a: &Fbas, b: &Fbas) -> Fbas {
+    let nodes_of = |fbas: &Fbas| -> Vec<serde_json::Value> {
+        match serde_json::from_str(&fbas.to_json_string()).expect("Error serialising the FBAS") {
+            serde_json::Value::Array(nodes) => nodes,
+            _ => unreachable!("An FBAS is serialised as a list of nodes"),
+        }
+    };
+    let mut merged = nodes_of(a);
+    for node in nodes_of(b) {
+        let public_key = node["publicKey"].as_str().unwrap_or_default();
+        if a.get_node_id(public_key).is_none() {
+            merged.push(node);
+        }
+    }
+    Fbas::from_json_str(&serde_json::Value::Array(merged).to_string())
+}
+
+/// Groups the nodes that have the same quorum set, i.e. the same quorum set hash in
+/// map_quorum_sets_to_generators. Every group and the list of groups are sorted by NodeId.
+pub fn symmetric_node_groups(fbas: &Fbas) -> Vec<Vec<NodeId>> {
+    let mut groups: Vec<Vec<NodeId>> = map_quorum_sets_to_generators(fbas)
+        .into_values()
+        .map(|generators| {
+            let mut group: Vec<NodeId> = generators.into_iter().collect();
+            group.sort_unstable();
+            group
+        })
+        .collect();
+    groups.sort_unstable();
+    groups
+}
+
 /// Returns all quorum sets in the FBAS in which the node is included in the outer quorum set
 pub(crate) fn all_quorum_sets_containing_node(node_id: NodeId, fbas: &Fbas) -> HashSet<QuorumSet> {
     let mut qsets_containting_node: HashSet<QuorumSet> = HashSet::default();
@@ -67,6 +231,29 @@ pub(crate) fn node_weight_in_quorum_set(node_id: NodeId, quorum_set: &QuorumSet)
     }
 }
 
+// T/|Q| as an exact fraction
+#[cfg(not(target_arch = "wasm32"))]
+fn qset_weight_rational(quorum_set: &QuorumSet) -> Rational {
+    Rational::from((quorum_set.threshold, quorum_set.contained_nodes().len()))
+}
+
+/// Same as node_weight_in_quorum_set but without rounding errors
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn node_weight_in_quorum_set_rational(
+    node_id: NodeId,
+    quorum_set: &QuorumSet,
+) -> Rational {
+    if nodes_nesting_depth(quorum_set, node_id) == 0 {
+        Rational::from(1)
+    } else {
+        qset_weight_rational(quorum_set)
+            * node_weight_in_quorum_set_rational(
+                node_id,
+                &find_next_quorum_set_containing_node(quorum_set, node_id),
+            )
+    }
+}
+
 /// Returns the first (inner) quorum set found that the node is included in
 fn find_next_quorum_set_containing_node(quorum_set: &QuorumSet, node_id: NodeId) -> QuorumSet {
     for set in &quorum_set.inner_quorum_sets {
@@ -98,7 +285,7 @@ fn nodes_nesting_depth(quorum_set: &QuorumSet, node: NodeId) -> usize {
 }
 
 fn is_in_qset(validators: &[NodeId], node: NodeId) -> bool {
-    validators.iter().any(|&validator| validator == node)
+    validators.contains(&node)
 }
 
 fn depth_in_inner_sets(inner_quorum_set: &QuorumSet, node: NodeId) -> usize {
@@ -137,6 +324,7 @@ pub(crate) fn get_list_of_creators_for_quorum_set(
     creators
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 pub(crate) fn n_factorial(n: usize) -> Integer {
     let n = n as u128;
     if n == 0 {
@@ -149,19 +337,174 @@ pub(crate) fn n_factorial(n: usize) -> Integer {
     factorial * n
 }
 
+/// Truncates n to the passed number of decimal places
+pub(crate) fn round_to_places(n: f64, places: u32) -> f64 {
+    let multiplier = 10f64.powi(places as i32);
+    f64::trunc(n * multiplier) / multiplier
+}
+
 pub(crate) fn round_to_three_places(n: f64) -> f64 {
-    f64::trunc(n * 1000.0) / 1000.0
+    round_to_places(n, 3)
+}
+
+/// How scores are scaled before they are reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScoreScale {
+    /// Scores sum up to 1, see normalize_scores
+    Sum,
+    /// The highest score is 1, e.g. for bar charts
+    Max,
+}
+
+impl FromStr for ScoreScale {
+    type Err = &'static str;
+    fn from_str(scale: &str) -> Result<Self, Self::Err> {
+        match scale.to_lowercase().as_ref() {
+            "sum" => Ok(ScoreScale::Sum),
+            "max" => Ok(ScoreScale::Max),
+            _ => Err("Unknown scale, expected sum or max"),
+        }
+    }
+}
+
+/// Scales the scores as requested and truncates them to three decimal places like the unscaled
+/// scores. If the highest score is 0, scaling to the maximum leaves all scores at 0.
+pub fn scale_scores(scores: &[Score], scale: ScoreScale) -> Vec<Score> {
+    let scaled = match (scale, scale_divisor(scores, scale)) {
+        (_, Some(divisor)) => scores.iter().map(|s| s / divisor).collect(),
+        (ScoreScale::Sum, None) => normalize_scores(scores),
+        (ScoreScale::Max, None) => scores.to_vec(),
+    };
+    scaled.into_iter().map(round_to_three_places).collect()
+}
+
+/// Same as scale_scores for a list of (score, (lower bound, upper bound)), the bounds of each
+/// score's confidence interval are divided by the same number as the scores. If the scores sum up
+/// to 0 or are all 0, the bounds are left as they are.
+pub fn scale_scores_with_ci(
+    scores_with_ci: &[(Score, (f64, f64))],
+    scale: ScoreScale,
+) -> Vec<(Score, (f64, f64))> {
+    let scores: Vec<Score> = scores_with_ci.iter().map(|s| s.0).collect();
+    let divisor = scale_divisor(&scores, scale).unwrap_or(1.0);
+    scale_scores(&scores, scale)
+        .into_iter()
+        .zip(scores_with_ci)
+        .map(|(score, (_, (low, high)))| (score, (low / divisor, high / divisor)))
+        .collect()
+}
+
+/// The number the scores are divided by to scale them, None if it is 0
+fn scale_divisor(scores: &[Score], scale: ScoreScale) -> Option<Score> {
+    let divisor = match scale {
+        ScoreScale::Sum => scores.iter().sum(),
+        ScoreScale::Max => scores.iter().cloned().fold(0.0, Score::max),
+    };
+    (divisor != 0.0).then_some(divisor)
+}
+
+/// Divides every score by the sum of all scores so that they sum up to 1. If the scores sum up to
+/// 0, every node gets the same share.
+pub fn normalize_scores(scores: &[Score]) -> Vec<Score> {
+    let score_sum: Score = scores.iter().sum();
+    if score_sum == 0.0 {
+        vec![1.0 / scores.len() as Score; scores.len()]
+    } else {
+        scores.iter().map(|s| s / score_sum).collect()
+    }
+}
+
+/// Inverse of the standard normal CDF using Acklam's rational approximation
+/// (relative error below 1.15e-9), e.g. 1.96 for p = 0.975
+pub(crate) fn standard_normal_quantile(p: f64) -> f64 {
+    assert!(p > 0.0 && p < 1.0, "Probability must be in (0, 1)");
+    const A: [f64; 6] = [
+        -3.969683028665376e1,
+        2.209460984245205e2,
+        -2.759285104469687e2,
+        1.38357751867269e2,
+        -3.066479806614716e1,
+        2.506628277459239,
+    ];
+    const B: [f64; 5] = [
+        -5.447609879822406e1,
+        1.615858368580409e2,
+        -1.556989798598866e2,
+        6.680131188771972e1,
+        -1.328068155288572e1,
+    ];
+    const C: [f64; 6] = [
+        -7.784894002430293e-3,
+        -3.223964580411365e-1,
+        -2.400758277161838,
+        -2.549732539343734,
+        4.374664141464968,
+        2.938163982698783,
+    ];
+    const D: [f64; 4] = [
+        7.784695709041462e-3,
+        3.224671290700398e-1,
+        2.445134137142996,
+        3.754408661907416,
+    ];
+    let p_low = 0.02425;
+    if p < p_low {
+        let q = (-2.0 * p.ln()).sqrt();
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    } else if p <= 1.0 - p_low {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    } else {
+        -standard_normal_quantile(1.0 - p)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use approx::*;
     use std::path::Path;
 
+    #[test]
+    fn top_tier_is_computed_without_quorum_intersection() {
+        let trivial = Fbas::from_json_file(Path::new("test_data/trivial.json"));
+        assert_eq!(vec![0, 1, 2], compute_top_tier(&trivial));
+        // two disjoint networks lack quorum intersection
+        let paper = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let disjoint = merge_fbas(&trivial, &paper);
+        assert!(!all_intersect(&find_minimal_quorums(&disjoint)));
+        assert_eq!((0..8).collect::<Vec<NodeId>>(), compute_top_tier(&disjoint));
+    }
+
+    #[test]
+    fn merged_fbas_contains_distinct_public_keys() {
+        let trivial = Fbas::from_json_file(Path::new("test_data/trivial.json"));
+        let paper = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let merged = merge_fbas(&trivial, &paper);
+        assert_eq!(8, merged.number_of_nodes());
+        let node3 = merged.get_node_id("node3").unwrap();
+        let node4 = merged.get_node_id("node4").unwrap();
+        assert_eq!(
+            paper
+                .get_quorum_set(paper.get_node_id("node3").unwrap())
+                .map(|q| q.threshold),
+            merged.get_quorum_set(node3).map(|q| q.threshold)
+        );
+        assert!(merged
+            .get_quorum_set(node3)
+            .unwrap()
+            .contained_nodes()
+            .contains(node4));
+        assert_eq!(3, merge_fbas(&trivial, &trivial).number_of_nodes());
+    }
+
     fn flat_qset(validators: &[NodeId], threshold: usize) -> QuorumSet {
         QuorumSet {
             threshold,
-            validators: validators.iter().copied().collect(),
+            validators: validators.to_vec(),
             inner_quorum_sets: vec![],
         }
     }
@@ -271,8 +614,8 @@ mod tests {
     }
     #[test]
     fn factorial() {
-        let numbers = vec![0, 1, 3];
-        let expected = vec![1, 1, 6];
+        let numbers = [0, 1, 3];
+        let expected = [1, 1, 6];
         for (i, n) in numbers.iter().enumerate() {
             let actual = n_factorial(*n);
             assert_eq!(expected[i], actual);
@@ -280,9 +623,187 @@ mod tests {
     }
     #[test]
     fn round() {
-        let pi = 3.1415926535897932384626433832;
+        let pi = std::f64::consts::PI;
         let actual = round_to_three_places(pi);
-        let expected = 3.141;
+        let expected = 3141.0 / 1000.0;
         assert_eq!(actual, expected);
     }
+    #[test]
+    fn round_to_different_places() {
+        let n = 1.234567891;
+        assert_eq!(1.0, round_to_places(n, 0));
+        assert_eq!(1.234, round_to_places(n, 3));
+        assert_eq!(1.234567, round_to_places(n, 6));
+    }
+    #[test]
+    fn normal_quantiles() {
+        assert_abs_diff_eq!(standard_normal_quantile(0.975), 1.959964, epsilon = 1e-6);
+        assert_abs_diff_eq!(standard_normal_quantile(0.5), 0.0, epsilon = 1e-9);
+        assert_abs_diff_eq!(standard_normal_quantile(0.001), -3.090232, epsilon = 1e-6);
+    }
+    #[test]
+    fn normalized_scores_sum_up_to_one() {
+        let actual = normalize_scores(&[0.666, 0.666, 0.333, 0.0]);
+        assert_abs_diff_eq!(1.0, actual.iter().sum::<Score>(), epsilon = 1e-12);
+        assert_abs_diff_eq!(0.4, actual[0], epsilon = 1e-12);
+        assert_eq!(0.0, actual[3]);
+        assert_eq!(vec![0.25; 4], normalize_scores(&[0.0; 4]));
+    }
+    #[test]
+    fn symmetric_node_groups_share_quorum_sets() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
+        assert_eq!(vec![vec![0, 1, 2]], symmetric_node_groups(&fbas));
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let expected = vec![vec![0], vec![1, 2], vec![3, 4]];
+        assert_eq!(expected, symmetric_node_groups(&fbas));
+    }
+
+    #[test]
+    fn same_fbas_derives_same_seed_and_approximation() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
+        let reloaded = Fbas::from_json_file(Path::new("test_data/trivial.json"));
+        assert_eq!(fbas_seed(&fbas), fbas_seed(&reloaded));
+        let paper = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        assert_ne!(fbas_seed(&fbas), fbas_seed(&paper));
+        let approximate = |fbas: &Fbas| {
+            let config = crate::RankConfig {
+                seed: Some(fbas_seed(fbas)),
+                ..Default::default()
+            };
+            crate::rank_nodes_with_config(
+                fbas,
+                crate::RankingAlg::PowerIndexApprox(50, None),
+                true,
+                config,
+            )
+        };
+        assert_eq!(approximate(&fbas), approximate(&reloaded));
+    }
+
+    #[test]
+    fn detect_empty_and_self_trusting_quorum_sets() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
+        assert!(nodes_with_empty_quorum_sets(&fbas).is_empty());
+        assert!(self_trusting_nodes(&fbas).is_empty());
+        let input = r#"[
+            { "publicKey": "n0", "quorumSet": { "threshold": 1, "validators": ["n0"] } },
+            { "publicKey": "n1", "quorumSet": { "threshold": 0, "validators": [] } },
+            { "publicKey": "n2", "quorumSet": { "threshold": 1, "validators": ["n0", "n2"] } }
+        ]"#;
+        let fbas = Fbas::from_json_str(input);
+        assert_eq!(vec![1], nodes_with_empty_quorum_sets(&fbas));
+        assert_eq!(vec![0], self_trusting_nodes(&fbas));
+    }
+
+    #[test]
+    fn isolated_node_is_unreachable() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
+        assert!(unreachable_nodes(&fbas, &find_minimal_quorums(&fbas)).is_empty());
+        let input = r#"[
+            { "publicKey": "n0", "quorumSet": { "threshold": 2, "validators": ["n0", "n1"] } },
+            { "publicKey": "n1", "quorumSet": { "threshold": 2, "validators": ["n0", "n1"] } },
+            { "publicKey": "n2", "quorumSet": { "threshold": 2, "validators": ["n0", "n1"] } }
+        ]"#;
+        let fbas = Fbas::from_json_str(input);
+        assert_eq!(
+            vec![2],
+            unreachable_nodes(&fbas, &find_minimal_quorums(&fbas))
+        );
+    }
+
+    #[test]
+    fn minimal_quorums_of_paper_example_as_public_keys() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let mut actual = minimal_quorums_with_pks(&fbas);
+        actual.sort();
+        let expected = vec![
+            vec!["node0", "node1", "node2"],
+            vec!["node0", "node3", "node4"],
+        ];
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn minimal_quorum_membership_of_paper_example() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/paper_example.json"));
+        let actual = minimal_quorum_membership(&fbas);
+        assert_eq!(vec![0, 1], actual[0]);
+        assert_eq!(1, actual[1].len());
+        assert_eq!(1, actual[3].len());
+        assert_ne!(actual[1], actual[3]);
+    }
+
+    #[test]
+    fn largest_strongly_connected_component_of_two_clusters() {
+        let mut fbas = Fbas::new();
+        for _ in 0..4 {
+            fbas.add_generic_node(QuorumSet::new(vec![0, 1, 2, 3], vec![], 3));
+        }
+        for _ in 0..2 {
+            fbas.add_generic_node(QuorumSet::new(vec![4, 5], vec![], 2));
+        }
+        assert_eq!(
+            vec![0, 1, 2, 3],
+            largest_strongly_connected_component(&fbas)
+        );
+    }
+
+    #[test]
+    fn disjoint_quorums_witness_lack_of_quorum_intersection() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/two_clusters.json"));
+        let (a, b) =
+            quorum_intersection_witness(&find_minimal_quorums(&fbas)).expect("No witness found");
+        assert!(a.is_disjoint(&b));
+        assert!(contains_quorum(&a, &fbas) && contains_quorum(&b, &fbas));
+        let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
+        assert_eq!(
+            None,
+            quorum_intersection_witness(&find_minimal_quorums(&fbas))
+        );
+    }
+
+    #[test]
+    fn leaf_node_is_trusted_by_no_one() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/leaf.json"));
+        assert_eq!(vec![0, 1, 2, 3], trusting_nodes(&fbas, 0));
+        assert!(trusting_nodes(&fbas, 3).is_empty());
+    }
+
+    #[test]
+    fn scores_scaled_to_maximum() {
+        assert_eq!(
+            vec![1.0, 0.5, 0.0],
+            scale_scores(&[0.4, 0.2, 0.0], ScoreScale::Max)
+        );
+        assert_eq!(vec![0.0, 0.0], scale_scores(&[0.0, 0.0], ScoreScale::Max));
+        assert_eq!(vec![0.75, 0.25], scale_scores(&[3.0, 1.0], ScoreScale::Sum));
+        assert_eq!(Ok(ScoreScale::Max), "MAX".parse());
+    }
+
+    #[test]
+    fn scaled_scores_are_truncated() {
+        assert_eq!(
+            vec![1.0, 0.292],
+            scale_scores(&[0.468, 0.137], ScoreScale::Max)
+        );
+        assert_eq!(vec![0.333; 3], scale_scores(&[0.0; 3], ScoreScale::Sum));
+    }
+
+    #[test]
+    fn confidence_intervals_are_scaled_with_the_scores() {
+        let actual = scale_scores_with_ci(&[(0.4, (0.3, 0.5)), (0.2, (0.1, 0.3))], ScoreScale::Max);
+        assert_eq!(
+            vec![1.0, 0.5],
+            actual.iter().map(|s| s.0).collect::<Vec<_>>()
+        );
+        assert_abs_diff_eq!(0.75, actual[0].1 .0);
+        assert_abs_diff_eq!(1.25, actual[0].1 .1);
+        assert_abs_diff_eq!(0.25, actual[1].1 .0);
+        assert_abs_diff_eq!(0.75, actual[1].1 .1);
+        let zeros = [(0.0, (0.0, 0.1)); 2];
+        assert_eq!(
+            zeros.to_vec(),
+            scale_scores_with_ci(&zeros, ScoreScale::Max)
+        );
+    }
 }
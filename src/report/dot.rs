@@ -0,0 +1,63 @@
+use crate::Score;
+use fbas_analyzer::{to_public_keys, Fbas, NodeId};
+use std::{fs, io, path::Path};
+
+/// Width in inches of a node with a score of 1 in the DOT graph
+const DOT_WIDTH_PER_SCORE: f64 = 2.0;
+
+/// Width in inches of nodes whose scores are too small to be drawn proportionally
+const DOT_MIN_WIDTH: f64 = 0.1;
+
+/// Renders the trust graph as a GraphViz DOT digraph. Every node is labelled with its public key
+/// and score and drawn with a width proportional to its score. There is an edge from every node
+/// to each node in its quorum set, including the nodes of inner quorum sets. The scores are
+/// indexed by NodeId.
+pub fn dot_graph(fbas: &Fbas, scores: &[Score]) -> String {
+    let all_nodes: Vec<NodeId> = (0..fbas.number_of_nodes()).collect();
+    assert_eq!(all_nodes.len(), scores.len(), "Expected one score per node");
+    let pks = to_public_keys(all_nodes.clone(), fbas);
+    let mut dot = String::from("digraph fbas {\n    node [shape=circle, fixedsize=true];\n");
+    for &node in all_nodes.iter() {
+        let width = (scores[node] * DOT_WIDTH_PER_SCORE).max(DOT_MIN_WIDTH);
+        dot.push_str(&format!(
+            "    {node} [label=\"{}\\n{}\", width={width:.3}];\n",
+            pks[node], scores[node]
+        ));
+    }
+    for &node in all_nodes.iter() {
+        if let Some(qset) = fbas.get_quorum_set(node) {
+            for trusted in qset.contained_nodes().iter() {
+                dot.push_str(&format!("    {node} -> {trusted};\n"));
+            }
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+/// Writes the DOT graph of dot_graph to the file at path
+pub fn export_dot(fbas: &Fbas, scores: &[Score], path: &Path) -> io::Result<()> {
+    fs::write(path, dot_graph(fbas, scores))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    #[test]
+    fn dot_graph_of_trivial_fbas() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
+        let scores = rank_nodes(&fbas, RankingAlg::PowerIndexEnum(None), true);
+        let actual = dot_graph(&fbas, &scores);
+        let node_lines: Vec<&str> = actual.lines().filter(|l| l.contains("label=")).collect();
+        assert_eq!(3, node_lines.len());
+        assert!(node_lines[0].starts_with(
+            "    0 [label=\"GCGB2S2KGYARPVIA37HYZXVRM2YZUEXA6S33ZU5BUDC6THSB62LZSTYH\\n0.333\""
+        ));
+        assert!(node_lines
+            .iter()
+            .all(|l| l.contains("\\n0.333\", width=0.666]")));
+        assert_eq!(9, actual.lines().filter(|l| l.contains(" -> ")).count());
+    }
+}
@@ -0,0 +1,129 @@
+use crate::{NodeRankingRecord, NodeRewardRecord};
+
+/// A record that can be written as one row of a Markdown table
+pub trait MarkdownRecord {
+    /// Column names. Optional columns are only included if the record has a value for them.
+    fn markdown_header(&self) -> Vec<&'static str>;
+    /// Cells in the order of the header. Scores and rewards are written with `precision`
+    /// decimal places.
+    fn markdown_cells(&self, precision: usize) -> Vec<String>;
+}
+
+impl MarkdownRecord for NodeRankingRecord {
+    fn markdown_header(&self) -> Vec<&'static str> {
+        let mut header = vec!["NodeId", "PK", "Score", "Rank"];
+        optional_columns(&mut header, &self.quorum_set_hash, self.seed);
+        if self.ci_low.is_some() {
+            header.extend(["CI low", "CI high"]);
+        }
+        header
+    }
+    fn markdown_cells(&self, precision: usize) -> Vec<String> {
+        let mut cells = vec![
+            self.node_id.to_string(),
+            self.public_key.clone(),
+            format!("{:.*}", precision, self.score),
+            self.rank.to_string(),
+        ];
+        optional_cells(&mut cells, &self.quorum_set_hash, self.seed);
+        if let (Some(low), Some(high)) = (self.ci_low, self.ci_high) {
+            cells.push(format!("{:.*}", precision, low));
+            cells.push(format!("{:.*}", precision, high));
+        }
+        cells
+    }
+}
+
+impl MarkdownRecord for NodeRewardRecord {
+    fn markdown_header(&self) -> Vec<&'static str> {
+        let mut header = vec!["NodeId", "PK", "Score", "Reward", "Rank"];
+        optional_columns(&mut header, &self.quorum_set_hash, self.seed);
+        header
+    }
+    fn markdown_cells(&self, precision: usize) -> Vec<String> {
+        let mut cells = vec![
+            self.node_id.to_string(),
+            self.public_key.clone(),
+            format!("{:.*}", precision, self.score),
+            format!("{:.*}", precision, self.reward),
+            self.rank.to_string(),
+        ];
+        optional_cells(&mut cells, &self.quorum_set_hash, self.seed);
+        cells
+    }
+}
+
+fn optional_columns(header: &mut Vec<&'static str>, qset_hash: &Option<String>, seed: Option<u64>) {
+    if qset_hash.is_some() {
+        header.push("Quorum set hash");
+    }
+    if seed.is_some() {
+        header.push("Seed");
+    }
+}
+
+fn optional_cells(cells: &mut Vec<String>, qset_hash: &Option<String>, seed: Option<u64>) {
+    if let Some(hash) = qset_hash {
+        cells.push(hash.clone());
+    }
+    if let Some(seed) = seed {
+        cells.push(seed.to_string());
+    }
+}
+
+/// Renders the records as a Markdown table with a header row and a separator line, one row per
+/// record. The header is taken from the first record; no records yield an empty string.
+pub fn markdown_table<T: MarkdownRecord>(records: &[T], precision: usize) -> String {
+    let header = match records.first() {
+        Some(record) => record.markdown_header(),
+        None => return String::new(),
+    };
+    let mut table = markdown_row(&header);
+    table.push_str(&format!("|{}\n", "---|".repeat(header.len())));
+    for record in records {
+        table.push_str(&markdown_row(&record.markdown_cells(precision)));
+    }
+    table
+}
+
+fn markdown_row<S: AsRef<str>>(cells: &[S]) -> String {
+    let cells: Vec<&str> = cells.iter().map(|c| c.as_ref()).collect();
+    format!("| {} |\n", cells.join(" | "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    #[test]
+    fn ranking_as_markdown_table() {
+        let rankings = vec![
+            (1, PublicKey::from("GB"), 0.5, 1),
+            (0, PublicKey::from("GA"), 0.25, 2),
+        ];
+        let expected = "| NodeId | PK | Score | Rank |\n\
+                        |---|---|---|---|\n\
+                        | 1 | GB | 0.50 | 1 |\n\
+                        | 0 | GA | 0.25 | 2 |\n";
+        let actual = markdown_table(&ranking_records(&rankings, None, None, None), 2);
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn optional_columns_are_added_to_markdown_table() {
+        let rewards = vec![(0, PublicKey::default(), 1.0, 10.0, 1)];
+        let hashes = vec![String::from("abc")];
+        let actual = markdown_table(&reward_records(&rewards, Some(&hashes), Some(7)), 1);
+        assert!(
+            actual.starts_with("| NodeId | PK | Score | Reward | Rank | Quorum set hash | Seed |")
+        );
+        assert!(actual.ends_with("| 0 |  | 1.0 | 10.0 | 1 | abc | 7 |\n"));
+    }
+
+    #[test]
+    fn no_records_yield_empty_markdown_table() {
+        let records: Vec<NodeRankingRecord> = vec![];
+        assert!(markdown_table(&records, 3).is_empty());
+    }
+}
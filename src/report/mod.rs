@@ -1,3 +1,9 @@
+pub mod dot;
+pub mod markdown;
+pub mod records;
 pub mod summary;
 
+pub use dot::*;
+pub use markdown::*;
+pub use records::*;
 pub use summary::*;
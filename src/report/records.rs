@@ -0,0 +1,238 @@
+use crate::{
+    winning_coalitions_iter, CooperativeGame, NodeRanking, NodeReward, PublicKey, Rank, Reward,
+    Score,
+};
+use fbas_analyzer::{Fbas, NodeId};
+use serde::Serialize;
+use std::str::FromStr;
+
+/// Formats the records of a ranking or distribution can be written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReportFormat {
+    /// One line per node
+    #[default]
+    Csv,
+    /// A JsonEnvelope containing all nodes
+    Json,
+    /// A Markdown table with one row per node
+    Markdown,
+    /// CSV lines whose rewards are the nodes' shares in basis points (1/10000) of the total.
+    /// Only supported for reward distributions.
+    BasisPoints,
+}
+
+impl FromStr for ReportFormat {
+    type Err = &'static str;
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_lowercase().as_ref() {
+            "csv" => Ok(ReportFormat::Csv),
+            "json" => Ok(ReportFormat::Json),
+            "markdown" | "md" => Ok(ReportFormat::Markdown),
+            "basis-points" | "bps" => Ok(ReportFormat::BasisPoints),
+            _ => Err("Unknown report format"),
+        }
+    }
+}
+
+/// One line of a ranking written as CSV or JSON
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NodeRankingRecord {
+    pub node_id: NodeId,
+    pub public_key: PublicKey,
+    pub score: Score,
+    pub rank: Rank,
+    /// Sha3-256 hex of the node's quorum set. Omitted unless requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quorum_set_hash: Option<String>,
+    /// Seed the approximation was run with. Omitted for the other algorithms.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Bounds of the confidence interval of the score. Omitted unless requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ci_low: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ci_high: Option<f64>,
+}
+
+/// One line of a reward distribution written as CSV or JSON
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NodeRewardRecord {
+    pub node_id: NodeId,
+    pub public_key: PublicKey,
+    pub score: Score,
+    pub reward: Reward,
+    pub rank: Rank,
+    /// Sha3-256 hex of the node's quorum set. Omitted unless requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quorum_set_hash: Option<String>,
+    /// Seed the approximation was run with. Omitted for the other algorithms.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+}
+
+/// Version of the JSON output of the CLI. Increase it whenever the JSON layout changes.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Wraps the records of a JSON output so that consumers can check the version of its layout
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JsonEnvelope<T> {
+    pub schema_version: u32,
+    /// Name of the ranking algorithm the records are based on
+    pub algorithm: String,
+    pub nodes: Vec<T>,
+}
+
+impl<T> JsonEnvelope<T> {
+    pub fn new(algorithm: &str, nodes: Vec<T>) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            algorithm: algorithm.to_string(),
+            nodes,
+        }
+    }
+}
+
+/// Turns a ranking report into records, keeping its order. qset_hashes and the confidence
+/// intervals as (low, high) are indexed by NodeId.
+pub fn ranking_records(
+    rankings: &[NodeRanking],
+    qset_hashes: Option<&[String]>,
+    seed: Option<u64>,
+    intervals: Option<&[(f64, f64)]>,
+) -> Vec<NodeRankingRecord> {
+    rankings
+        .iter()
+        .map(|(node, pk, score, rank)| NodeRankingRecord {
+            node_id: *node,
+            public_key: pk.clone(),
+            score: *score,
+            rank: *rank,
+            quorum_set_hash: qset_hashes.map(|hashes| hashes[*node].clone()),
+            seed,
+            ci_low: intervals.map(|ci| ci[*node].0),
+            ci_high: intervals.map(|ci| ci[*node].1),
+        })
+        .collect()
+}
+
+/// Turns a reward report into records, keeping its order. qset_hashes are indexed by NodeId.
+pub fn reward_records(
+    rewards: &[NodeReward],
+    qset_hashes: Option<&[String]>,
+    seed: Option<u64>,
+) -> Vec<NodeRewardRecord> {
+    rewards
+        .iter()
+        .map(|(node, pk, score, reward, rank)| NodeRewardRecord {
+            node_id: *node,
+            public_key: pk.clone(),
+            score: *score,
+            reward: *reward,
+            rank: *rank,
+            quorum_set_hash: qset_hashes.map(|hashes| hashes[*node].clone()),
+            seed,
+        })
+        .collect()
+}
+
+/// The cooperative game of the top tier in explicit form: its characteristic function is 1 for
+/// every listed coalition and 0 for all other subsets of the players
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GameRecord {
+    pub players: Vec<NodeId>,
+    pub winning_coalitions: Vec<Vec<NodeId>>,
+}
+
+/// Collects the winning coalitions of the top tier, ordered by size and then node IDs. The number
+/// of coalitions grows exponentially with the size of the top tier.
+pub fn game_record(fbas: &Fbas, qi_check: bool) -> GameRecord {
+    let players = CooperativeGame::get_involved_nodes(fbas, qi_check);
+    let mut winning_coalitions: Vec<Vec<NodeId>> = winning_coalitions_iter(fbas, false)
+        .map(|coalition| coalition.iter().collect())
+        .collect();
+    winning_coalitions.sort_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+    GameRecord {
+        players,
+        winning_coalitions,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+    use fbas_analyzer::Fbas;
+    use std::path::Path;
+
+    #[test]
+    fn symmetric_nodes_share_quorum_set_hash_in_records() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
+        let rankings = vec![
+            (0, PublicKey::default(), 0.333, 1),
+            (1, PublicKey::default(), 0.333, 1),
+            (2, PublicKey::default(), 0.333, 1),
+        ];
+        let hashes = quorum_set_hashes(&fbas);
+        let records = ranking_records(&rankings, Some(&hashes), None, None);
+        assert!(records[0].quorum_set_hash.is_some());
+        assert!(records
+            .iter()
+            .all(|r| r.quorum_set_hash == records[0].quorum_set_hash));
+        let records = ranking_records(&rankings, None, None, None);
+        assert!(records.iter().all(|r| r.quorum_set_hash.is_none()));
+    }
+
+    #[test]
+    fn quorum_set_hash_column_is_omitted_unless_requested() {
+        let rewards = vec![(0, PublicKey::default(), 1.0, 10.0, 1)];
+        let plain = serde_json::to_string(&reward_records(&rewards, None, None)).unwrap();
+        assert!(!plain.contains("quorum_set_hash"));
+        let hashes = vec![String::from("abc")];
+        let with_hash =
+            serde_json::to_string(&reward_records(&rewards, Some(&hashes), None)).unwrap();
+        assert!(with_hash.contains(r#""quorum_set_hash":"abc""#));
+    }
+
+    #[test]
+    fn seed_is_only_serialized_if_present() {
+        let rankings = vec![(0, PublicKey::default(), 1.0, 1)];
+        let plain = serde_json::to_string(&ranking_records(&rankings, None, None, None)).unwrap();
+        assert!(!plain.contains("seed"));
+        let seeded =
+            serde_json::to_string(&ranking_records(&rankings, None, Some(7), None)).unwrap();
+        assert!(seeded.contains(r#""seed":7"#));
+    }
+
+    #[test]
+    fn confidence_intervals_are_only_serialized_if_present() {
+        let rankings = vec![(1, PublicKey::default(), 0.5, 1)];
+        let plain = serde_json::to_string(&ranking_records(&rankings, None, None, None)).unwrap();
+        assert!(!plain.contains("ci_low"));
+        let intervals = [(0.0, 0.1), (0.4, 0.6)];
+        let records = ranking_records(&rankings, None, None, Some(&intervals));
+        assert_eq!(
+            (Some(0.4), Some(0.6)),
+            (records[0].ci_low, records[0].ci_high)
+        );
+    }
+
+    #[test]
+    fn json_envelope_contains_schema_version() {
+        let rankings = vec![(0, PublicKey::default(), 1.0, 1)];
+        let envelope = JsonEnvelope::new("node-rank", ranking_records(&rankings, None, None, None));
+        let actual = serde_json::to_value(&envelope).unwrap();
+        assert_eq!(SCHEMA_VERSION, actual["schema_version"]);
+        assert_eq!("node-rank", actual["algorithm"]);
+        assert_eq!(0, actual["nodes"][0]["node_id"]);
+    }
+
+    #[test]
+    fn game_record_lists_winning_coalitions() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
+        let expected = GameRecord {
+            players: vec![0, 1, 2],
+            winning_coalitions: vec![vec![0, 1], vec![0, 2], vec![1, 2], vec![0, 1, 2]],
+        };
+        assert_eq!(expected, game_record(&fbas, true));
+    }
+}
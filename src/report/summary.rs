@@ -1,5 +1,16 @@
-use crate::{NodeRanking, NodeReward, PublicKey, Reward, Score};
+use crate::{
+    apportion_rewards, normalize_scores, reward_distribution, NodeRanking, NodeReward, PublicKey,
+    Rank, RankingAlg, Reward, RewardDelta, Score,
+};
 use fbas_analyzer::{to_public_keys, Fbas, NodeId};
+use serde::Deserialize;
+use std::{collections::HashMap, io};
+
+/// Returns the competition rank of a score among all scores, i.e. one plus the number of strictly
+/// higher scores
+fn competition_rank(score: Score, scores: &[Score]) -> Rank {
+    1 + scores.iter().filter(|&&s| s > score).count()
+}
 
 /// Returns a list of NodeRankings sorted by scores
 pub fn create_node_ranking_report(
@@ -24,6 +35,7 @@ pub fn create_node_ranking_report(
                     PublicKey::default()
                 },
                 scores[node],
+                competition_rank(scores[node], &scores),
             )
         })
         .collect();
@@ -37,6 +49,7 @@ pub fn create_reward_report(
     id_score_reward: Vec<(NodeId, Score, Reward)>,
     fbas: &Fbas,
     with_pks: bool,
+    ascending: bool,
 ) -> Vec<NodeReward> {
     let nodes: Vec<NodeId> = id_score_reward.iter().map(|n| n.0).collect();
     let scores: Vec<Score> = id_score_reward.iter().map(|s| s.1).collect();
@@ -59,17 +72,200 @@ pub fn create_reward_report(
                 },
                 scores[node],
                 rewards[node],
+                competition_rank(scores[node], &scores),
             )
         })
         .collect();
-    rewards.sort_by(|x, y| scores[y.0].partial_cmp(&scores[x.0]).unwrap());
+    rewards.sort_by(|x, y| {
+        let order = scores[y.0].partial_cmp(&scores[x.0]).unwrap();
+        if ascending {
+            order.reverse()
+        } else {
+            order
+        }
+    });
     rewards
 }
 
+/// One line of a CSV of scores computed elsewhere
+#[derive(Debug, Deserialize)]
+struct ScoreRecord {
+    node_id: NodeId,
+    score: Score,
+}
+
+/// Reads a CSV with a `node_id,score` header and one line per node and returns the scores indexed
+/// by NodeId. The node IDs must be 0 to n-1, each exactly once.
+pub fn read_scores_csv<R: io::Read>(reader: R) -> Vec<Score> {
+    let records: Vec<ScoreRecord> = csv::Reader::from_reader(reader)
+        .deserialize()
+        .collect::<Result<_, _>>()
+        .expect("Failed to parse the scores CSV");
+    let mut scores = vec![None; records.len()];
+    for record in records {
+        assert!(
+            record.node_id < scores.len() && scores[record.node_id].is_none(),
+            "The node IDs of the scores CSV must be 0 to n-1, each exactly once"
+        );
+        scores[record.node_id] = Some(record.score);
+    }
+    scores.into_iter().flatten().collect()
+}
+
+/// Distributes the budget proportionally to the scores of a `node_id,score` CSV, e.g. computed
+/// offline, and returns a list of (id, pk, score, reward) sorted by score. The public keys are
+/// taken from the FBAS, which must consist of the nodes of the CSV.
+pub fn create_reward_report_from_scores_csv<R: io::Read>(
+    scores_csv: R,
+    fbas: &Fbas,
+    budget: Reward,
+    with_pks: bool,
+) -> Vec<NodeReward> {
+    let scores = read_scores_csv(scores_csv);
+    assert_eq!(
+        fbas.number_of_nodes(),
+        scores.len(),
+        "Expected one score per node of the FBAS"
+    );
+    create_reward_report(apportion_rewards(&scores, budget), fbas, with_pks, false)
+}
+
+/// Distributes the same reward using both algorithms and returns every node's reward under A,
+/// under B and the difference B - A, sorted by the absolute difference (largest first)
+pub fn reward_delta(
+    fbas: &Fbas,
+    alg_a: RankingAlg,
+    alg_b: RankingAlg,
+    reward: Reward,
+    qi_check: bool,
+) -> Vec<RewardDelta> {
+    let rewards_a = reward_distribution(alg_a, fbas, reward, qi_check);
+    let rewards_b = reward_distribution(alg_b, fbas, reward, qi_check);
+    let pks = to_public_keys(0..fbas.number_of_nodes(), fbas);
+    let mut deltas: Vec<RewardDelta> = rewards_a
+        .iter()
+        .zip(rewards_b.iter())
+        .map(|(&(node, _, a), &(_, _, b))| (node, pks[node].clone(), a, b, b - a))
+        .collect();
+    deltas.sort_by(|x, y| y.4.abs().partial_cmp(&x.4.abs()).unwrap());
+    deltas
+}
+
+/// Maps the scores, indexed by NodeId, to the nodes' public keys. Nodes with an empty public key
+/// are keyed by their NodeId instead.
+pub fn scores_by_public_key(scores: &[Score], fbas: &Fbas) -> HashMap<PublicKey, Score> {
+    to_public_keys(0..scores.len(), fbas)
+        .into_iter()
+        .zip(scores)
+        .enumerate()
+        .map(|(node, (pk, &score))| {
+            if pk.is_empty() {
+                (node.to_string(), score)
+            } else {
+                (pk, score)
+            }
+        })
+        .collect()
+}
+
+/// Aggregate statistics of a reward distribution
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct RewardSummary {
+    pub total: Reward,
+    pub mean: Reward,
+    pub median: Reward,
+    /// Mean reward of the top-tier nodes only
+    pub top_tier_mean: Reward,
+}
+
+/// Summarises the rewards, indexed by NodeId. Means and medians of empty sets are 0.
+pub fn reward_summary(rewards: &[Reward], top_tier: &[NodeId]) -> RewardSummary {
+    let top_tier_rewards: Vec<Reward> = top_tier.iter().map(|&node| rewards[node]).collect();
+    RewardSummary {
+        // folding from +0 avoids reporting a total of -0 for no rewards
+        total: rewards.iter().fold(0.0, |total, reward| total + reward),
+        mean: mean(rewards),
+        median: median(rewards),
+        top_tier_mean: mean(&top_tier_rewards),
+    }
+}
+
+/// Fraction of the total reward, indexed by NodeId, that goes to the top-tier nodes. 0 if nothing
+/// is distributed.
+pub fn top_tier_reward_fraction(rewards: &[Reward], top_tier: &[NodeId]) -> f64 {
+    let total: Reward = rewards.iter().sum();
+    if total == 0.0 {
+        return 0.0;
+    }
+    // folding from +0 avoids reporting a fraction of -0 for an empty top tier
+    top_tier.iter().fold(0.0, |sum, &node| sum + rewards[node]) / total
+}
+
+fn mean(values: &[Reward]) -> Reward {
+    if values.is_empty() {
+        0.0
+    } else {
+        values.iter().sum::<Reward>() / values.len() as Reward
+    }
+}
+
+fn median(values: &[Reward]) -> Reward {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|x, y| x.partial_cmp(y).unwrap());
+    let mid = sorted.len() / 2;
+    match sorted.len() {
+        0 => 0.0,
+        n if n % 2 == 0 => (sorted[mid - 1] + sorted[mid]) / 2.0,
+        _ => sorted[mid],
+    }
+}
+
+/// Shannon entropy of the normalised scores divided by its maximum ln(n). 1.0 means that all nodes
+/// have the same power, 0.0 that a single node has all of it. FBAS with less than two nodes get 0.
+pub fn decentralization_index(scores: &[Score]) -> f64 {
+    if scores.len() < 2 {
+        return 0.0;
+    }
+    let entropy: f64 = normalize_scores(scores)
+        .iter()
+        .filter(|&&p| p > 0.0)
+        .map(|p| -p * p.ln())
+        .sum();
+    entropy / (scores.len() as f64).ln()
+}
+
+/// Returns the smallest set of nodes whose combined normalised score exceeds the threshold, e.g.
+/// 0.5 for the nodes controlling the majority of the power. The most powerful nodes are taken
+/// first, nodes with equal scores by lowest NodeId; the set is ordered the same way.
+pub fn controlling_set(scores: &[Score], fbas: &Fbas, threshold: f64) -> Vec<NodeId> {
+    assert_eq!(
+        scores.len(),
+        fbas.number_of_nodes(),
+        "Expected one score per node of the FBAS"
+    );
+    assert!(
+        (0.0..1.0).contains(&threshold),
+        "Threshold must be in [0, 1)"
+    );
+    let normalized = normalize_scores(scores);
+    let mut nodes: Vec<NodeId> = (0..scores.len()).collect();
+    nodes.sort_by(|&x, &y| normalized[y].partial_cmp(&normalized[x]).unwrap());
+    let mut controlled = 0.0;
+    nodes
+        .into_iter()
+        .take_while(|&node| {
+            let below_threshold = controlled <= threshold;
+            controlled += normalized[node];
+            below_threshold
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::*;
+    use approx::*;
 
     fn read_fbas_from_str() -> Fbas {
         let input = r#"[
@@ -128,7 +324,7 @@ mod tests {
                     ]
                 }
             }]"#;
-        Fbas::from_json_str(&input)
+        Fbas::from_json_str(input)
     }
 
     #[test]
@@ -139,11 +335,11 @@ mod tests {
         let scores = compute_node_rank_for_fbas(&nodes, &fbas, qi_check);
         let actual = create_node_ranking_report(&nodes, scores.to_owned(), &fbas, true);
         let expected = vec![
-            (0, String::from("node0"), scores[0]),
-            (1, String::from("node1"), scores[1]),
-            (2, String::from("node2"), scores[2]),
-            (4, String::from("node4"), scores[4]),
-            (3, String::from("node3"), scores[3]),
+            (0, String::from("node0"), scores[0], 1),
+            (1, String::from("node1"), scores[1], 2),
+            (2, String::from("node2"), scores[2], 2),
+            (4, String::from("node4"), scores[4], 2),
+            (3, String::from("node3"), scores[3], 5),
         ];
         assert_eq!(expected, actual);
     }
@@ -153,14 +349,214 @@ mod tests {
         let nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
         let reward = 10.0;
         let qi_check = true;
-        let dist = graph_theory_distribution(&nodes, &fbas, reward, qi_check);
-        let actual = create_reward_report(dist.to_owned(), &fbas, true);
+        let dist = graph_theory_distribution(&nodes, &fbas, reward, true, qi_check);
+        let actual = create_reward_report(dist.to_owned(), &fbas, true, false);
         let expected = vec![
-            (0, String::from("node0"), dist[0].1, dist[0].2),
-            (1, String::from("node1"), dist[1].1, dist[1].2),
-            (2, String::from("node2"), dist[2].1, dist[2].2),
-            (4, String::from("node4"), dist[4].1, dist[4].2),
-            (3, String::from("node3"), dist[3].1, dist[3].2),
+            (0, String::from("node0"), dist[0].1, dist[0].2, 1),
+            (1, String::from("node1"), dist[1].1, dist[1].2, 2),
+            (2, String::from("node2"), dist[2].1, dist[2].2, 2),
+            (4, String::from("node4"), dist[4].1, dist[4].2, 2),
+            (3, String::from("node3"), dist[3].1, dist[3].2, 5),
+        ];
+        assert_eq!(expected, actual);
+    }
+    #[test]
+    fn node_rewards_output_can_be_sorted_ascending() {
+        let fbas = read_fbas_from_str();
+        let nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+        let reward = 10.0;
+        let qi_check = true;
+        let dist = graph_theory_distribution(&nodes, &fbas, reward, true, qi_check);
+        let descending = create_reward_report(dist.to_owned(), &fbas, true, false);
+        let ascending = create_reward_report(dist, &fbas, true, true);
+        assert_eq!(descending.first().unwrap().0, ascending.last().unwrap().0);
+        assert_eq!(descending.last().unwrap().0, ascending.first().unwrap().0);
+    }
+    #[test]
+    fn tied_nodes_share_competition_rank() {
+        let fbas = read_fbas_from_str();
+        let nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
+        let scores = vec![0.3, 0.3, 0.2, 0.1, 0.1];
+        let actual: Vec<Rank> = create_node_ranking_report(&nodes, scores, &fbas, false)
+            .into_iter()
+            .map(|r| r.3)
+            .collect();
+        assert_eq!(vec![1, 1, 3, 4, 4], actual);
+    }
+    #[test]
+    fn top_tier_mean_ignores_leaf_nodes() {
+        let input = r#"[
+            {
+                "publicKey": "node0",
+                "quorumSet": { "threshold": 2, "validators": ["node0", "node1", "node2"] }
+            },
+            {
+                "publicKey": "node1",
+                "quorumSet": { "threshold": 2, "validators": ["node0", "node1", "node2"] }
+            },
+            {
+                "publicKey": "node2",
+                "quorumSet": { "threshold": 2, "validators": ["node0", "node1", "node2"] }
+            },
+            {
+                "publicKey": "node3",
+                "quorumSet": { "threshold": 2, "validators": ["node0", "node1", "node2"] }
+            },
+            {
+                "publicKey": "node4",
+                "quorumSet": { "threshold": 2, "validators": ["node0", "node1", "node2"] }
+            }
+        ]"#;
+        let fbas = Fbas::from_json_str(input);
+        let top_tier = vec![0, 1, 2];
+        let dist = exact_game_theory_distribution(&fbas, 3.0, Some(top_tier.clone()), true);
+        let rewards: Vec<Reward> = dist.iter().map(|d| d.2).collect();
+        assert_eq!(0.0, rewards[3] + rewards[4]);
+        let actual = reward_summary(&rewards, &top_tier);
+        assert_abs_diff_eq!(3.0, actual.total, epsilon = 1e-9);
+        assert_abs_diff_eq!(0.6, actual.mean, epsilon = 1e-9);
+        assert_abs_diff_eq!(0.999, actual.median, epsilon = 1e-9);
+        assert_abs_diff_eq!(1.0, actual.top_tier_mean, epsilon = 1e-9);
+        assert_ne!(actual.mean, actual.top_tier_mean);
+    }
+    #[test]
+    fn only_node_rank_rewards_nodes_outside_top_tier() {
+        let fbas = Fbas::from_json_file(std::path::Path::new("test_data/correct.json"));
+        let min_qs = fbas_analyzer::find_minimal_quorums(&fbas);
+        let top_tier: Vec<NodeId> = fbas_analyzer::involved_nodes(&min_qs).into_iter().collect();
+        let fraction = |alg| {
+            let mut rewards = vec![Reward::default(); fbas.number_of_nodes()];
+            for (node, _, reward) in reward_distribution(alg, &fbas, 1.0, true) {
+                rewards[node] = reward;
+            }
+            top_tier_reward_fraction(&rewards, &top_tier)
+        };
+        assert!(fraction(RankingAlg::NodeRank) < 1.0);
+        assert_abs_diff_eq!(
+            1.0,
+            fraction(RankingAlg::PowerIndexEnum(None)),
+            epsilon = 1e-9
+        );
+        assert_eq!(0.0, top_tier_reward_fraction(&[0.0, 0.0], &[0]));
+        // no top tier, e.g. without quorums, must not be reported as -0
+        assert!(top_tier_reward_fraction(&[1.0, 1.0], &[]).is_sign_positive());
+    }
+    #[test]
+    fn summary_of_no_rewards_is_zero() {
+        let actual = reward_summary(&[], &[]);
+        assert_eq!(RewardSummary::default(), actual);
+        assert!(actual.total.is_sign_positive());
+    }
+    #[test]
+    fn reward_deltas_between_node_rank_and_power_index_sum_to_zero() {
+        let fbas = Fbas::from_json_file(std::path::Path::new("test_data/paper_example.json"));
+        let actual = reward_delta(
+            &fbas,
+            RankingAlg::NodeRank,
+            RankingAlg::PowerIndexEnum(None),
+            100.0,
+            true,
+        );
+        assert_eq!(fbas.number_of_nodes(), actual.len());
+        for (_, _, a, b, delta) in actual.iter() {
+            assert_abs_diff_eq!(b - a, delta, epsilon = 1e-9);
+        }
+        assert!(actual.windows(2).all(|w| w[0].4.abs() >= w[1].4.abs()));
+        let total: Reward = actual.iter().map(|d| d.4).sum();
+        assert_abs_diff_eq!(0.0, total, epsilon = 1e-9);
+    }
+    #[test]
+    fn decentralization_index_of_symmetric_and_dictator_fbas() {
+        let symmetric = Fbas::from_json_file(std::path::Path::new("test_data/trivial.json"));
+        let scores = rank_nodes(&symmetric, RankingAlg::PowerIndexEnum(None), true);
+        assert_abs_diff_eq!(1.0, decentralization_index(&scores), epsilon = 1e-9);
+        let input = r#"[
+            {
+                "publicKey": "node0",
+                "quorumSet": { "threshold": 1, "validators": ["node0"] }
+            },
+            {
+                "publicKey": "node1",
+                "quorumSet": { "threshold": 1, "validators": ["node0"] }
+            },
+            {
+                "publicKey": "node2",
+                "quorumSet": { "threshold": 1, "validators": ["node0"] }
+            }
+        ]"#;
+        let dictator = Fbas::from_json_str(input);
+        let scores = rank_nodes(&dictator, RankingAlg::PowerIndexEnum(None), true);
+        assert_abs_diff_eq!(0.0, decentralization_index(&scores), epsilon = 1e-9);
+    }
+
+    #[test]
+    fn scores_keyed_by_public_key() {
+        let fbas = Fbas::from_json_file(std::path::Path::new("test_data/trivial.json"));
+        let actual = scores_by_public_key(&[0.5, 0.25, 0.25], &fbas);
+        let expected = HashMap::from([
+            (
+                "GCGB2S2KGYARPVIA37HYZXVRM2YZUEXA6S33ZU5BUDC6THSB62LZSTYH".to_string(),
+                0.5,
+            ),
+            (
+                "GCM6QMP3DLRPTAZW2UZPCPX2LF3SXWXKPMP3GKFZBDSF3QZGV2G5QSTK".to_string(),
+                0.25,
+            ),
+            (
+                "GABMKJM6I25XI4K7U6XWMULOUQIQ27BCTMLS6BYYSOWKTBUXVRJSXHYQ".to_string(),
+                0.25,
+            ),
+        ]);
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn scores_of_nodes_without_public_key_keyed_by_node_id() {
+        let input = r#"[
+            { "publicKey": "", "quorumSet": { "threshold": 1, "validators": [""] } }
+        ]"#;
+        let fbas = Fbas::from_json_str(input);
+        let actual = scores_by_public_key(&[1.0], &fbas);
+        assert_eq!(HashMap::from([("0".to_string(), 1.0)]), actual);
+    }
+
+    #[test]
+    fn controlling_set_of_paper_example() {
+        // node 0 has 7/15 of the power, every other node 2/15
+        let fbas = Fbas::from_json_file(std::path::Path::new("test_data/paper_example.json"));
+        let scores = rank_nodes(&fbas, RankingAlg::PowerIndexEnum(None), true);
+        assert_eq!(vec![0], controlling_set(&scores, &fbas, 0.4));
+        assert_eq!(vec![0, 1], controlling_set(&scores, &fbas, 0.5));
+        assert_eq!(vec![0, 1, 2, 3, 4], controlling_set(&scores, &fbas, 0.9));
+    }
+
+    #[test]
+    fn reward_report_from_scores_csv_has_public_keys() {
+        let fbas = Fbas::from_json_file(std::path::Path::new("test_data/trivial.json"));
+        let csv = "node_id,score\n2,0.2\n0,0.5\n1,0.3\n";
+        let actual = create_reward_report_from_scores_csv(csv.as_bytes(), &fbas, 10.0, true);
+        let expected = vec![
+            (
+                0,
+                "GCGB2S2KGYARPVIA37HYZXVRM2YZUEXA6S33ZU5BUDC6THSB62LZSTYH".to_string(),
+                0.5,
+                5.0,
+                1,
+            ),
+            (
+                1,
+                "GCM6QMP3DLRPTAZW2UZPCPX2LF3SXWXKPMP3GKFZBDSF3QZGV2G5QSTK".to_string(),
+                0.3,
+                3.0,
+                2,
+            ),
+            (
+                2,
+                "GABMKJM6I25XI4K7U6XWMULOUQIQ27BCTMLS6BYYSOWKTBUXVRJSXHYQ".to_string(),
+                0.2,
+                2.0,
+                3,
+            ),
         ];
         assert_eq!(expected, actual);
     }
@@ -46,10 +46,18 @@ impl FromStr for FbasType {
 #[derive(Debug)]
 pub enum Task {
     ReusePerfData(PerfDataPoint),
-    ReuseErrorData(ErrorDataPoint),
+    ReuseErrorData(Box<ErrorDataPoint>),
     Analyze(InputDataPoint),
 }
 impl Task {
+    /// The input the task's data point belongs to
+    pub fn input(&self) -> InputDataPoint {
+        match self {
+            Task::ReusePerfData(output) => InputDataPoint::from_perf_data_point(output),
+            Task::Analyze(input) => input.clone(),
+            Task::ReuseErrorData(output) => InputDataPoint::from_error_data_point(output),
+        }
+    }
     pub fn label(&self) -> usize {
         match self {
             Task::ReusePerfData(output) => output.top_tier_size,
@@ -74,7 +82,7 @@ fn make_almost_ideal_fbas(top_tier_size: usize) -> Fbas {
 
 fn make_almost_ideal_stellarlike_fbas(top_tier_size: usize) -> Fbas {
     assert!(
-        top_tier_size % 3 == 0,
+        top_tier_size.is_multiple_of(3),
         "Nodes in the Stellar network top tier always come in groups of (at least) 3..."
     );
     let mut quorum_set = QuorumSet::new_empty();
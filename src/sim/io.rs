@@ -1,6 +1,34 @@
-use csv::{Reader, Writer};
+use csv::{Reader, Writer, WriterBuilder};
 use serde::{Deserialize, Serialize};
-use std::{error::Error, io, path::Path};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    error::Error,
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader},
+    path::Path,
+    str::FromStr,
+};
+
+/// File format of the data points written by the batch binaries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// One line per data point
+    #[default]
+    Csv,
+    /// A single array containing all data points
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = &'static str;
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_lowercase().as_ref() {
+            "csv" => Ok(OutputFormat::Csv),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err("Unknown output format"),
+        }
+    }
+}
 
 #[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Default)]
 pub struct InputDataPoint {
@@ -29,7 +57,7 @@ pub struct PerfDataPoint {
     pub duration: f64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
 pub struct ErrorDataPoint {
     pub top_tier_size: usize,
     pub run: usize,
@@ -57,6 +85,110 @@ pub struct ErrorDataPoint {
     pub mean_abs_error_10_pow_8: f64,
     pub median_abs_error_10_pow_8: f64,
     pub mean_abs_percentage_error_10_pow_8: f64,
+    /// |sum(scores) - 1| of the approximations. NaN if read from a file written by an older
+    /// version, which lacks these columns.
+    #[serde(default = "missing_deviation")]
+    pub score_sum_deviation_10_pow_1: f64,
+    #[serde(default = "missing_deviation")]
+    pub score_sum_deviation_10_pow_2: f64,
+    #[serde(default = "missing_deviation")]
+    pub score_sum_deviation_10_pow_3: f64,
+    #[serde(default = "missing_deviation")]
+    pub score_sum_deviation_10_pow_4: f64,
+    #[serde(default = "missing_deviation")]
+    pub score_sum_deviation_10_pow_5: f64,
+    #[serde(default = "missing_deviation")]
+    pub score_sum_deviation_10_pow_6: f64,
+    #[serde(default = "missing_deviation")]
+    pub score_sum_deviation_10_pow_7: f64,
+    #[serde(default = "missing_deviation")]
+    pub score_sum_deviation_10_pow_8: f64,
+    /// Whether the approximations ordered the nodes like the exact indices, ignoring the order of
+    /// nodes with equal exact scores. Missing in files written by older versions.
+    #[serde(default)]
+    pub ordering_recovered_10_pow_1: bool,
+    #[serde(default)]
+    pub ordering_recovered_10_pow_2: bool,
+    #[serde(default)]
+    pub ordering_recovered_10_pow_3: bool,
+    #[serde(default)]
+    pub ordering_recovered_10_pow_4: bool,
+    #[serde(default)]
+    pub ordering_recovered_10_pow_5: bool,
+    #[serde(default)]
+    pub ordering_recovered_10_pow_6: bool,
+    #[serde(default)]
+    pub ordering_recovered_10_pow_7: bool,
+    #[serde(default)]
+    pub ordering_recovered_10_pow_8: bool,
+}
+
+/// Deviations missing in old files must not read as a perfect 0
+fn missing_deviation() -> f64 {
+    f64::NAN
+}
+
+/// Orders the data points by their input, i.e. by (top_tier_size, run), so that the output of a
+/// batch run does not depend on the order in which its parallel tasks finish. Unlike sorting, this
+/// keeps the output incremental: every data point is yielded as soon as the data points of all
+/// smaller inputs have arrived, and only the ones that arrive early are buffered.
+/// `inputs` are the inputs of the expected data points; unexpected ones are yielded at the end.
+pub fn in_input_order<T, I, F>(
+    data_points: I,
+    mut inputs: Vec<InputDataPoint>,
+    input: F,
+) -> InInputOrder<I::IntoIter, T, F>
+where
+    I: IntoIterator<Item = T>,
+    F: Fn(&T) -> InputDataPoint,
+{
+    inputs.sort();
+    InInputOrder {
+        data_points: data_points.into_iter(),
+        pending: inputs.into(),
+        early: BTreeMap::new(),
+        input,
+    }
+}
+
+/// Reorder buffer returned by in_input_order
+pub struct InInputOrder<I, T, F> {
+    data_points: I,
+    /// Inputs whose data points have not been yielded yet, in order
+    pending: VecDeque<InputDataPoint>,
+    /// Data points that arrived before the data point of the next pending input
+    early: BTreeMap<InputDataPoint, T>,
+    input: F,
+}
+
+impl<I, T, F> Iterator for InInputOrder<I, T, F>
+where
+    I: Iterator<Item = T>,
+    F: Fn(&T) -> InputDataPoint,
+{
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        loop {
+            if let Some(next_input) = self.pending.front() {
+                if let Some(data_point) = self.early.remove(next_input) {
+                    self.pending.pop_front();
+                    return Some(data_point);
+                }
+            } else if let Some((_, data_point)) = self.early.pop_first() {
+                return Some(data_point);
+            }
+            match self.data_points.next() {
+                Some(data_point) => {
+                    self.early.insert((self.input)(&data_point), data_point);
+                }
+                None => {
+                    // some expected data points are missing, flush the rest in order
+                    self.pending.clear();
+                    return self.early.pop_first().map(|(_, data_point)| data_point);
+                }
+            }
+        }
+    }
 }
 
 pub fn read_csv_from_file(path: &Path) -> Result<Vec<PerfDataPoint>, Box<dyn Error>> {
@@ -85,6 +217,62 @@ pub fn write_csv_to_file(
     write_csv_via_writer(data_points, writer)
 }
 
+/// Appends the data points to the CSV file at path, creating it if it does not exist. The header
+/// is only written if the file is empty. Fails without writing anything if the file has a
+/// different header, e.g. because it was written by an older version with fewer columns.
+pub fn append_csv_to_file(
+    data_points: impl IntoIterator<Item = impl serde::Serialize>,
+    path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let mut data_points = data_points.into_iter().peekable();
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let is_empty = file.metadata()?.len() == 0;
+    if let (false, Some(first)) = (is_empty, data_points.peek()) {
+        let expected = csv_header(first)?;
+        let actual = csv_header_of_file(path)?;
+        if actual != expected {
+            return Err(Box::new(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "The header of {} differs from the columns of the new data points, refusing \
+                     to append to it.",
+                    path.display()
+                ),
+            )));
+        }
+    }
+    let writer = WriterBuilder::new().has_headers(is_empty).from_writer(file);
+    write_csv_via_writer(data_points, writer)
+}
+
+/// Rewrites the error data CSV file at path with the current columns if it was written by an
+/// older version, so that new data points can be appended to it. The missing columns get their
+/// defaults, see ErrorDataPoint. Returns whether the file was rewritten.
+pub fn upgrade_error_data_csv(path: &Path) -> Result<bool, Box<dyn Error>> {
+    let header = csv_header_of_file(path)?;
+    if header.is_empty() || header == csv_header(&ErrorDataPoint::default())? {
+        return Ok(false);
+    }
+    let data_points = read_error_data_csv_from_file(path)?;
+    write_csv_to_file(data_points, path)?;
+    Ok(true)
+}
+
+/// Header line the CSV writer writes for the data point
+fn csv_header(data_point: &impl serde::Serialize) -> Result<String, Box<dyn Error>> {
+    let mut writer = Writer::from_writer(vec![]);
+    writer.serialize(data_point)?;
+    let csv = String::from_utf8(writer.into_inner()?)?;
+    Ok(csv.lines().next().unwrap_or_default().to_string())
+}
+
+/// First line of the file at path, i.e. its header if it is a CSV file
+fn csv_header_of_file(path: &Path) -> Result<String, Box<dyn Error>> {
+    let mut header = String::new();
+    BufReader::new(File::open(path)?).read_line(&mut header)?;
+    Ok(header.trim_end().to_string())
+}
+
 pub fn write_csv_to_stdout(
     data_points: impl IntoIterator<Item = impl serde::Serialize>,
 ) -> Result<(), Box<dyn Error>> {
@@ -103,6 +291,32 @@ pub fn write_csv_via_writer(
     Ok(())
 }
 
+pub fn write_json_to_file(
+    data_points: impl IntoIterator<Item = impl serde::Serialize>,
+    path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let writer = File::create(path)?;
+    write_json_via_writer(data_points, writer)
+}
+
+pub fn write_json_to_stdout(
+    data_points: impl IntoIterator<Item = impl serde::Serialize>,
+) -> Result<(), Box<dyn Error>> {
+    write_json_via_writer(data_points, io::stdout())
+}
+
+/// Writes all data points as one JSON array. Unlike the CSV writer, this has to wait for the last
+/// data point before writing anything.
+pub fn write_json_via_writer(
+    data_points: impl IntoIterator<Item = impl serde::Serialize>,
+    mut writer: impl io::Write,
+) -> Result<(), Box<dyn Error>> {
+    let data_points: Vec<_> = data_points.into_iter().collect();
+    serde_json::to_writer_pretty(&mut writer, &data_points)?;
+    writeln!(writer)?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -127,4 +341,132 @@ mod tests {
         let actual = write_csv_to_file(vec![mock_data], file_path);
         assert!(actual.is_err());
     }
+
+    #[test]
+    fn ordered_data_points_give_identical_csvs() {
+        let data_points: Vec<PerfDataPoint> = (0..6)
+            .map(|i| PerfDataPoint {
+                top_tier_size: 1 + i % 3,
+                run: i / 3,
+                duration: i as f64,
+            })
+            .collect();
+        let inputs: Vec<InputDataPoint> = data_points
+            .iter()
+            .map(InputDataPoint::from_perf_data_point)
+            .collect();
+        let write = |data_points: Vec<PerfDataPoint>| {
+            let mut buffer = Vec::new();
+            let ordered = in_input_order(
+                data_points,
+                inputs.clone(),
+                InputDataPoint::from_perf_data_point,
+            );
+            write_csv_via_writer(ordered, Writer::from_writer(&mut buffer)).unwrap();
+            buffer
+        };
+        let first = write(data_points.clone());
+        let second = write(data_points.into_iter().rev().collect());
+        assert_eq!(first, second);
+        let csv = String::from_utf8(first).unwrap();
+        assert!(csv.starts_with("top_tier_size,run,duration\n1,0,0.0\n1,1,3.0\n2,0,1.0\n"));
+    }
+
+    #[test]
+    fn ordered_data_points_are_yielded_as_soon_as_possible() {
+        let input = |top_tier_size, run| InputDataPoint { top_tier_size, run };
+        // the tasks of (1, 1) and (1, 0) finish first, in the wrong order
+        let arrival = vec![
+            input(1, 1),
+            input(1, 0),
+            input(2, 0),
+            input(3, 0),
+            input(2, 1),
+        ];
+        let arrived = std::cell::Cell::new(0);
+        let data_points = arrival.clone().into_iter().inspect(|_| {
+            arrived.set(arrived.get() + 1);
+        });
+        let mut ordered = in_input_order(data_points, arrival, |d: &InputDataPoint| d.clone());
+        assert_eq!(Some(input(1, 0)), ordered.next());
+        assert_eq!(2, arrived.get());
+        assert_eq!(Some(input(1, 1)), ordered.next());
+        assert_eq!(Some(input(2, 0)), ordered.next());
+        assert_eq!(3, arrived.get());
+        let rest: Vec<InputDataPoint> = ordered.collect();
+        assert_eq!(vec![input(2, 1), input(3, 0)], rest);
+    }
+
+    #[test]
+    fn perf_data_point_serializes_to_json() {
+        let data_point = PerfDataPoint {
+            top_tier_size: 3,
+            run: 1,
+            duration: 0.5,
+        };
+        let mut buffer = Vec::new();
+        write_json_via_writer(vec![data_point], &mut buffer).unwrap();
+        let actual: serde_json::Value = serde_json::from_slice(&buffer).unwrap();
+        let expected = serde_json::json!([{"top_tier_size": 3, "run": 1, "duration": 0.5}]);
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn outdated_csv_is_upgraded_before_appending() {
+        let file_path = std::env::temp_dir().join(format!(
+            "fbas_reward_distributor_outdated_{}.csv",
+            std::process::id()
+        ));
+        let old_columns: String = csv_header(&ErrorDataPoint::default())
+            .unwrap()
+            .split(',')
+            .take(26)
+            .collect::<Vec<_>>()
+            .join(",");
+        let old_row = vec!["0"; 26].join(",");
+        std::fs::write(&file_path, format!("{old_columns}\n{old_row}\n")).unwrap();
+        let new_data_point = ErrorDataPoint {
+            top_tier_size: 1,
+            ..Default::default()
+        };
+        assert!(append_csv_to_file(vec![new_data_point.clone()], &file_path).is_err());
+        assert!(upgrade_error_data_csv(&file_path).unwrap());
+        assert!(!upgrade_error_data_csv(&file_path).unwrap());
+        append_csv_to_file(vec![new_data_point], &file_path).unwrap();
+        let actual = read_error_data_csv_from_file(&file_path).unwrap();
+        std::fs::remove_file(&file_path).unwrap();
+        assert_eq!(2, actual.len());
+        assert!(actual[0].score_sum_deviation_10_pow_1.is_nan());
+        assert_eq!(0.0, actual[1].score_sum_deviation_10_pow_1);
+    }
+
+    #[test]
+    fn appending_to_partial_csv_completes_it() {
+        let file_path = std::env::temp_dir().join(format!(
+            "fbas_reward_distributor_partial_{}.csv",
+            std::process::id()
+        ));
+        let data_points: Vec<ErrorDataPoint> = (0..4)
+            .map(|run| ErrorDataPoint {
+                top_tier_size: 2,
+                run,
+                mean_abs_error_10_pow_1: run as f64,
+                ..Default::default()
+            })
+            .collect();
+        // the first run got interrupted after two data points
+        write_csv_to_file(data_points[..2].to_vec(), &file_path).unwrap();
+        let existing = read_error_data_csv_from_file(&file_path).unwrap();
+        assert_eq!(data_points[..2].to_vec(), existing);
+        // the update only computes what is missing and appends it
+        let missing: Vec<ErrorDataPoint> = data_points
+            .iter()
+            .filter(|d| !existing.contains(d))
+            .cloned()
+            .collect();
+        append_csv_to_file(missing, &file_path).unwrap();
+        let actual = read_error_data_csv_from_file(&file_path).unwrap();
+        std::fs::remove_file(&file_path).unwrap();
+        assert_eq!(data_points, actual);
+    }
 }
@@ -1,7 +1,15 @@
+#[cfg(not(target_arch = "wasm32"))]
 mod common;
+#[cfg(not(target_arch = "wasm32"))]
 mod io;
+#[cfg(feature = "batch")]
+mod progress;
 mod stats;
 
+#[cfg(not(target_arch = "wasm32"))]
 pub use common::*;
+#[cfg(not(target_arch = "wasm32"))]
 pub use io::*;
+#[cfg(feature = "batch")]
+pub use progress::*;
 pub use stats::*;
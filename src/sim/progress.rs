@@ -0,0 +1,34 @@
+use indicatif::{ProgressBar, ProgressFinish, ProgressStyle};
+use std::io::IsTerminal;
+
+/// Returns a progress bar for a batch of `num_tasks` tasks. The bar is only drawn if STDERR is a
+/// terminal; otherwise it is hidden but still keeps count.
+pub fn task_progress_bar(num_tasks: usize) -> ProgressBar {
+    let bar = if std::io::stderr().is_terminal() {
+        ProgressBar::new(num_tasks as u64)
+    } else {
+        ProgressBar::hidden()
+    };
+    bar.set_length(num_tasks as u64);
+    bar.set_style(
+        ProgressStyle::with_template(
+            "[{elapsed_precise}] {wide_bar} {pos}/{len} tasks (ETA {eta})",
+        )
+        .expect("Invalid progress bar template"),
+    );
+    bar.with_finish(ProgressFinish::AndLeave)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indicatif::ProgressIterator;
+
+    #[test]
+    fn progress_bar_reaches_number_of_tasks() {
+        let bar = task_progress_bar(3);
+        let results: Vec<usize> = (0..3).progress_with(bar.clone()).collect();
+        assert_eq!(vec![0, 1, 2], results);
+        assert_eq!(Some(bar.position()), bar.length());
+    }
+}
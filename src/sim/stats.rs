@@ -1,14 +1,75 @@
-use crate::{rank::round_to_three_places, Score};
+use crate::{rank::round_to_places, Score};
 
 /// Expects a list of approximations and one of the truth values
 /// Returns a tuple of absolute error values in the order of the name of the function
 pub fn mean_med_pctg_errors(approx: &[Score], exact: &[Score]) -> (f64, f64, f64) {
+    mean_med_pctg_errors_with_precision(approx, exact, PCTG_ERROR_PLACES)
+}
+
+/// Decimal places the percentage error of mean_med_pctg_errors is truncated to
+pub const PCTG_ERROR_PLACES: u32 = 3;
+
+/// Same as mean_med_pctg_errors but truncates the percentage error to the passed number of
+/// decimal places, e.g. to tell apart the tiny errors of large sample budgets
+pub fn mean_med_pctg_errors_with_precision(
+    approx: &[Score],
+    exact: &[Score],
+    places: u32,
+) -> (f64, f64, f64) {
     let mean = mean_abs_error(approx, exact);
     let median = median_abs_error(approx, exact);
-    let percentage = mean_abs_pctg_error(approx, exact);
+    let percentage = mean_abs_pctg_error(approx, exact, places);
     (mean, median, percentage)
 }
 
+/// Distance of the sum of the scores from 1. The exact Shapley-Shubik indices sum up to 1 (up to
+/// rounding), so a large deviation of an approximation hints at too few samples.
+pub fn score_sum_deviation(scores: &[Score]) -> f64 {
+    (scores.iter().sum::<Score>() - 1.0).abs()
+}
+
+/// Spearman's rank correlation of two score vectors, i.e. the Pearson correlation of the scores'
+/// ranks. Tied scores get the average of the ranks they span. The correlation is undefined if
+/// either vector has only equal scores; it is then 1 if both do, as they induce the same (empty)
+/// ordering, and 0 otherwise.
+pub fn spearman_correlation(a: &[Score], b: &[Score]) -> f64 {
+    assert_eq!(a.len(), b.len(), "Expected scores of the same nodes");
+    let (ranks_a, ranks_b) = (average_ranks(a), average_ranks(b));
+    let mean = (a.len() as f64 + 1.0) / 2.0;
+    let covariance: f64 = ranks_a
+        .iter()
+        .zip(ranks_b.iter())
+        .map(|(x, y)| (x - mean) * (y - mean))
+        .sum();
+    let variance = |ranks: &[f64]| ranks.iter().map(|x| (x - mean).powi(2)).sum::<f64>();
+    let (variance_a, variance_b) = (variance(&ranks_a), variance(&ranks_b));
+    match (variance_a == 0.0, variance_b == 0.0) {
+        (true, true) => 1.0,
+        (true, false) | (false, true) => 0.0,
+        (false, false) => covariance / (variance_a * variance_b).sqrt(),
+    }
+}
+
+/// 1-based ranks of the scores in ascending order, ties get the average of the ranks they span
+fn average_ranks(scores: &[Score]) -> Vec<f64> {
+    let mut order: Vec<usize> = (0..scores.len()).collect();
+    order.sort_by(|&x, &y| scores[x].partial_cmp(&scores[y]).unwrap());
+    let mut ranks = vec![0.0; scores.len()];
+    let mut start = 0;
+    while start < order.len() {
+        let mut end = start;
+        while end + 1 < order.len() && scores[order[end + 1]] == scores[order[start]] {
+            end += 1;
+        }
+        let rank = (start + end) as f64 / 2.0 + 1.0;
+        for &i in order[start..=end].iter() {
+            ranks[i] = rank;
+        }
+        start = end + 1;
+    }
+    ranks
+}
+
 fn mean_abs_error(approximation: &[Score], truth: &[Score]) -> f64 {
     let mut mean_error = 0.0;
     assert!(approximation.len() == truth.len());
@@ -34,7 +95,7 @@ fn median_abs_error(approximation: &[f64], truth: &[f64]) -> f64 {
 
 // The idea of this metric is to be sensitive to relative errors. It is for example not changed by
 // a global scaling of the target variable.
-fn mean_abs_pctg_error(approximation: &[f64], truth: &[f64]) -> f64 {
+fn mean_abs_pctg_error(approximation: &[f64], truth: &[f64], places: u32) -> f64 {
     let epsilon: f64 = f64::EPSILON; //  is an arbitrary small yet strictly positive number to avoid undefined results when y is zero
     let mut average_percentage_error = 0.0;
     for (i, value) in approximation.iter().enumerate() {
@@ -42,7 +103,10 @@ fn mean_abs_pctg_error(approximation: &[f64], truth: &[f64]) -> f64 {
         let max = epsilon.max(truth[i]);
         average_percentage_error += abs_diff / max;
     }
-    round_to_three_places((1.0 / (approximation.len() as f64)) * average_percentage_error)
+    round_to_places(
+        (1.0 / (approximation.len() as f64)) * average_percentage_error,
+        places,
+    )
 }
 
 #[cfg(test)]
@@ -70,8 +134,57 @@ mod tests {
     fn percentage_error() {
         let truth = vec![1.0, 10.0, 1e6];
         let prediction = vec![0.9, 15.0, 1.2e6];
-        let actual = mean_abs_pctg_error(&prediction, &truth);
+        let actual = mean_abs_pctg_error(&prediction, &truth, PCTG_ERROR_PLACES);
         let expected = 0.266;
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn tiny_percentage_errors_are_distinguishable_at_higher_precision() {
+        let truth = vec![0.5, 0.5];
+        let close = vec![0.5001, 0.4999];
+        let closer = vec![0.50001, 0.49999];
+        let (_, _, close_default) = mean_med_pctg_errors(&close, &truth);
+        let (_, _, closer_default) = mean_med_pctg_errors(&closer, &truth);
+        assert_eq!((0.0, 0.0), (close_default, closer_default));
+        let (_, _, close_precise) = mean_med_pctg_errors_with_precision(&close, &truth, 6);
+        let (_, _, closer_precise) = mean_med_pctg_errors_with_precision(&closer, &truth, 6);
+        assert!(closer_precise < close_precise);
+        assert!(closer_precise > 0.0);
+    }
+
+    #[test]
+    fn spearman_correlation_of_rankings() {
+        assert_eq!(
+            1.0,
+            spearman_correlation(&[0.1, 0.5, 0.3], &[1.0, 9.0, 2.0])
+        );
+        assert_eq!(
+            -1.0,
+            spearman_correlation(&[0.1, 0.5, 0.3], &[0.9, 0.1, 0.5])
+        );
+        assert_eq!(
+            vec![1.0, 2.5, 2.5, 4.0],
+            average_ranks(&[0.1, 0.2, 0.2, 0.3])
+        );
+        assert_eq!(1.0, spearman_correlation(&[0.3; 3], &[0.6; 3]));
+        assert_eq!(0.0, spearman_correlation(&[0.3; 3], &[0.1, 0.2, 0.3]));
+    }
+
+    #[test]
+    fn score_sum_deviation_of_exact_and_approximate_indices() {
+        use crate::*;
+        use fbas_analyzer::Fbas;
+        let fbas = Fbas::from_json_file(std::path::Path::new("test_data/paper_example.json"));
+        let exact = rank_nodes(&fbas, RankingAlg::PowerIndexEnum(None), true);
+        assert!(score_sum_deviation(&exact) < 0.01);
+        let config = RankConfig {
+            seed: Some(1),
+            ..Default::default()
+        };
+        let approx =
+            rank_nodes_with_config(&fbas, RankingAlg::PowerIndexApprox(11, None), true, config);
+        assert!(score_sum_deviation(&approx) > score_sum_deviation(&exact));
+        assert_eq!(0.5, score_sum_deviation(&[0.25, 0.25]));
+    }
 }
@@ -0,0 +1,26 @@
+use fbas_analyzer::NodeId;
+use std::fmt;
+
+/// Errors of a ranking run whose inputs contradict the FBAS
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RankingError {
+    /// The top tier passed to a power index does not equal the nodes that are part of at least
+    /// one minimal quorum. Both lists are sorted by NodeId.
+    TopTierMismatch {
+        expected: Vec<NodeId>,
+        actual: Vec<NodeId>,
+    },
+}
+
+impl fmt::Display for RankingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RankingError::TopTierMismatch { expected, actual } => write!(
+                f,
+                "Top tier {actual:?} does not match the FBAS' top tier {expected:?}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RankingError {}
@@ -1,6 +1,7 @@
-use crate::Coalition;
+use crate::{Coalition, RankConfig};
 use fbas_analyzer::{Fbas, NodeId};
-use log::{info, trace};
+use log::{info, trace, warn};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct CooperativeGame<'a> {
@@ -8,8 +9,79 @@ pub(crate) struct CooperativeGame<'a> {
     pub(crate) fbas: &'a Fbas,
     /// The set of players
     pub(crate) players: Vec<NodeId>,
-    /// The top tier of the FBAS. Relevant for the approximation only
+    /// The top tier of the FBAS. Computed by the exact algorithm if not passed; the approximation
+    /// samples all players unless it is passed
     pub(crate) top_tier: Option<Vec<NodeId>>,
+    /// Optional settings, e.g. memoization
+    pub(crate) config: RankConfig,
+    /// Critical coalitions enumerated by an earlier exact index, if config.cache_coalitions
+    pub(crate) coalition_cache: RefCell<Option<CoalitionCache>>,
+    /// Nodes that are part of every coalition, e.g. a mandatory hub validator. They are not
+    /// players; the characteristic function is evaluated on the union of a coalition and them.
+    /// Only taken into account by the exact Shapley-Shubik index.
+    pub(crate) forced: Vec<NodeId>,
+}
+
+/// Every player's critical coalitions among the winning coalitions of a top tier
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CoalitionCache {
+    pub(crate) top_tier: Vec<NodeId>,
+    pub(crate) critical_coalitions: Rc<HashMap<NodeId, Vec<Coalition>>>,
+}
+
+/// Memoized results of quorum containment checks within one ranking run.
+/// Coalitions are keyed by a bitmask over the (at most 128) nodes they can be drawn from.
+#[derive(Debug, Default)]
+pub(crate) struct QuorumCache {
+    positions: HashMap<NodeId, u32>,
+    results: RefCell<HashMap<u128, bool>>,
+}
+
+impl QuorumCache {
+    /// Returns None if there are too many nodes to represent coalitions as a u128 bitmask
+    pub(crate) fn new(nodes: &[NodeId]) -> Option<Self> {
+        if nodes.len() > u128::BITS as usize {
+            warn!(
+                "Not memoizing quorums: {} nodes exceed the supported {}.",
+                nodes.len(),
+                u128::BITS
+            );
+            return None;
+        }
+        let positions = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, &v)| (v, i as u32))
+            .collect();
+        Some(Self {
+            positions,
+            results: RefCell::default(),
+        })
+    }
+
+    fn bitmask(&self, coalition: &Coalition) -> Option<u128> {
+        let mut mask = 0u128;
+        for v in coalition.iter() {
+            mask |= 1 << self.positions.get(&v)?;
+        }
+        Some(mask)
+    }
+}
+
+/// Checks whether the coalition contains a quorum, consulting the cache if one is passed
+pub(crate) fn contains_quorum(
+    coalition: &Coalition,
+    fbas: &Fbas,
+    cache: Option<&QuorumCache>,
+) -> bool {
+    match cache.and_then(|c| c.bitmask(coalition).map(|mask| (c, mask))) {
+        Some((c, mask)) => *c
+            .results
+            .borrow_mut()
+            .entry(mask)
+            .or_insert_with(|| fbas_analyzer::contains_quorum(coalition, fbas)),
+        None => fbas_analyzer::contains_quorum(coalition, fbas),
+    }
 }
 
 impl<'a> CooperativeGame<'a> {
@@ -21,6 +93,9 @@ impl<'a> CooperativeGame<'a> {
             fbas,
             players,
             top_tier: None,
+            config: RankConfig::default(),
+            coalition_cache: RefCell::default(),
+            forced: Vec::new(),
         }
     }
 
@@ -36,9 +111,42 @@ impl<'a> CooperativeGame<'a> {
             fbas,
             players,
             top_tier: Some(top_tier.into()),
+            config: RankConfig::default(),
+            coalition_cache: RefCell::default(),
+            forced: Vec::new(),
+        }
+    }
+
+    /// Sets the players to the nodes that are not forced into every coalition and the corresponding
+    /// FBAS
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn init_from_fbas_with_forced(nodes: &[NodeId], forced: &[NodeId], fbas: &'a Fbas) -> Self {
+        let mut players: Vec<NodeId> = nodes
+            .iter()
+            .filter(|v| !forced.contains(v))
+            .copied()
+            .collect();
+        players.dedup();
+        Self {
+            fbas,
+            players,
+            top_tier: None,
+            config: RankConfig::default(),
+            coalition_cache: RefCell::default(),
+            forced: forced.into(),
         }
     }
 
+    /// Creates a quorum cache over the passed nodes if memoization is enabled
+    pub(crate) fn new_quorum_cache(&self, nodes: &[NodeId]) -> Option<QuorumCache> {
+        if self.config.memoize_quorums {
+            QuorumCache::new(nodes)
+        } else {
+            None
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
     pub(crate) fn coalitions_cardinatily(coalition: &Coalition) -> usize {
         coalition.len()
     }
@@ -84,6 +192,9 @@ mod tests {
             fbas: &fbas,
             players: vec![0, 1, 2],
             top_tier: None,
+            config: RankConfig::default(),
+            coalition_cache: RefCell::default(),
+            forced: Vec::new(),
         };
         let actual = CooperativeGame::init_from_fbas(&all_nodes, &fbas);
         assert_eq!(expected.players, actual.players);
@@ -91,8 +202,8 @@ mod tests {
 
     #[test]
     fn set_cardinality() {
-        let coalitions = vec![bitset![], bitset![0, 4, 8]];
-        let expected = vec![0, 3];
+        let coalitions = [bitset![], bitset![0, 4, 8]];
+        let expected = [0, 3];
         for (i, c) in coalitions.iter().enumerate() {
             assert_eq!(expected[i], CooperativeGame::coalitions_cardinatily(c));
         }
@@ -157,11 +268,11 @@ mod tests {
                     ]
                 }
             }]"#;
-        let fbas = Fbas::from_json_str(&input);
+        let fbas = Fbas::from_json_str(input);
         // U = {0, 1, 2}, {0, 3, 4}, {0, 1, 2, 3, 4}
         let all_nodes: Vec<NodeId> = (0..fbas.all_nodes().len()).collect();
         let game = CooperativeGame::init_from_fbas(&all_nodes, &fbas);
-        let expected = vec![
+        let expected = [
             vec![
                 bitset![0, 1, 2],
                 bitset![0, 3, 4],
@@ -197,11 +308,33 @@ mod tests {
             fbas: &fbas,
             players: vec![0, 1, 2],
             top_tier: Some(tt.clone()),
+            config: RankConfig::default(),
+            coalition_cache: RefCell::default(),
+            forced: Vec::new(),
         };
         let actual = CooperativeGame::init_from_fbas_with_top_tier(&all_nodes, &tt, &fbas);
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn cached_quorum_checks_match_uncached() {
+        let fbas = Fbas::from_json_file(Path::new("test_data/trivial.json"));
+        let cache = QuorumCache::new(&[0, 1, 2]).unwrap();
+        for coalition in [
+            bitset![0],
+            bitset![0, 1],
+            bitset![1, 2],
+            bitset![0],
+            bitset![],
+        ] {
+            assert_eq!(
+                fbas_analyzer::contains_quorum(&coalition, &fbas),
+                contains_quorum(&coalition, &fbas, Some(&cache))
+            );
+        }
+        assert_eq!(cache.results.borrow().len(), 4);
+    }
+
     #[should_panic]
     #[test]
     fn fbas_wo_intersection_panics() {
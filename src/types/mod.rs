@@ -1,10 +1,16 @@
+mod error;
 mod game;
 
+pub use error::*;
 pub(crate) use game::*;
 
 use crate::{Reward, Score};
 use fbas_analyzer::NodeId;
 
-pub type NodeRanking = (NodeId, PublicKey, Score);
-pub type NodeReward = (NodeId, PublicKey, Score, Reward);
+pub type NodeRanking = (NodeId, PublicKey, Score, Rank);
+pub type NodeReward = (NodeId, PublicKey, Score, Reward, Rank);
+/// Rewards of a node under two algorithms: (NodeId, PK, reward A, reward B, reward B - reward A)
+pub type RewardDelta = (NodeId, PublicKey, Reward, Reward, Reward);
 pub type PublicKey = String;
+/// 1-based competition rank, i.e. nodes with equal scores share a rank (1, 2, 2, 4, ...)
+pub type Rank = usize;
@@ -0,0 +1,41 @@
+//! Pins the scores of seeded approximations. The permutations are drawn from ChaCha8Rng, whose
+//! output for a seed is fixed by the rand_chacha version, so the same seed has to yield the same
+//! scores on every platform. If one of these tests fails, the sampler, the RNG (e.g. after
+//! upgrading rand_chacha) or the way the contributions are accumulated has changed, which changes
+//! the results of reproduced runs.
+use fbas_analyzer::Fbas;
+use fbas_reward_distributor::*;
+use std::path::Path;
+
+const SEED: u64 = 42;
+const SAMPLES: usize = 1000;
+
+fn seeded_approximation(path: &str) -> Vec<Score> {
+    let fbas = Fbas::from_json_file(Path::new(path));
+    let config = RankConfig {
+        seed: Some(SEED),
+        ..Default::default()
+    };
+    rank_nodes_with_config(
+        &fbas,
+        RankingAlg::PowerIndexApprox(SAMPLES, None),
+        false,
+        config,
+    )
+}
+
+#[test]
+fn seeded_approximation_of_trivial_fbas_is_pinned() {
+    assert_eq!(
+        vec![0.332, 0.334, 0.334],
+        seeded_approximation("test_data/trivial.json")
+    );
+}
+
+#[test]
+fn seeded_approximation_of_paper_example_is_pinned() {
+    assert_eq!(
+        vec![0.479, 0.129, 0.128, 0.135, 0.129],
+        seeded_approximation("test_data/paper_example.json")
+    );
+}
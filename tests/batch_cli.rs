@@ -29,3 +29,36 @@ fn no_fbas_type_in_command() -> Result<(), Box<dyn std::error::Error>> {
     assert!(!output.status.success());
     Ok(())
 }
+
+#[test]
+#[cfg_attr(not(feature = "measurements"), ignore)]
+fn parallel_batch_perf_experiments_are_written_in_input_order(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("performance_tests")?;
+    cmd.arg("-r")
+        .arg("3")
+        .arg("-m")
+        .arg("9")
+        .arg("-j")
+        .arg("4")
+        .arg("stellar")
+        .arg("node-rank");
+    let output = cmd.output().expect("error executing command");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    let inputs: Vec<(usize, usize)> = stdout
+        .lines()
+        .skip(1)
+        .map(|line| {
+            let fields: Vec<&str> = line.split(',').collect();
+            (fields[0].parse().unwrap(), fields[1].parse().unwrap())
+        })
+        .collect();
+    // stellar-like top tiers grow in organizations of 3 nodes
+    let expected: Vec<(usize, usize)> = [3, 6, 9]
+        .into_iter()
+        .flat_map(|top_tier_size| (0..3).map(move |run| (top_tier_size, run)))
+        .collect();
+    assert_eq!(expected, inputs);
+    Ok(())
+}
@@ -8,7 +8,7 @@ fn rank_only_command() -> Result<(), Box<dyn std::error::Error>> {
         .arg("test_data/trivial.json")
         .arg("power-index-enum");
     cmd.assert().success().stdout(predicate::str::contains(
-        "List of Rankings as (NodeId, PK, Score):",
+        "List of Rankings as (NodeId, PK, Score, Rank):",
     ));
     Ok(())
 }
@@ -20,7 +20,7 @@ fn dist_command() -> Result<(), Box<dyn std::error::Error>> {
         .arg("test_data/trivial.json")
         .arg("node-rank");
     cmd.assert().success().stdout(predicate::str::contains(
-        "List of Distributions as (NodeId, PK, Score, Reward):",
+        "List of Distributions as (NodeId, PK, Score, Reward, Rank):",
     ));
     Ok(())
 }
@@ -48,3 +48,927 @@ fn approx_command_without_samples() -> Result<(), Box<dyn std::error::Error>> {
     ));
     Ok(())
 }
+
+#[test]
+fn approx_rank_command_with_bootstrap_ci() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("test_data/trivial.json")
+        .arg("power-index-approx")
+        .arg("100")
+        .arg("--bootstrap-ci")
+        .arg("0.95");
+    cmd.assert().success().stdout(predicate::str::contains(
+        "Bootstrap confidence intervals (0.95) as (NodeId, Low, High):",
+    ));
+    Ok(())
+}
+
+#[test]
+fn approx_command_suggests_samples() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("distribute")
+        .arg("test_data/trivial.json")
+        .arg("power-index-approx")
+        .arg("10")
+        .arg("--suggest-samples")
+        .arg("0.05");
+    cmd.assert().success().stdout(predicate::str::contains(
+        "Suggested number of samples for a maximum error of 0.05",
+    ));
+    Ok(())
+}
+
+#[test]
+fn dist_command_with_apportionment() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("distribute")
+        .arg("-r")
+        .arg("10")
+        .arg("--apportionment")
+        .arg("webster")
+        .arg("test_data/trivial.json")
+        .arg("power-index-enum");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("4.0"))
+        .stdout(predicate::str::contains("3.0"));
+    Ok(())
+}
+
+#[test]
+fn node_rank_command_shows_pagerank() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("test_data/trivial.json")
+        .arg("node-rank")
+        .arg("--show-pagerank");
+    cmd.assert().success().stdout(predicate::str::contains(
+        "List of Rankings as (NodeId, PK, Score, Rank, PageRank):",
+    ));
+    Ok(())
+}
+
+#[test]
+fn verbose_node_rank_shows_amplification() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("test_data/paper_example.json")
+        .arg("node-rank")
+        .arg("--verbose");
+    cmd.assert().success().stdout(predicate::str::contains(
+        "List of Rankings as (NodeId, PK, Score, Rank, PageRank, Amplification):",
+    ));
+    Ok(())
+}
+
+#[test]
+fn node_rank_of_largest_strongly_connected_component_only() -> Result<(), Box<dyn std::error::Error>>
+{
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("test_data/two_clusters.json")
+        .arg("--no-quorum-intersection")
+        .arg("--format")
+        .arg("csv")
+        .arg("node-rank")
+        .arg("--lscc-only");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("4,,0.0,5\n5,,0.0,5"));
+    Ok(())
+}
+
+#[test]
+fn node_rank_with_analyzer_pagerank_backend() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("test_data/trivial.json")
+        .arg("node-rank")
+        .arg("--pagerank-backend")
+        .arg("analyzer");
+    cmd.assert().success().stdout(predicate::str::contains(
+        "List of Rankings as (NodeId, PK, Score, Rank):",
+    ));
+    Ok(())
+}
+
+#[test]
+fn approximation_output_contains_passed_seed() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("--format")
+        .arg("json")
+        .arg("test_data/trivial.json")
+        .arg("power-index-approx")
+        .arg("100")
+        .arg("--seed")
+        .arg("42");
+    let output: serde_json::Value = serde_json::from_slice(&cmd.output()?.stdout)?;
+    assert_eq!(42, output["nodes"][0]["seed"]);
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("distribute")
+        .arg("test_data/trivial.json")
+        .arg("power-index-approx")
+        .arg("100")
+        .arg("--seed")
+        .arg("42");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Seed: 42"));
+    Ok(())
+}
+
+#[test]
+fn seed_derived_from_fbas_is_stable() -> Result<(), Box<dyn std::error::Error>> {
+    let run = || -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+        let mut cmd = Command::cargo_bin("reward_distributor")?;
+        cmd.arg("rank")
+            .arg("--format")
+            .arg("json")
+            .arg("test_data/trivial.json")
+            .arg("power-index-approx")
+            .arg("100")
+            .arg("--seed-from-fbas");
+        Ok(serde_json::from_slice(&cmd.output()?.stdout)?)
+    };
+    let (first, second) = (run()?, run()?);
+    assert!(first["nodes"][0]["seed"].is_u64());
+    assert_eq!(first, second);
+    Ok(())
+}
+
+#[test]
+fn approximation_csv_output_contains_confidence_intervals() -> Result<(), Box<dyn std::error::Error>>
+{
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("--format")
+        .arg("csv")
+        .arg("test_data/paper_example.json")
+        .arg("power-index-approx")
+        .arg("100")
+        .arg("--seed")
+        .arg("1")
+        .arg("--with-ci");
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let output = String::from_utf8(output)?;
+    let mut lines = output.lines();
+    assert_eq!(
+        Some("node_id,public_key,score,rank,seed,ci_low,ci_high"),
+        lines.next()
+    );
+    for line in lines {
+        let fields: Vec<f64> = line
+            .rsplitn(3, ',')
+            .take(2)
+            .map(|f| f.parse().unwrap())
+            .collect();
+        assert!(fields[1] <= fields[0]);
+    }
+    Ok(())
+}
+
+#[test]
+fn dist_command_sorts_ascending() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("distribute")
+        .arg("--ascending")
+        .arg("test_data/paper_example.json")
+        .arg("power-index-enum");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Rank):\n [(1, "));
+    Ok(())
+}
+
+#[cfg(feature = "measurements")]
+#[test]
+fn performance_tests_record_nan_on_timeout() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("performance_tests")?;
+    cmd.arg("-m")
+        .arg("20")
+        .arg("-r")
+        .arg("1")
+        .arg("--timeout-secs")
+        .arg("1")
+        .arg("nonsymmetric")
+        .arg("power-index-enum");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("20,0,NaN"));
+    Ok(())
+}
+
+#[cfg(feature = "measurements")]
+#[test]
+fn performance_tests_retry_timed_out_runs_on_update() -> Result<(), Box<dyn std::error::Error>> {
+    let file_path = std::env::temp_dir().join(format!(
+        "fbas_reward_distributor_timed_out_{}.csv",
+        std::process::id()
+    ));
+    std::fs::write(
+        &file_path,
+        "top_tier_size,run,duration\n1,0,0.5\n2,0,NaN\n3,0,NaN\n",
+    )?;
+    let mut cmd = Command::cargo_bin("performance_tests")?;
+    cmd.arg("-m")
+        .arg("2")
+        .arg("-r")
+        .arg("1")
+        .arg("-u")
+        .arg("-o")
+        .arg(&file_path)
+        .arg("nonsymmetric")
+        .arg("node-rank");
+    cmd.assert().success();
+    let lines: Vec<String> = std::fs::read_to_string(&file_path)?
+        .lines()
+        .map(String::from)
+        .collect();
+    std::fs::remove_file(&file_path)?;
+    assert_eq!("1,0,0.5", lines[1]);
+    assert!(lines[2].starts_with("2,0,") && !lines[2].ends_with("NaN"));
+    // outside of the requested sizes, timed out runs are kept as they are
+    assert_eq!("3,0,NaN", lines[3]);
+    Ok(())
+}
+
+/// Unanimity of 24 nodes. Node 0 is in an inner quorum set so that the power indices can only be
+/// enumerated, which takes much longer than a second.
+fn large_unanimity_fbas_json() -> String {
+    let validators: Vec<String> = (0..24).map(|i| format!("\"n{i}\"")).collect();
+    let flat = format!(
+        r#"{{ "threshold": 24, "validators": [{}] }}"#,
+        validators.join(",")
+    );
+    let nested = format!(
+        r#"{{ "threshold": 24, "validators": [{}],
+            "innerQuorumSets": [{{ "threshold": 1, "validators": ["n0"] }}] }}"#,
+        validators[1..].join(",")
+    );
+    let nodes: Vec<String> = (0..24)
+        .map(|i| {
+            let qset = if i == 0 { &nested } else { &flat };
+            format!(r#"{{ "publicKey": "n{i}", "quorumSet": {qset} }}"#)
+        })
+        .collect();
+    format!("[{}]", nodes.join(","))
+}
+
+#[test]
+fn exact_enumeration_falls_back_to_approximation_on_timeout(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("power-index-enum")
+        .arg("--exact-timeout-secs")
+        .arg("1")
+        .write_stdin(large_unanimity_fbas_json());
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "falling back to power-index-approx",
+        ))
+        .stdout(predicate::str::contains("The scores are approximate."));
+    Ok(())
+}
+
+#[test]
+fn exact_enumeration_rejects_large_top_tier() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("power-index-enum")
+        .arg("--max-nodes")
+        .arg("20")
+        .write_stdin(large_unanimity_fbas_json());
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "Use power-index-approx instead or raise the limit with --max-nodes",
+    ));
+
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("power-index-enum")
+        .arg("--max-nodes")
+        .arg("0")
+        .arg("--exact-timeout-secs")
+        .arg("1")
+        .write_stdin(large_unanimity_fbas_json());
+    cmd.assert().success().stdout(predicate::str::contains(
+        "falling back to power-index-approx",
+    ));
+    Ok(())
+}
+
+#[test]
+fn rank_command_warns_about_unreachable_nodes() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("test_data/correct.json")
+        .arg("node-rank");
+    cmd.assert().success().stderr(predicate::str::contains(
+        "Nodes outside every minimal quorum",
+    ));
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("test_data/trivial.json")
+        .arg("node-rank");
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("Nodes outside every minimal quorum").not());
+    // opting out of the QI analysis also skips finding the minimal quorums for the warning
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("--no-quorum-intersection")
+        .arg("--skip-qi-report")
+        .arg("test_data/correct.json")
+        .arg("node-rank");
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("Nodes outside every minimal quorum").not());
+    Ok(())
+}
+
+#[test]
+fn dist_command_prints_reward_summary() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("distribute")
+        .arg("-r")
+        .arg("3")
+        .arg("test_data/trivial.json")
+        .arg("power-index-enum");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Reward summary as (Total, Mean, Median, Top tier mean):",
+        ))
+        .stdout(predicate::str::contains(", 0.999, "))
+        .stdout(predicate::str::contains("Top tier reward fraction: 1.000"));
+    Ok(())
+}
+
+#[test]
+fn rank_command_reads_base64_from_stdin() -> Result<(), Box<dyn std::error::Error>> {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    let encoded = STANDARD.encode(std::fs::read("test_data/trivial.json")?);
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank").arg("--base64").arg("node-rank");
+    cmd.write_stdin(encoded)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("(0, \"\", 0.666, 1)"))
+        .stdout(predicate::str::contains("(1, \"\", 0.666, 1)"))
+        .stdout(predicate::str::contains("(2, \"\", 0.666, 1)"));
+    Ok(())
+}
+
+#[test]
+fn rank_command_rejects_invalid_base64() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("--nodes-b64")
+        .arg("not base64!")
+        .arg("node-rank");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Input is not valid base64"));
+    Ok(())
+}
+
+#[test]
+fn rank_command_prints_decentralization_index() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("test_data/trivial.json")
+        .arg("power-index-enum");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Decentralization index: 1.000"));
+    Ok(())
+}
+
+#[test]
+fn rank_command_reports_quorum_intersection() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("--no-quorum-intersection")
+        .arg("test_data/trivial.json")
+        .arg("node-rank");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Quorum intersection: yes"));
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("--skip-qi-report")
+        .arg("test_data/trivial.json")
+        .arg("node-rank");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Quorum intersection").not());
+    Ok(())
+}
+
+#[test]
+fn rank_command_prints_symmetric_groups() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("--groups")
+        .arg("test_data/paper_example.json")
+        .arg("node-rank");
+    cmd.assert().success().stdout(predicate::str::contains(
+        "Nodes with the same quorum set:\n [[0], [1, 2], [3, 4]]",
+    ));
+    Ok(())
+}
+
+#[test]
+fn rank_command_prints_minimal_quorums() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("--minimal-quorums")
+        .arg("test_data/trivial.json")
+        .arg("node-rank");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Minimal quorums:\n [["));
+    Ok(())
+}
+
+#[test]
+fn rank_command_prints_quorum_membership() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("--quorum-membership")
+        .arg("test_data/paper_example.json")
+        .arg("node-rank");
+    cmd.assert().success().stdout(predicate::str::contains(
+        "Minimal quorums each node belongs to as (PK, Quorum indices):\n [(\"node0\", [0, 1]), (\"node1\", [0])",
+    ));
+    Ok(())
+}
+
+#[test]
+fn rank_command_only_estimates_enumeration() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("test_data/paper_example.json")
+        .arg("power-index-enum")
+        .arg("--estimate");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Estimated cost of the exact enumeration: 2^5 = 32 coalitions",
+        ))
+        .stdout(predicate::str::contains("List of Rankings").not());
+    Ok(())
+}
+
+#[test]
+fn rank_command_csv_output_contains_quorum_set_hashes() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("--format")
+        .arg("csv")
+        .arg("--qset-hash")
+        .arg("test_data/trivial.json")
+        .arg("node-rank");
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let output = String::from_utf8(output)?;
+    let mut lines = output.lines();
+    assert_eq!(
+        Some("node_id,public_key,score,rank,quorum_set_hash"),
+        lines.next()
+    );
+    let hashes: Vec<&str> = lines.map(|l| l.rsplit(',').next().unwrap()).collect();
+    assert_eq!(3, hashes.len());
+    assert!(hashes.iter().all(|h| h.len() == 64 && *h == hashes[0]));
+    Ok(())
+}
+
+#[test]
+fn rank_command_markdown_output() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("--format")
+        .arg("markdown")
+        .arg("test_data/trivial.json")
+        .arg("node-rank");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("| NodeId |"))
+        .stdout(predicate::str::contains("\n|---"));
+    Ok(())
+}
+
+#[test]
+fn dist_command_json_output_has_schema_version() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("distribute")
+        .arg("--format")
+        .arg("json")
+        .arg("test_data/paper_example.json")
+        .arg("power-index-enum");
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let output: serde_json::Value = serde_json::from_slice(&output)?;
+    assert_eq!(1, output["schema_version"]);
+    assert_eq!("power-index-enum", output["algorithm"]);
+    assert_eq!(5, output["nodes"].as_array().unwrap().len());
+    Ok(())
+}
+
+#[test]
+fn rank_command_ranks_time_series() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("--series")
+        .arg("test_data/series")
+        .arg("power-index-enum");
+    cmd.assert().success().stdout(predicate::str::contains(
+        r#"("node2", [("2023-01-01", 0.333), ("2023-02-01", 0.0)])"#,
+    ));
+    Ok(())
+}
+
+#[test]
+fn rank_command_prints_absolute_banzhaf_index() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("test_data/trivial.json")
+        .arg("banzhaf-index-enum")
+        .arg("--absolute");
+    cmd.assert().success().stdout(predicate::str::contains(
+        r#"[(0, "", 0.5, 1), (1, "", 0.5, 1), (2, "", 0.5, 1)]"#,
+    ));
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("test_data/trivial.json")
+        .arg("banzhaf-index-enum");
+    cmd.assert().success().stdout(predicate::str::contains(
+        r#"[(0, "", 0.333, 1), (1, "", 0.333, 1), (2, "", 0.333, 1)]"#,
+    ));
+    Ok(())
+}
+
+#[test]
+fn validate_command_passes_trivial_fbas() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("validate").arg("test_data/trivial.json");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Quorum intersection: pass"))
+        .stdout(predicate::str::contains("No empty quorum sets []: pass"))
+        .stdout(predicate::str::contains("All checks passed"));
+    Ok(())
+}
+
+#[test]
+fn export_game_lists_winning_coalitions() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("export-game").arg("test_data/trivial.json");
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let game: serde_json::Value = serde_json::from_slice(&output)?;
+    assert_eq!(
+        serde_json::json!([[0, 1], [0, 2], [1, 2], [0, 1, 2]]),
+        game["winning_coalitions"]
+    );
+    Ok(())
+}
+
+#[test]
+fn export_dot_labels_nodes_with_scores() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("export-dot")
+        .arg("test_data/trivial.json")
+        .arg("power-index-enum");
+    let output = String::from_utf8(cmd.assert().success().get_output().stdout.clone())?;
+    assert!(output.starts_with("digraph fbas {"));
+    assert_eq!(3, output.matches("\\n0.333\"").count());
+    Ok(())
+}
+
+#[test]
+fn rank_merged_fbas() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("test_data/paper_example.json")
+        .arg("--merge-with")
+        .arg("test_data/trivial.json")
+        .arg("--no-quorum-intersection")
+        .arg("--format")
+        .arg("csv")
+        .arg("node-rank");
+    let output = String::from_utf8(cmd.assert().success().get_output().stdout.clone())?;
+    // header and one line per node of both FBASs
+    assert_eq!(9, output.lines().count());
+    Ok(())
+}
+
+#[test]
+fn rank_command_prints_controlling_set() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("test_data/paper_example.json")
+        .arg("--controlling")
+        .arg("0.5")
+        .arg("power-index-enum");
+    cmd.assert().success().stdout(predicate::str::contains(
+        "Controlling set (> 0.5):\n [0, 1]",
+    ));
+    Ok(())
+}
+
+#[test]
+fn approximation_prints_score_sum_deviation() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("test_data/trivial.json")
+        .arg("power-index-approx")
+        .arg("100")
+        .arg("--seed")
+        .arg("1");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Score sum deviation: "));
+    Ok(())
+}
+
+#[test]
+fn distribution_in_basis_points() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("distribute")
+        .arg("test_data/trivial.json")
+        .arg("--format")
+        .arg("basis-points")
+        .arg("power-index-enum");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("0,,0.333,3334.0,1"))
+        .stdout(predicate::str::contains("2,,0.333,3333.0,1"));
+    Ok(())
+}
+
+#[test]
+fn nodes_inactive_in_status_file_are_not_ranked() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("test_data/trivial.json")
+        .arg("--status")
+        .arg("test_data/trivial_status.json")
+        .arg("--format")
+        .arg("csv")
+        .arg("node-rank");
+    let output = String::from_utf8(cmd.assert().success().get_output().stdout.clone())?;
+    // header and the two active nodes
+    assert_eq!(3, output.lines().count());
+    Ok(())
+}
+
+#[test]
+fn apportion_external_scores() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("apportion")
+        .arg("test_data/external_scores.csv")
+        .arg("-r")
+        .arg("7")
+        .arg("--apportionment")
+        .arg("hamilton")
+        .arg("--format")
+        .arg("csv");
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let rewards: Vec<f64> = String::from_utf8(output)?
+        .lines()
+        .skip(1)
+        .map(|line| line.split(',').nth(3).unwrap().parse().unwrap())
+        .collect();
+    assert_eq!(3, rewards.len());
+    assert_eq!(7.0, rewards.iter().sum::<f64>());
+    Ok(())
+}
+
+#[test]
+fn profile_lists_phases() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("test_data/paper_example.json")
+        .arg("--profile")
+        .arg("power-index-enum");
+    let output = cmd.assert().success().get_output().stderr.clone();
+    let stderr = String::from_utf8(output)?;
+    let profile = stderr
+        .split("Profile as (Phase, Seconds):\n")
+        .nth(1)
+        .expect("No profile printed");
+    let phases: Vec<(&str, f64)> = profile
+        .lines()
+        .map(|line| {
+            let (phase, secs) = line.trim().split_once(": ").unwrap();
+            (phase, secs.parse().unwrap())
+        })
+        .collect();
+    assert_eq!(
+        vec!["loading", "top tier", "ranking"],
+        phases.iter().map(|p| p.0).collect::<Vec<&str>>()
+    );
+    assert!(phases.iter().all(|p| p.1 >= 0.0));
+    Ok(())
+}
+
+#[test]
+fn distribution_is_compared_with_equal_split() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("distribute")
+        .arg("test_data/trivial.json")
+        .arg("-r")
+        .arg("3")
+        .arg("--compare-equal")
+        .arg("power-index-enum");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Comparison with an equal split as (NodeId, PK, Reward, Equal share, Difference):",
+        ))
+        .stdout(predicate::str::contains("(1, \"\", 0.999, 1.0, -0.001"));
+    Ok(())
+}
+
+#[test]
+fn sqrt_distribution_flattens_rewards() -> Result<(), Box<dyn std::error::Error>> {
+    let top_reward = |sqrt: bool| -> Result<f64, Box<dyn std::error::Error>> {
+        let mut cmd = Command::cargo_bin("reward_distributor")?;
+        cmd.arg("distribute")
+            .arg("--format")
+            .arg("json")
+            .arg("test_data/paper_example.json");
+        if sqrt {
+            cmd.arg("--sqrt");
+        }
+        cmd.arg("power-index-enum");
+        let output: serde_json::Value = serde_json::from_slice(&cmd.output()?.stdout)?;
+        let total: f64 = output["nodes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|node| node["reward"].as_f64().unwrap())
+            .sum();
+        assert!((total - 1.0).abs() < 1e-9);
+        Ok(output["nodes"][0]["reward"].as_f64().unwrap())
+    };
+    assert!(top_reward(true)? < top_reward(false)?);
+    Ok(())
+}
+
+#[test]
+fn sqrt_distribution_in_basis_points_and_csv() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("distribute")
+        .arg("test_data/paper_example.json")
+        .arg("--sqrt")
+        .arg("--format")
+        .arg("basis-points")
+        .arg("power-index-enum");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("0,,0.466,3188.0,1"))
+        .stdout(predicate::str::contains("1,,0.133,1703.0,2"));
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("distribute")
+        .arg("test_data/paper_example.json")
+        .arg("--sqrt")
+        .arg("-r")
+        .arg("10000")
+        .arg("--format")
+        .arg("csv")
+        .arg("power-index-enum");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("0,,0.466,3187.82,1\n"))
+        .stdout(predicate::str::contains("1,,0.133,1703.045,2"));
+    Ok(())
+}
+
+#[test]
+fn lack_of_quorum_intersection_is_reported_with_disjoint_quorums(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("test_data/two_clusters.json")
+        .arg("node-rank");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "FBAS lacks quorum intersection! These quorums are disjoint:",
+        ))
+        .stderr(predicate::str::contains(r#"["B0", "B1"]"#));
+    Ok(())
+}
+
+#[test]
+fn explain_identifies_leaf_node_as_dummy() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("test_data/leaf.json")
+        .arg("--explain")
+        .arg("3")
+        .arg("node-rank");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Node 3 is a dummy player: it is never critical in any winning coalition.",
+        ))
+        .stdout(predicate::str::contains(
+            "It belongs to no quorum set, so it receives no NodeRank.",
+        ));
+    Ok(())
+}
+
+#[test]
+fn apportion_with_public_keys_of_fbas() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("apportion")
+        .arg("test_data/external_scores.csv")
+        .arg("--nodes")
+        .arg("test_data/trivial.json")
+        .arg("-r")
+        .arg("100")
+        .arg("--format")
+        .arg("csv");
+    cmd.assert().success().stdout(predicate::str::contains(
+        "0,GCGB2S2KGYARPVIA37HYZXVRM2YZUEXA6S33ZU5BUDC6THSB62LZSTYH,0.45,45.0,1",
+    ));
+    Ok(())
+}
+
+#[test]
+fn all_algorithms_are_compared() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("all")
+        .arg("test_data/trivial.json")
+        .arg("--seed")
+        .arg("1");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "List of Scores as (NodeId, PK, [NodeRank, Exact Shapley-Shubik, Approximate Shapley-Shubik]):",
+        ))
+        .stdout(predicate::str::contains(
+            "Spearman correlation (NodeRank, Exact Shapley-Shubik): 1.000",
+        ))
+        .stdout(predicate::str::contains(
+            "Spearman correlation (NodeRank, Approximate Shapley-Shubik):",
+        ))
+        .stdout(predicate::str::contains(
+            "Spearman correlation (Exact Shapley-Shubik, Approximate Shapley-Shubik):",
+        ));
+    Ok(())
+}
+
+#[test]
+fn scores_scaled_to_maximum() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("test_data/paper_example.json")
+        .arg("--scale")
+        .arg("max")
+        .arg("--format")
+        .arg("csv")
+        .arg("power-index-enum");
+    cmd.assert().success().stdout(predicate::str::contains(
+        "node_id,public_key,score,rank\n0,,1.0,1\n",
+    ));
+    Ok(())
+}
+
+#[test]
+fn scores_and_confidence_intervals_scaled_to_maximum() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("test_data/paper_example.json")
+        .arg("--scale")
+        .arg("max")
+        .arg("power-index-approx")
+        .arg("2000")
+        .arg("--seed")
+        .arg("1")
+        .arg("--with-ci");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("[(0, \"\", 1.0, 1, 0.95"))
+        .stdout(predicate::str::contains("(2, \"\", 0.292, 2, 0.26"));
+    let mut cmd = Command::cargo_bin("reward_distributor")?;
+    cmd.arg("rank")
+        .arg("test_data/paper_example.json")
+        .arg("--scale")
+        .arg("max")
+        .arg("--format")
+        .arg("csv")
+        .arg("power-index-approx")
+        .arg("2000")
+        .arg("--seed")
+        .arg("1")
+        .arg("--with-ci");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\n0,,1.0,1,1,0.95"))
+        .stdout(predicate::str::contains("\n2,,0.292,2,1,0.26"));
+    Ok(())
+}